@@ -0,0 +1,356 @@
+use crate::PI;
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+// an angle, stored in radians
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Angle(f64);
+
+// what a bare number (no h/°/rad and not sexagesimal) is read as
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AngleUnit {
+    Degrees,
+    Radians,
+}
+
+impl AngleUnit {
+    pub fn angle(&self, value: f64) -> Angle {
+        match self {
+            AngleUnit::Degrees => Angle::from_degrees(value),
+            AngleUnit::Radians => Angle::from_radians(value),
+        }
+    }
+}
+
+// sign and sexagesimal fields of an angle, seconds not rounded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sexagesimal {
+    pub negative: bool,
+    pub whole: u64,
+    pub minutes: u64,
+    pub seconds: f64,
+}
+
+impl Sexagesimal {
+    fn of(value: f64) -> Sexagesimal {
+        let magnitude: f64 = value.abs();
+        let whole: f64 = magnitude.floor();
+        let minutes: f64 = ((magnitude - whole) * 60.0).floor();
+        Sexagesimal {
+            negative: value < 0.0,
+            whole: whole as u64,
+            minutes: minutes as u64,
+            seconds: ((magnitude - whole) * 60.0 - minutes) * 60.0,
+        }
+    }
+}
+
+impl Angle {
+    pub fn from_radians(radians: f64) -> Angle {
+        Angle(radians)
+    }
+
+    pub fn from_degrees(degrees: f64) -> Angle {
+        Angle(degrees * (PI / 180.0))
+    }
+
+    pub fn from_arcminutes(arcminutes: f64) -> Angle {
+        Angle::from_degrees(arcminutes / 60.0)
+    }
+
+    pub fn from_arcseconds(arcseconds: f64) -> Angle {
+        Angle::from_degrees(arcseconds / 3600.0)
+    }
+
+    pub fn from_hours(hours: f64) -> Angle {
+        Angle::from_degrees(hours * 15.0)
+    }
+
+    // -0°30′ is from_dms(true, 0, 30, 0.0), the sign is separate so it survives a zero degree field
+    pub fn from_dms(negative: bool, degrees: f64, minutes: f64, seconds: f64) -> Angle {
+        let magnitude: Angle = Angle::from_degrees(degrees + minutes / 60.0 + seconds / 3600.0);
+        if negative { -magnitude } else { magnitude }
+    }
+
+    pub fn from_hms(hours: f64, minutes: f64, seconds: f64) -> Angle {
+        Angle::from_hours(hours + minutes / 60.0 + seconds / 3600.0)
+    }
+
+    pub fn radians(&self) -> f64 {
+        self.0
+    }
+
+    pub fn degrees(&self) -> f64 {
+        self.0 * (180.0 / PI)
+    }
+
+    pub fn arcminutes(&self) -> f64 {
+        self.degrees() * 60.0
+    }
+
+    pub fn arcseconds(&self) -> f64 {
+        self.degrees() * 3600.0
+    }
+
+    pub fn hours(&self) -> f64 {
+        self.degrees() / 15.0
+    }
+
+    // multiples of π, e.g. 0.5 for a right angle
+    pub fn pi_radians(&self) -> f64 {
+        self.0 / PI
+    }
+
+    pub fn dms(&self) -> Sexagesimal {
+        Sexagesimal::of(self.degrees())
+    }
+
+    pub fn hms(&self) -> Sexagesimal {
+        Sexagesimal::of(self.hours())
+    }
+
+    // [0, 360°)
+    pub fn normalized(&self) -> Angle {
+        Angle(self.0.rem_euclid(2.0 * PI))
+    }
+
+    // (-180°, 180°]
+    pub fn wrapped(&self) -> Angle {
+        let angle: f64 = self.0.rem_euclid(2.0 * PI);
+        Angle(if angle > PI { angle - 2.0 * PI } else { angle })
+    }
+
+    // clamps into [-90°, 90°], e.g. a latitude after rounding error
+    pub fn clamped_latitude(&self) -> Angle {
+        Angle(self.0.clamp(-PI / 2.0, PI / 2.0))
+    }
+
+    pub fn sin(&self) -> f64 {
+        self.0.sin()
+    }
+
+    pub fn cos(&self) -> f64 {
+        self.0.cos()
+    }
+
+    pub fn tan(&self) -> f64 {
+        self.0.tan()
+    }
+
+    pub fn abs(&self) -> Angle {
+        Angle(self.0.abs())
+    }
+
+    // 83.633° -> 05h34m31.92s, wrapped into [0h, 24h)
+    pub fn format_hms(&self) -> String {
+        let centiseconds: i64 = (self.normalized().hours() * 360000.0).round() as i64 % 8640000;
+        format!(
+            "{:02}h{:02}m{:02}.{:02}s",
+            centiseconds / 360000,
+            centiseconds / 6000 % 60,
+            centiseconds / 100 % 60,
+            centiseconds % 100
+        )
+    }
+
+    // -5.391° -> -05°23′27.6″
+    pub fn format_dms(&self) -> String {
+        let degrees: f64 = self.degrees();
+        let sign: char = if degrees < 0.0 { '-' } else { '+' };
+        let deciseconds: i64 = (degrees.abs() * 36000.0).round() as i64;
+        format!(
+            "{}{:02}°{:02}′{:02}.{}″",
+            sign,
+            deciseconds / 36000,
+            deciseconds / 600 % 60,
+            deciseconds / 10 % 60,
+            deciseconds % 10
+        )
+    }
+
+    // an hour angle, -02h13m05.00s, wrapped into (-12h, 12h]
+    pub fn format_signed_hms(&self) -> String {
+        let wrapped: Angle = self.wrapped();
+        if wrapped.0 < 0.0 {
+            format!("-{}", (-wrapped).format_hms())
+        } else {
+            format!("+{}", wrapped.format_hms())
+        }
+    }
+
+    // the decimal formats go through tidy, so π rad prints as 180° rather than 180.00000000000003°
+    pub fn format_degrees(&self) -> String {
+        format!("{}°", tidy(self.degrees()))
+    }
+
+    pub fn format_radians(&self) -> String {
+        format!("{} rad", tidy(self.0))
+    }
+
+    pub fn format_pi_radians(&self) -> String {
+        format!("{}π rad", tidy(self.pi_radians()))
+    }
+
+    pub fn format_arcminutes(&self) -> String {
+        format!("{}′", tidy(self.arcminutes()))
+    }
+
+    pub fn format_arcseconds(&self) -> String {
+        format!("{}″", tidy(self.arcseconds()))
+    }
+}
+
+// rounds to 15 significant digits, dropping the last bit or two of noise left by unit conversions
+pub fn tidy(value: f64) -> f64 {
+    format!("{:.14e}", value).parse().unwrap_or(value)
+}
+
+// degrees, e.g. 12.5°
+impl fmt::Display for Angle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}°", self.degrees())
+    }
+}
+
+impl Add for Angle {
+    type Output = Angle;
+    fn add(self, other: Angle) -> Angle {
+        Angle(self.0 + other.0)
+    }
+}
+
+impl Sub for Angle {
+    type Output = Angle;
+    fn sub(self, other: Angle) -> Angle {
+        Angle(self.0 - other.0)
+    }
+}
+
+impl AddAssign for Angle {
+    fn add_assign(&mut self, other: Angle) {
+        self.0 += other.0;
+    }
+}
+
+impl SubAssign for Angle {
+    fn sub_assign(&mut self, other: Angle) {
+        self.0 -= other.0;
+    }
+}
+
+impl Neg for Angle {
+    type Output = Angle;
+    fn neg(self) -> Angle {
+        Angle(-self.0)
+    }
+}
+
+impl Mul<f64> for Angle {
+    type Output = Angle;
+    fn mul(self, factor: f64) -> Angle {
+        Angle(self.0 * factor)
+    }
+}
+
+impl Mul<Angle> for f64 {
+    type Output = Angle;
+    fn mul(self, angle: Angle) -> Angle {
+        Angle(self * angle.0)
+    }
+}
+
+impl Div<f64> for Angle {
+    type Output = Angle;
+    fn div(self, divisor: f64) -> Angle {
+        Angle(self.0 / divisor)
+    }
+}
+
+// the ratio of two angles
+impl Div for Angle {
+    type Output = f64;
+    fn div(self, other: Angle) -> f64 {
+        self.0 / other.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_round_trips() {
+        assert_eq!(Angle::from_degrees(180.0).radians(), PI);
+        assert_eq!(Angle::from_radians(PI).degrees(), 180.0);
+        assert!((Angle::from_arcseconds(3600.0).degrees() - 1.0).abs() < 1e-15);
+        assert!((Angle::from_degrees(0.5).arcseconds() - 1800.0).abs() < 1e-9);
+        assert!((Angle::from_arcminutes(90.0).degrees() - 1.5).abs() < 1e-15);
+        assert!((Angle::from_hours(6.0).degrees() - 90.0).abs() < 1e-12);
+        assert!((Angle::from_degrees(45.0).hours() - 3.0).abs() < 1e-15);
+        assert!((Angle::from_degrees(90.0).pi_radians() - 0.5).abs() < 1e-15);
+    }
+
+    #[test]
+    fn sexagesimal_constructors() {
+        let ra: Angle = Angle::from_hms(5.0, 34.0, 31.94);
+        assert!((ra.degrees() - 15.0 * (5.0 + 34.0 / 60.0 + 31.94 / 3600.0)).abs() < 1e-12);
+        let dec: Angle = Angle::from_dms(true, 0.0, 30.0, 0.0);
+        assert!((dec.degrees() + 0.5).abs() < 1e-15);
+        let parts: Sexagesimal = Angle::from_dms(true, 5.0, 23.0, 27.6).dms();
+        assert!(parts.negative);
+        assert_eq!((parts.whole, parts.minutes), (5, 23));
+        assert!((parts.seconds - 27.6).abs() < 1e-9);
+        let parts: Sexagesimal = ra.hms();
+        assert_eq!((parts.whole, parts.minutes), (5, 34));
+        assert!((parts.seconds - 31.94).abs() < 1e-9);
+    }
+
+    #[test]
+    fn normalizing_and_wrapping() {
+        assert!((Angle::from_degrees(-30.0).normalized().degrees() - 330.0).abs() < 1e-12);
+        assert!((Angle::from_degrees(725.0).normalized().degrees() - 5.0).abs() < 1e-12);
+        assert!((Angle::from_degrees(350.0).wrapped().degrees() + 10.0).abs() < 1e-12);
+        assert!((Angle::from_degrees(180.0).wrapped().degrees() - 180.0).abs() < 1e-12);
+        assert!((Angle::from_degrees(-190.0).wrapped().degrees() - 170.0).abs() < 1e-12);
+        assert_eq!(Angle::from_degrees(91.0).clamped_latitude().degrees(), 90.0);
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(Angle::from_degrees(83.633).format_hms(), "05h34m31.92s");
+        assert_eq!(Angle::from_degrees(359.99999999).format_hms(), "00h00m00.00s");
+        assert_eq!(Angle::from_degrees(-5.391).format_dms(), "-05°23′27.6″");
+        assert_eq!(Angle::from_degrees(22.0145).format_dms(), "+22°00′52.2″");
+        assert_eq!(Angle::from_degrees(-30.0).format_signed_hms(), "-02h00m00.00s");
+        assert_eq!(Angle::from_degrees(350.0).format_signed_hms(), "-00h40m00.00s");
+        assert_eq!(Angle::from_degrees(15.0).format_signed_hms(), "+01h00m00.00s");
+        assert_eq!(Angle::from_radians(0.5).format_radians(), "0.5 rad");
+        assert_eq!(Angle::from_radians(PI).format_pi_radians(), "1π rad");
+        assert_eq!(Angle::from_radians(PI).format_degrees(), "180°");
+        assert_eq!(Angle::from_radians(PI).to_string(), "180°");
+        assert_eq!(Angle::from_degrees(0.5).format_arcminutes(), "30′");
+        assert_eq!(Angle::from_radians(0.17 * PI).format_degrees(), "30.6°");
+        assert_eq!(Angle::from_degrees(2.2).format_pi_radians(), "0.0122222222222222π rad");
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a: Angle = Angle::from_degrees(30.0);
+        let b: Angle = Angle::from_degrees(15.0);
+        assert!(((a + b).degrees() - 45.0).abs() < 1e-12);
+        assert!(((a - b).degrees() - 15.0).abs() < 1e-12);
+        assert!(((-a).degrees() + 30.0).abs() < 1e-12);
+        assert!(((a * 2.0).degrees() - 60.0).abs() < 1e-12);
+        assert!(((2.0 * a).degrees() - 60.0).abs() < 1e-12);
+        assert!(((a / 3.0).degrees() - 10.0).abs() < 1e-12);
+        assert!((a / b - 2.0).abs() < 1e-15);
+        let mut c: Angle = a;
+        c += b;
+        c -= a;
+        assert!(((c - b).radians()).abs() < 1e-15);
+        assert!((a.sin() - 0.5).abs() < 1e-15);
+        assert!(a > b && (-a).abs() == a);
+        assert_eq!(AngleUnit::Degrees.angle(180.0).radians(), PI);
+        assert_eq!(AngleUnit::Radians.angle(1.0).radians(), 1.0);
+    }
+}
@@ -0,0 +1,178 @@
+// annual parallax and aberration: catalogue (J2000/ICRS) positions as seen from the moving
+// Earth, with the Earth on a Keplerian orbit (Meeus, Astronomical Algorithms, ch. 23 and 25)
+
+use crate::coords::{mat_vec, rot1, transpose, EquatorialCoord};
+use crate::motion::MAS_TO_RAD;
+use crate::precession::{centuries_since_j2000, mean_obliquity, precession_matrix};
+use crate::time::utc_to_tt;
+use crate::trace::{Derived, Trace};
+
+// Gaussian gravitational constant (AU^3/2 per day) and the speed of light in AU/day
+pub const GAUSS_K: f64 = 0.01720209895;
+pub const C_AU_PER_DAY: f64 = 173.1446326846693;
+
+// the heliocentric Earth, position in AU and velocity in AU/day on the mean equator and equinox
+// of date, plus the geometric longitude and distance of the Sun it came from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EarthOrbit {
+    pub sun_longitude: f64,
+    pub distance: f64,
+    pub eccentricity: f64,
+    pub perihelion: f64,
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+}
+
+// Meeus ch. 25 low accuracy solar theory, ~0.01° in longitude
+pub fn earth_orbit(jd_tt: f64) -> EarthOrbit {
+    let t: f64 = centuries_since_j2000(jd_tt);
+    let mean_lon: f64 = 280.46646 + t * (36000.76983 + t * 0.0003032);
+    let m: f64 = (357.52911 + t * (35999.05029 - t * 0.0001537)).to_radians();
+    let e: f64 = 0.016708634 - t * (0.000042037 + t * 0.0000001267);
+    let centre: f64 = (1.914602 - t * (0.004817 + t * 0.000014)) * m.sin()
+        + (0.019993 - t * 0.000101) * (2.0 * m).sin()
+        + 0.000289 * (3.0 * m).sin();
+    let sun_longitude: f64 = (mean_lon + centre).rem_euclid(360.0).to_radians();
+    let anomaly: f64 = m + centre.to_radians();
+    let a: f64 = 1.000001018;
+    let distance: f64 = a * (1.0 - e * e) / (1.0 + e * anomaly.cos());
+    // longitude of the Earth's perihelion
+    let perihelion: f64 = (102.93735 + t * (1.71946 + t * 0.00046)).to_radians();
+
+    // the Earth is opposite the Sun. its velocity is k/√p along the direction of motion on a
+    // circle, less e times the same at perihelion
+    let (sl, cl): (f64, f64) = sun_longitude.sin_cos();
+    let position: [f64; 3] = [-distance * cl, -distance * sl, 0.0];
+    let speed: f64 = GAUSS_K / (a * (1.0 - e * e)).sqrt();
+    let velocity: [f64; 3] = [speed * (sl - e * perihelion.sin()), speed * (-cl + e * perihelion.cos()), 0.0];
+    let to_equator: [[f64; 3]; 3] = rot1(-mean_obliquity(t));
+    EarthOrbit {
+        sun_longitude,
+        distance,
+        eccentricity: e,
+        perihelion,
+        position: mat_vec(&to_equator, position),
+        velocity: mat_vec(&to_equator, velocity),
+    }
+}
+
+fn normalized(v: [f64; 3]) -> [f64; 3] {
+    let length: f64 = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    v.map(|x: f64| x / length)
+}
+
+// the direction from the Earth at position (AU) to a star with parallax in mas, instead of from
+// the Sun. both on the same equator and equinox
+pub fn annual_parallax(coord: &EquatorialCoord, parallax_mas: f64, earth_position: [f64; 3]) -> EquatorialCoord {
+    let u: [f64; 3] = coord.to_vector();
+    let p: f64 = parallax_mas * MAS_TO_RAD;
+    EquatorialCoord::from_vector(normalized([0, 1, 2].map(|i: usize| u[i] - p * earth_position[i])))
+}
+
+// the direction displaced towards the Earth's velocity (AU/day), to first order in v/c
+pub fn annual_aberration(coord: &EquatorialCoord, earth_velocity: [f64; 3]) -> EquatorialCoord {
+    let u: [f64; 3] = coord.to_vector();
+    EquatorialCoord::from_vector(normalized([0, 1, 2].map(|i: usize| u[i] + earth_velocity[i] / C_AU_PER_DAY)))
+}
+
+// a star after parallax and then after aberration as well
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnnualPlace {
+    pub parallax: EquatorialCoord,
+    pub apparent: EquatorialCoord,
+}
+
+// J2000/ICRS catalogue position -> where it appears from the Earth at jd_utc, still on the J2000
+// equator so it can be compared with the catalogue (use `precess_coord` for the place of date)
+pub fn annual(coord: &EquatorialCoord, parallax_mas: f64, jd_utc: f64, show_work: bool) -> Derived<AnnualPlace> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+
+    let jd_tt: f64 = utc_to_tt(jd_utc);
+    let t: f64 = centuries_since_j2000(jd_tt);
+    note!(trace, "The Earth's orbit (Meeus ch. 25), t = {} centuries since J2000", t);
+    note!(trace, "M = 357.52911° + 35999.05029°t, e = 0.016708634 - 0.000042037t");
+    note!(trace, "L☉ = 280.46646° + 36000.76983°t + C, R = a(1 - e²) / (1 + e cos(M + C))");
+    let orbit: EarthOrbit = earth_orbit(jd_tt);
+    value!(trace, ["L☉"], ["{}°", orbit.sun_longitude.to_degrees()]);
+    value!(trace, ["R"], ["{} AU", orbit.distance]);
+    note!(trace, "Heliocentric Earth, E = -R(cos(L☉), sin(L☉), 0) and v = k/√(a(1 - e²)) (sin(L☉) - e sin(Π), -cos(L☉) + e cos(Π), 0)");
+    note!(trace, "Π = 102.93735° + 1.71946°t, the longitude of perihelion");
+    note!(trace, "rotated to the equator by ε then back to J2000 by P(t)ᵀ");
+    let to_j2000: [[f64; 3]; 3] = transpose(&precession_matrix(t));
+    let position: [f64; 3] = mat_vec(&to_j2000, orbit.position);
+    let velocity: [f64; 3] = mat_vec(&to_j2000, orbit.velocity);
+    value!(trace, ["E"], ["({}, {}, {}) AU", position[0], position[1], position[2]]);
+    value!(trace, ["v"], ["({}, {}, {}) AU/day", velocity[0], velocity[1], velocity[2]]);
+
+    let u: [f64; 3] = coord.to_vector();
+    value!(trace, ["u"], ["({}, {}, {})", u[0], u[1], u[2]]);
+    note!(trace, "Annual parallax, from the Sun to the Earth");
+    note!(trace, "u' = (u - ϖE) / |u - ϖE|");
+    let after_parallax: EquatorialCoord = annual_parallax(coord, parallax_mas, position);
+    value!(trace, ["α, δ"], ["{}°, {}°", after_parallax.ra.degrees(), after_parallax.dec.degrees()]);
+    note!(trace, "Annual aberration, towards the Earth's motion");
+    note!(trace, "u'' = (u' + v/c) / |u' + v/c|, c = {} AU/day", C_AU_PER_DAY);
+    let apparent: EquatorialCoord = annual_aberration(&after_parallax, velocity);
+    value!(trace, ["α, δ"], ["{}°, {}°", apparent.ra.degrees(), apparent.dec.degrees()]);
+
+    Derived { value: AnnualPlace { parallax: after_parallax, apparent }, trace }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Angle;
+    use crate::precession::ARCSEC_TO_RAD;
+
+    #[test]
+    fn meeus_sun() {
+        // Meeus example 25.a, 1992 October 13.0 TD: ☉ = 199.90988°, R = 0.99766 AU
+        let orbit: EarthOrbit = earth_orbit(2448908.5);
+        assert!((orbit.sun_longitude.to_degrees() - 199.90988).abs() < 1e-4, "got {}", orbit.sun_longitude.to_degrees());
+        assert!((orbit.distance - 0.99766).abs() < 1e-5, "got {}", orbit.distance);
+        // the Earth moves at about 2π AU a year
+        let speed: f64 = orbit.velocity.iter().map(|v: &f64| v * v).sum::<f64>().sqrt();
+        assert!((speed * 365.25 / (2.0 * crate::PI) - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn meeus_theta_persei_aberration() {
+        // Meeus example 23.a, 2028 November 13.19 TD, mean place of date: Δα = +30.045″, Δδ = +6.697″
+        let coord: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(41.5472), Angle::from_degrees(49.3485));
+        let orbit: EarthOrbit = earth_orbit(2462088.69);
+        let out: EquatorialCoord = annual_aberration(&coord, orbit.velocity);
+        let d_ra: f64 = (out.ra - coord.ra).radians() / ARCSEC_TO_RAD;
+        let d_dec: f64 = (out.dec - coord.dec).radians() / ARCSEC_TO_RAD;
+        assert!((d_ra - 30.045).abs() < 0.05, "got {}", d_ra);
+        assert!((d_dec - 6.697).abs() < 0.05, "got {}", d_dec);
+    }
+
+    #[test]
+    fn parallax_ellipse() {
+        // at J2000 the Earth is near (-0.177, 0.887, 0.385) AU (JPL DE405)
+        let orbit: EarthOrbit = earth_orbit(2451545.0);
+        for (got, expected) in orbit.position.iter().zip([-0.177, 0.887, 0.385]) {
+            assert!((got - expected).abs() < 0.005, "got {:?}", orbit.position);
+        }
+        // a star at the ecliptic pole moves on a circle of radius ϖR, 1″ at 1 AU
+        let pole: EquatorialCoord = EquatorialCoord::new(Angle::from_hours(18.0), Angle::from_degrees(90.0 - 23.4392911));
+        for jd in [2460000.5, 2460091.5, 2460182.5, 2460273.5] {
+            let orbit: EarthOrbit = earth_orbit(jd);
+            let out: EquatorialCoord = annual_parallax(&pole, 1000.0, orbit.position);
+            let shift: f64 = crate::coords::angular_separation(pole.ra.radians(), pole.dec.radians(), out.ra.radians(), out.dec.radians());
+            assert!((shift / ARCSEC_TO_RAD - orbit.distance).abs() < 0.01, "got {}″", shift / ARCSEC_TO_RAD);
+        }
+    }
+
+    #[test]
+    fn annual_combines_both() {
+        let coord: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(217.42895), Angle::from_degrees(-62.67949));
+        let result: Derived<AnnualPlace> = annual(&coord, 768.0665, 2460310.5, true);
+        assert!(result.trace.is_some());
+        // aberration is at most κ ≈ 20.5″, parallax at most ϖ ≈ 0.77″ for Proxima
+        let shift = |a: &EquatorialCoord, b: &EquatorialCoord| crate::coords::angular_separation(a.ra.radians(), a.dec.radians(), b.ra.radians(), b.dec.radians()) / ARCSEC_TO_RAD;
+        assert!(shift(&coord, &result.value.parallax) < 0.78);
+        assert!(shift(&result.value.parallax, &result.value.apparent) < 20.6);
+        assert!(shift(&coord, &annual(&coord, 0.0, 2460310.5, false).value.parallax) == 0.0);
+    }
+}
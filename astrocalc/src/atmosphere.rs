@@ -0,0 +1,175 @@
+// atmospheric refraction (Meeus, Astronomical Algorithms ch. 16) and relative airmass
+
+use crate::trace::{Derived, Trace};
+
+// the standard atmosphere the refraction formulas are fitted to, hPa and °C
+pub const STANDARD_PRESSURE: f64 = 1010.0;
+pub const STANDARD_TEMPERATURE: f64 = 10.0;
+
+// refraction scales with the density of the air at the observer
+pub fn refraction_factor(pressure_hpa: f64, temperature_c: f64) -> f64 {
+    pressure_hpa / STANDARD_PRESSURE * 283.0 / (273.0 + temperature_c)
+}
+
+// Bennett (1982): refraction in arcminutes from the apparent (observed) altitude in degrees
+pub fn bennett(apparent_alt: f64, pressure_hpa: f64, temperature_c: f64) -> f64 {
+    let h: f64 = apparent_alt;
+    refraction_factor(pressure_hpa, temperature_c) / (h + 7.31 / (h + 4.4)).to_radians().tan()
+}
+
+// Saemundsson (1986): refraction in arcminutes from the true (airless) altitude in degrees, the
+// inverse of Bennett to within 0.1′
+pub fn saemundsson(true_alt: f64, pressure_hpa: f64, temperature_c: f64) -> f64 {
+    let h: f64 = true_alt;
+    refraction_factor(pressure_hpa, temperature_c) * 1.02 / (h + 10.3 / (h + 5.11)).to_radians().tan()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AirmassModel {
+    PlaneParallel,
+    KastenYoung,
+    Pickering,
+}
+
+pub const AIRMASS_MODELS: [AirmassModel; 3] = [AirmassModel::PlaneParallel, AirmassModel::KastenYoung, AirmassModel::Pickering];
+
+impl AirmassModel {
+    pub fn parse(name: &str) -> Option<AirmassModel> {
+        match name.trim().to_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "planeparallel" | "secz" | "plane" => Some(AirmassModel::PlaneParallel),
+            "kastenyoung" | "ky" => Some(AirmassModel::KastenYoung),
+            "pickering" => Some(AirmassModel::Pickering),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AirmassModel::PlaneParallel => "plane-parallel",
+            AirmassModel::KastenYoung => "Kasten-Young",
+            AirmassModel::Pickering => "Pickering",
+        }
+    }
+
+    // Pickering's fit is to the apparent altitude, the others take the true altitude
+    pub fn uses_apparent(&self) -> bool {
+        *self == AirmassModel::Pickering
+    }
+
+    // relative airmass from the altitude in degrees, None at or below the horizon
+    pub fn airmass(&self, alt: f64) -> Option<f64> {
+        if alt < 0.0 || (*self == AirmassModel::PlaneParallel && alt <= 0.0) {
+            return None;
+        }
+        Some(match self {
+            // sec z, fine above ~30° but infinite at the horizon
+            AirmassModel::PlaneParallel => 1.0 / alt.to_radians().sin(),
+            // Kasten & Young (1989), 37.92 at the horizon
+            AirmassModel::KastenYoung => 1.0 / (alt.to_radians().sin() + 0.50572 * (alt + 6.07995).powf(-1.6364)),
+            // Pickering (2002), 38.75 at the horizon
+            AirmassModel::Pickering => 1.0 / (alt + 244.0 / (165.0 + 47.0 * alt.powf(1.1))).to_radians().sin(),
+        })
+    }
+
+    pub fn formula(&self) -> &'static str {
+        match self {
+            AirmassModel::PlaneParallel => "X = 1 / sin(h)",
+            AirmassModel::KastenYoung => "X = 1 / (sin(h) + 0.50572(h + 6.07995°)^-1.6364)",
+            AirmassModel::Pickering => "X = 1 / sin(h' + 244 / (165 + 47h'^1.1))",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Refraction {
+    // degrees, and the refraction between them in arcminutes
+    pub true_alt: f64,
+    pub apparent_alt: f64,
+    pub refraction: f64,
+    pub airmass: Vec<(AirmassModel, Option<f64>)>,
+}
+
+// from a true altitude (degrees) to where it is seen through the atmosphere, or from an observed
+// altitude back with `apparent`, then the airmass of every model at that altitude
+pub fn refraction(alt: f64, apparent: bool, pressure_hpa: f64, temperature_c: f64, show_work: bool) -> Derived<Refraction> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+
+    note!(trace, "Refraction scales with air density, f = (P / 1010 hPa)(283 K / (273 + T))");
+    let f: f64 = refraction_factor(pressure_hpa, temperature_c);
+    value!(trace, ["f = ({} / 1010)(283 / {})", pressure_hpa, 273.0 + temperature_c], ["{}", f]);
+    let (true_alt, apparent_alt, r): (f64, f64, f64) = if apparent {
+        note!(trace, "Bennett, from the apparent altitude h'");
+        note!(trace, "R = f / tan(h' + 7.31 / (h' + 4.4)), arcminutes");
+        let r: f64 = bennett(alt, pressure_hpa, temperature_c);
+        value!(trace, ["R"], ["{}′", r]);
+        note!(trace, "h = h' - R");
+        (alt - r / 60.0, alt, r)
+    } else {
+        note!(trace, "Saemundsson, from the true altitude h");
+        note!(trace, "R = 1.02f / tan(h + 10.3 / (h + 5.11)), arcminutes");
+        let r: f64 = saemundsson(alt, pressure_hpa, temperature_c);
+        value!(trace, ["R"], ["{}′", r]);
+        note!(trace, "h' = h + R");
+        (alt, alt + r / 60.0, r)
+    };
+    value!(trace, ["h"], ["{}°", true_alt]);
+    value!(trace, ["h'"], ["{}°", apparent_alt]);
+
+    let airmass: Vec<(AirmassModel, Option<f64>)> = AIRMASS_MODELS
+        .iter()
+        .map(|model: &AirmassModel| {
+            let x: Option<f64> = model.airmass(if model.uses_apparent() { apparent_alt } else { true_alt });
+            note!(trace, "Airmass, {}", model.name());
+            note!(trace, "{}", model.formula());
+            if let Some(x) = x {
+                value!(trace, ["X"], ["{}", x]);
+            }
+            (*model, x)
+        })
+        .collect();
+
+    Derived { value: Refraction { true_alt, apparent_alt, refraction: r, airmass }, trace }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meeus_refraction() {
+        // Meeus example 16.a, apparent altitude 0°30′: R = 28.754′ in the standard atmosphere
+        let r: f64 = bennett(0.5, STANDARD_PRESSURE, STANDARD_TEMPERATURE);
+        assert!((r - 28.754).abs() < 1e-3, "got {}", r);
+        // about a minute of arc at 45°, and the two formulas invert each other to 0.1′
+        assert!((bennett(45.0, 1010.0, 10.0) - 1.0).abs() < 0.02);
+        for h in [0.0, 2.0, 10.0, 30.0, 60.0] {
+            let back: f64 = saemundsson(h - bennett(h, 1010.0, 10.0) / 60.0, 1010.0, 10.0);
+            assert!((back - bennett(h, 1010.0, 10.0)).abs() < 0.1, "h = {}", h);
+        }
+        // thinner, warmer air bends less
+        assert!(bennett(10.0, 700.0, 25.0) < bennett(10.0, 1010.0, 10.0) * 0.7);
+    }
+
+    #[test]
+    fn airmass_models() {
+        for model in AIRMASS_MODELS {
+            assert!((model.airmass(90.0).unwrap() - 1.0).abs() < 1e-3, "{}", model.name());
+            assert!((model.airmass(30.0).unwrap() - 2.0).abs() < 0.01, "{}", model.name());
+            assert!(model.airmass(-1.0).is_none());
+            assert_eq!(AirmassModel::parse(model.name()), Some(model));
+        }
+        assert!(AirmassModel::PlaneParallel.airmass(0.0).is_none());
+        assert!((AirmassModel::KastenYoung.airmass(0.0).unwrap() - 37.92).abs() < 0.01);
+        assert!((AirmassModel::Pickering.airmass(0.0).unwrap() - 38.75).abs() < 0.1);
+    }
+
+    #[test]
+    fn refraction_both_ways() {
+        let seen: Derived<Refraction> = refraction(0.5, true, 1010.0, 10.0, true);
+        assert!((seen.value.true_alt - (0.5 - 28.754 / 60.0)).abs() < 1e-4);
+        assert_eq!(seen.value.airmass.len(), 3);
+        assert!(seen.trace.is_some());
+        let back: Refraction = refraction(seen.value.true_alt, false, 1010.0, 10.0, false).value;
+        assert!((back.apparent_alt - 0.5).abs() < 0.1 / 60.0);
+    }
+}
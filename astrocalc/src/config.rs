@@ -0,0 +1,238 @@
+// named observer sites and targets, kept in ~/.config/rust-utils/astrocalc.toml beside the
+// config of the other tools
+
+use crate::angle::AngleUnit;
+use crate::coords::EquatorialCoord;
+use crate::error::Error;
+use crate::observer::{parse_site, Site};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+// values are kept as typed, so sexagesimal stays sexagesimal, and parsed when used
+#[derive(Clone, Debug, PartialEq)]
+pub struct SitePreset {
+    pub lat: String,
+    pub lon: String,
+    pub elevation: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TargetPreset {
+    pub ra: String,
+    pub dec: String,
+}
+
+impl SitePreset {
+    pub fn site(&self) -> Result<Site, String> {
+        parse_site(&self.lat, &self.lon, Some(&self.elevation.to_string()))
+    }
+}
+
+impl TargetPreset {
+    pub fn coord(&self) -> Result<EquatorialCoord, String> {
+        EquatorialCoord::parse(&self.ra, &self.dec, AngleUnit::Degrees)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub sites: BTreeMap<String, SitePreset>,
+    pub targets: BTreeMap<String, TargetPreset>,
+}
+
+// $ASTROCALC_CONFIG if set, otherwise ~/.config/rust-utils/astrocalc.toml
+pub fn config_path() -> Result<PathBuf, Error> {
+    if let Ok(path) = std::env::var("ASTROCALC_CONFIG") {
+        return Ok(PathBuf::from(path));
+    }
+    let home: String = std::env::var("HOME").map_err(|_| Error::Io("HOME is not set, can't find the config file".to_string()))?;
+    Ok(PathBuf::from(home).join(".config").join("rust-utils").join("astrocalc.toml"))
+}
+
+// names go on the command line where numbers would, so they can't look like an option
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() || name.trim() != name || name.starts_with('-') {
+        return Err(format!("invalid name: '{}', names can't be blank, start with - or have spaces at either end", name));
+    }
+    Ok(())
+}
+
+// a number or string field of a preset, as text
+fn text(table: &Table, key: &str, owner: &str) -> Result<String, String> {
+    match table.get(key) {
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(Value::Float(x)) => Ok(x.to_string()),
+        Some(Value::Integer(n)) => Ok(n.to_string()),
+        Some(_) => Err(format!("{}: {} should be a number or a string", owner, key)),
+        None => Err(format!("{}: missing {}", owner, key)),
+    }
+}
+
+// numbers are written as numbers, anything else (sexagesimal) as a string
+fn value(text: &str) -> Value {
+    match text.trim().parse::<f64>() {
+        Ok(x) if x.is_finite() => Value::Float(x),
+        _ => Value::String(text.trim().to_string()),
+    }
+}
+
+fn find<'a, T>(map: &'a BTreeMap<String, T>, name: &str) -> Option<(&'a String, &'a T)> {
+    map.iter().find(|(key, _)| key.eq_ignore_ascii_case(name.trim()))
+}
+
+impl Config {
+    pub fn parse(data: &str) -> Result<Config, String> {
+        let table: Table = data.parse::<Table>().map_err(|e| e.to_string().trim().to_string())?;
+        let mut config: Config = Config::default();
+        let section = |name: &str| -> Result<Table, String> {
+            match table.get(name) {
+                Some(Value::Table(t)) => Ok(t.clone()),
+                Some(_) => Err(format!("[{}] should be a table of named entries", name)),
+                None => Ok(Table::new()),
+            }
+        };
+        for (name, entry) in section("sites")? {
+            let owner: String = format!("site '{}'", name);
+            let entry: Table = entry.as_table().cloned().ok_or_else(|| format!("{} should be a table", owner))?;
+            let elevation: f64 = match entry.get("elevation") {
+                Some(v) => v.as_float().or(v.as_integer().map(|n: i64| n as f64)).ok_or_else(|| format!("{}: elevation should be a number", owner))?,
+                None => 0.0,
+            };
+            let preset: SitePreset = SitePreset { lat: text(&entry, "lat", &owner)?, lon: text(&entry, "lon", &owner)?, elevation };
+            preset.site().map_err(|e| format!("{}: {}", owner, e))?;
+            config.sites.insert(name, preset);
+        }
+        for (name, entry) in section("targets")? {
+            let owner: String = format!("target '{}'", name);
+            let entry: Table = entry.as_table().cloned().ok_or_else(|| format!("{} should be a table", owner))?;
+            let preset: TargetPreset = TargetPreset { ra: text(&entry, "ra", &owner)?, dec: text(&entry, "dec", &owner)? };
+            preset.coord().map_err(|e| format!("{}: {}", owner, e))?;
+            config.targets.insert(name, preset);
+        }
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut sites: Table = Table::new();
+        for (name, site) in &self.sites {
+            let mut entry: Table = Table::new();
+            entry.insert("lat".to_string(), value(&site.lat));
+            entry.insert("lon".to_string(), value(&site.lon));
+            entry.insert("elevation".to_string(), Value::Float(site.elevation));
+            sites.insert(name.clone(), Value::Table(entry));
+        }
+        let mut targets: Table = Table::new();
+        for (name, target) in &self.targets {
+            let mut entry: Table = Table::new();
+            entry.insert("ra".to_string(), value(&target.ra));
+            entry.insert("dec".to_string(), value(&target.dec));
+            targets.insert(name.clone(), Value::Table(entry));
+        }
+        let mut table: Table = Table::new();
+        table.insert("sites".to_string(), Value::Table(sites));
+        table.insert("targets".to_string(), Value::Table(targets));
+        table.to_string()
+    }
+
+    // a missing file is an empty config
+    pub fn load(path: &PathBuf) -> Result<Config, Error> {
+        match std::fs::read_to_string(path) {
+            Ok(data) => Config::parse(&data).map_err(|e| Error::Input(format!("Invalid config {}: {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(Error::Io(format!("could not read {}: {}", path.display(), e))),
+        }
+    }
+
+    pub fn save(&self, path: &PathBuf) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| Error::Io(format!("could not create {}: {}", dir.display(), e)))?;
+        }
+        std::fs::write(path, self.to_toml()).map_err(|e| Error::Io(format!("could not write {}: {}", path.display(), e)))
+    }
+
+    // lookups ignore case, and adding a name that's there in another case replaces it
+    pub fn site(&self, name: &str) -> Option<(&String, &SitePreset)> {
+        find(&self.sites, name)
+    }
+
+    pub fn target(&self, name: &str) -> Option<(&String, &TargetPreset)> {
+        find(&self.targets, name)
+    }
+
+    pub fn add_site(&mut self, name: &str, site: SitePreset) -> Result<bool, String> {
+        check_name(name)?;
+        site.site()?;
+        let existing: Option<String> = self.site(name).map(|(key, _)| key.clone());
+        if let Some(key) = &existing {
+            self.sites.remove(key);
+        }
+        self.sites.insert(name.to_string(), site);
+        Ok(existing.is_some())
+    }
+
+    pub fn add_target(&mut self, name: &str, target: TargetPreset) -> Result<bool, String> {
+        check_name(name)?;
+        target.coord()?;
+        // a name that parses as a coordinate would never be looked up
+        if EquatorialCoord::parse(name, "0", AngleUnit::Degrees).is_ok() {
+            return Err(format!("invalid name: '{}' reads as a coordinate", name));
+        }
+        let existing: Option<String> = self.target(name).map(|(key, _)| key.clone());
+        if let Some(key) = &existing {
+            self.targets.remove(key);
+        }
+        self.targets.insert(name.to_string(), target);
+        Ok(existing.is_some())
+    }
+
+    pub fn remove_site(&mut self, name: &str) -> Option<SitePreset> {
+        let key: String = self.site(name)?.0.clone();
+        self.sites.remove(&key)
+    }
+
+    pub fn remove_target(&mut self, name: &str) -> Option<TargetPreset> {
+        let key: String = self.target(name)?.0.clone();
+        self.targets.remove(&key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let data: &str = "[sites.mauna-kea]\nlat = 19.8207\nlon = \"-155:28:05\"\nelevation = 4205\n\n[targets.M31]\nra = \"00:42:44.3\"\ndec = 41.269\n";
+        let config: Config = Config::parse(data).unwrap();
+        let (name, site): (&String, &SitePreset) = config.site("Mauna-Kea").unwrap();
+        assert_eq!(name, "mauna-kea");
+        assert_eq!(site, &SitePreset { lat: "19.8207".to_string(), lon: "-155:28:05".to_string(), elevation: 4205.0 });
+        assert!((site.site().unwrap().lon.degrees() + 155.468).abs() < 1e-3);
+        assert!((config.target("m31").unwrap().1.coord().unwrap().ra.degrees() - 10.6846).abs() < 1e-3);
+        assert_eq!(Config::parse(&config.to_toml()).unwrap(), config);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+
+        assert!(Config::parse("[sites.x]\nlat = 95\nlon = 0\n").unwrap_err().contains("site 'x'"));
+        assert!(Config::parse("[targets.y]\nra = \"1h\"\n").unwrap_err().contains("missing dec"));
+        assert!(Config::parse("sites = 3").is_err());
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut config: Config = Config::default();
+        let site: SitePreset = SitePreset { lat: "52".to_string(), lon: "0".to_string(), elevation: 0.0 };
+        assert_eq!(config.add_site("home", site.clone()), Ok(false));
+        assert_eq!(config.add_site("HOME", site.clone()), Ok(true));
+        assert_eq!(config.sites.len(), 1);
+        assert!(config.add_site("-x", site.clone()).is_err());
+        assert!(config.add_site("bad", SitePreset { lat: "100".to_string(), ..site }).is_err());
+
+        let target: TargetPreset = TargetPreset { ra: "5h35m".to_string(), dec: "-5.4".to_string() };
+        assert_eq!(config.add_target("orion", target.clone()), Ok(false));
+        assert!(config.add_target("12.5", target.clone()).is_err());
+        assert!(config.remove_target("ORION").is_some());
+        assert!(config.remove_target("orion").is_none());
+        assert!(config.remove_site("home").is_some() && config.sites.is_empty());
+    }
+}
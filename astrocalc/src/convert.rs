@@ -0,0 +1,221 @@
+// unit conversions for the `convert` command
+
+use crate::angle::{tidy, Angle, AngleUnit};
+use crate::ephemeris::AU_KM;
+use crate::error::Error;
+use crate::expr::{evaluate, Quantity};
+use crate::parse::{parse_coord, CoordKind};
+use crate::PI;
+
+// IAU light-year, c times a Julian year
+pub const LIGHT_YEAR_KM: f64 = 9460730472580.8;
+// 1 AU subtending 1″
+pub const PARSEC_KM: f64 = AU_KM * 648000.0 / PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unit {
+    Degrees,
+    Radians,
+    // multiples of π radians
+    PiRadians,
+    Hours,
+    Arcminutes,
+    Arcseconds,
+    Milliarcseconds,
+    // sexagesimal strings, ±DD°MM′SS.S″ and HHhMMmSS.SSs
+    Dms,
+    Hms,
+    Kilometres,
+    AstronomicalUnits,
+    LightYears,
+    Parsecs,
+}
+
+impl Unit {
+    pub fn parse(name: &str) -> Result<Unit, Error> {
+        match name.trim().to_lowercase().as_str() {
+            "deg" | "degree" | "degrees" | "°" => Ok(Unit::Degrees),
+            "rad" | "radian" | "radians" => Ok(Unit::Radians),
+            "pi" | "π" | "pirad" | "πrad" => Ok(Unit::PiRadians),
+            "h" | "hr" | "hour" | "hours" => Ok(Unit::Hours),
+            "arcmin" | "amin" | "'" | "′" => Ok(Unit::Arcminutes),
+            "arcsec" | "asec" | "\"" | "″" => Ok(Unit::Arcseconds),
+            "mas" => Ok(Unit::Milliarcseconds),
+            "dms" => Ok(Unit::Dms),
+            "hms" => Ok(Unit::Hms),
+            "km" => Ok(Unit::Kilometres),
+            "au" => Ok(Unit::AstronomicalUnits),
+            "ly" | "lightyear" | "lightyears" | "light-year" | "light-years" => Ok(Unit::LightYears),
+            "pc" | "parsec" | "parsecs" => Ok(Unit::Parsecs),
+            _ => Err(Error::Usage(format!("Unknown unit: {} (deg, rad, pi, h, arcmin, arcsec, mas, dms, hms, km, au, ly, pc)", name))),
+        }
+    }
+
+    pub fn is_distance(&self) -> bool {
+        matches!(self, Unit::Kilometres | Unit::AstronomicalUnits | Unit::LightYears | Unit::Parsecs)
+    }
+
+    // radians or kilometres per unit, dms and hms counting as degrees and hours
+    pub fn scale(&self) -> f64 {
+        match self {
+            Unit::Degrees | Unit::Dms => PI / 180.0,
+            Unit::Radians => 1.0,
+            Unit::PiRadians => PI,
+            Unit::Hours | Unit::Hms => PI / 12.0,
+            Unit::Arcminutes => PI / 180.0 / 60.0,
+            Unit::Arcseconds => PI / 180.0 / 3600.0,
+            Unit::Milliarcseconds => PI / 180.0 / 3600000.0,
+            Unit::Kilometres => 1.0,
+            Unit::AstronomicalUnits => AU_KM,
+            Unit::LightYears => LIGHT_YEAR_KM,
+            Unit::Parsecs => PARSEC_KM,
+        }
+    }
+}
+
+// a parsed value, angles in radians and distances in kilometres
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Measure {
+    Angle(Angle),
+    Distance(f64),
+}
+
+// reads input in the from unit. input is an expression (see expr) whose plain numbers are in
+// that unit, dms/hms also take sexagesimal strings like -12:30:00 or 05h34m31.9s
+pub fn parse_measure(input: &str, from: Unit) -> Result<Measure, Error> {
+    let quantity: Result<Quantity, Error> = evaluate(input).map_err(Error::from);
+    let quantity: Quantity = match (quantity, from) {
+        (Ok(quantity), _) => quantity,
+        (Err(e), Unit::Dms | Unit::Hms) => {
+            let kind: CoordKind = if from == Unit::Dms { CoordKind::Angle } else { CoordKind::Hours };
+            // the expression error has the caret, so it wins if the sexagesimal form fails too
+            return Ok(Measure::Angle(parse_coord(input, kind, AngleUnit::Degrees).map_err(|_| e)?));
+        }
+        (Err(e), _) => return Err(e),
+    };
+    match quantity {
+        Quantity::Number(n) if from.is_distance() => Ok(Measure::Distance(n * from.scale())),
+        Quantity::Number(n) => Ok(Measure::Angle(Angle::from_radians(n * from.scale()))),
+        Quantity::Angle(_) if from.is_distance() => Err(Error::Input(format!("Expected a distance, not an angle: {}", input))),
+        Quantity::Angle(angle) => Ok(Measure::Angle(angle)),
+    }
+}
+
+// a measure in the to unit, an angle and a distance being a parallax and the distance it
+// corresponds to (d = 1/p, in parsecs and arcseconds)
+pub fn convert_measure(measure: Measure, to: Unit) -> Result<String, Error> {
+    let measure: Measure = match (measure, to.is_distance()) {
+        (Measure::Angle(parallax), true) => {
+            if parallax.radians() <= 0.0 {
+                return Err(Error::Input("Parallax must be positive to give a distance".to_string()));
+            }
+            Measure::Distance(PARSEC_KM / parallax.arcseconds())
+        }
+        (Measure::Distance(km), false) => {
+            if km <= 0.0 {
+                return Err(Error::Input("Distance must be positive to give a parallax".to_string()));
+            }
+            Measure::Angle(Angle::from_arcseconds(PARSEC_KM / km))
+        }
+        (measure, _) => measure,
+    };
+    Ok(match measure {
+        Measure::Angle(angle) => match to {
+            Unit::Degrees => angle.format_degrees(),
+            Unit::Radians => angle.format_radians(),
+            Unit::PiRadians => angle.format_pi_radians(),
+            Unit::Hours => format!("{}h", tidy(angle.hours())),
+            Unit::Arcminutes => angle.format_arcminutes(),
+            Unit::Arcseconds => angle.format_arcseconds(),
+            Unit::Milliarcseconds => format!("{} mas", tidy(angle.arcseconds() * 1000.0)),
+            Unit::Dms => angle.format_dms(),
+            _ => angle.format_hms(),
+        },
+        Measure::Distance(km) => {
+            let symbol: &str = match to {
+                Unit::Kilometres => "km",
+                Unit::AstronomicalUnits => "AU",
+                Unit::LightYears => "ly",
+                _ => "pc",
+            };
+            format!("{} {}", tidy(km / to.scale()), symbol)
+        }
+    })
+}
+
+// `convert <value> <from> <to>`, formatted the way the command prints it
+pub fn convert(input: &str, from: &str, to: &str) -> Result<String, Error> {
+    let (from, to): (Unit, Unit) = (Unit::parse(from)?, Unit::parse(to)?);
+    convert_measure(parse_measure(input, from)?, to)
+}
+
+// the older `convert <deg-rad|rad-deg> <value>` form
+pub fn convert_pair(conversion: &str, input: &str) -> Result<String, Error> {
+    match conversion {
+        "deg-rad" => convert(input, "deg", "pi"),
+        "rad-deg" => convert(input, "rad", "deg"),
+        _ => Err(Error::Usage("Invalid conversion type".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degrees_and_radians() {
+        assert_eq!(convert_pair("deg-rad", "90").unwrap(), "0.5π rad");
+        assert_eq!(convert_pair("rad-deg", "pi").unwrap(), "180°");
+        assert_eq!(convert_pair("rad-deg", "2pi").unwrap(), "360°");
+        assert_eq!(convert_pair("rad-deg", "0").unwrap(), "0°");
+        assert_eq!(convert_pair("rad-deg", "0.17pi").unwrap(), "30.6°");
+        assert_eq!(convert_pair("deg-rad", "30.6").unwrap(), "0.17π rad");
+        assert_eq!(convert_pair("rad-deg", "1").unwrap(), "57.2957795130823°");
+        assert_eq!(convert_pair("rad-deg", "pi/4").unwrap(), "45°");
+        assert_eq!(convert_pair("rad-deg", "3pi/2").unwrap(), "270°");
+        assert_eq!(convert_pair("deg-rad", "45 + 30/60").unwrap(), "0.252777777777778π rad");
+        assert_eq!(convert_pair("deg-rad", "1h").unwrap(), "0.0833333333333333π rad");
+    }
+
+    #[test]
+    fn bad_input_is_an_error() {
+        assert!(convert_pair("deg-rad", "ninety").is_err());
+        assert!(convert_pair("rad-deg", "xpi").is_err());
+        assert_eq!(convert_pair("rad-deg", "pi/").unwrap_err().to_string(), "Invalid value: expected a value but found end of input\npi/\n   ^");
+        assert!(convert_pair("rad-hours", "1").is_err());
+        assert!(convert("1", "deg", "furlong").is_err());
+        assert!(convert("1deg", "km", "au").is_err());
+        assert!(convert("0", "mas", "pc").is_err());
+        assert!(convert("-1", "pc", "arcsec").is_err());
+        assert!(convert("12:99", "dms", "deg").is_err());
+    }
+
+    #[test]
+    fn angle_units() {
+        assert_eq!(convert("90", "deg", "rad").unwrap(), "1.5707963267949 rad");
+        assert_eq!(convert("6", "h", "deg").unwrap(), "90°");
+        assert_eq!(convert("1", "deg", "arcmin").unwrap(), "60′");
+        assert_eq!(convert("1", "arcsec", "mas").unwrap(), "1000 mas");
+        assert_eq!(convert("0.5", "arcmin", "arcsec").unwrap(), "30″");
+        assert_eq!(convert("45", "deg", "h").unwrap(), "3h");
+        assert_eq!(convert("83.633", "deg", "hms").unwrap(), "05h34m31.92s");
+        assert_eq!(convert("-5.391", "deg", "dms").unwrap(), "-05°23′27.6″");
+        assert_eq!(convert("-05:23:27.6", "dms", "deg").unwrap(), "-5.391°");
+        assert_eq!(convert("05h34m31.92s", "hms", "deg").unwrap(), "83.633°");
+        assert_eq!(convert("12.5", "hms", "h").unwrap(), "12.5h");
+        // unit suffixes override the from unit
+        assert_eq!(convert("1h + 30'", "rad", "deg").unwrap(), "15.5°");
+    }
+
+    #[test]
+    fn distances_and_parallax() {
+        assert_eq!(convert("1", "au", "km").unwrap(), "149597870.7 km");
+        assert_eq!(convert("1", "pc", "au").unwrap(), "206264.806247096 AU");
+        assert_eq!(convert("1", "pc", "ly").unwrap(), "3.26156377716743 ly");
+        assert_eq!(convert("1", "ly", "km").unwrap(), "9460730472580.8 km");
+        // Proxima Centauri, 768.07 mas
+        assert_eq!(convert("768.07", "mas", "pc").unwrap(), "1.301964664679 pc");
+        assert_eq!(convert("10", "pc", "mas").unwrap(), "100 mas");
+        assert_eq!(convert("0.1", "arcsec", "ly").unwrap(), "32.6156377716743 ly");
+    }
+}
@@ -0,0 +1,379 @@
+// equatorial coordinates, separations and offsets, plus the vector/matrix helpers the
+// frame and epoch rotations are built on
+
+use crate::angle::{Angle, AngleUnit, Sexagesimal};
+use crate::parse::{parse_coord, CoordKind};
+use crate::trace::{Derived, Trace};
+use crate::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EquatorialCoord {
+    pub ra: Angle,
+    pub dec: Angle,
+}
+
+impl EquatorialCoord {
+    pub fn new(ra: Angle, dec: Angle) -> EquatorialCoord {
+        EquatorialCoord { ra, dec }
+    }
+
+    // parses an Ra/Dec pair, see parse_coord for the accepted forms
+    pub fn parse(ra: &str, dec: &str, bare: AngleUnit) -> Result<EquatorialCoord, String> {
+        Ok(EquatorialCoord {
+            ra: parse_coord(ra, CoordKind::Ra, bare)?,
+            dec: parse_coord(dec, CoordKind::Dec, bare)?,
+        })
+    }
+
+    pub fn to_vector(&self) -> [f64; 3] {
+        to_vector(self.ra.radians(), self.dec.radians())
+    }
+
+    pub fn from_vector(v: [f64; 3]) -> EquatorialCoord {
+        let (ra, dec): (f64, f64) = from_vector(v);
+        EquatorialCoord::new(Angle::from_radians(ra), Angle::from_radians(dec))
+    }
+}
+
+// position angle and offsets of one point about another
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Offset {
+    // east of north, [0, 2π)
+    pub position_angle: Angle,
+    pub separation: Angle,
+    // gnomonic (Δα·cos(δ), Δδ), None when the separation is 90° or more
+    pub tangent: Option<(Angle, Angle)>,
+}
+
+// the second point in the frame of the first: the east and north components and cos(θ) that
+// the separation, position angle and tangent-plane offsets are all built from
+struct Components {
+    delta_alpha: f64,
+    east: f64,
+    north: f64,
+    cos_theta: f64,
+}
+
+impl Components {
+    fn new(a1: f64, d1: f64, a2: f64, d2: f64) -> Components {
+        let delta_alpha: f64 = a2 - a1;
+        Components {
+            delta_alpha,
+            east: d2.cos() * delta_alpha.sin(),
+            north: d1.cos() * d2.sin() - d1.sin() * d2.cos() * delta_alpha.cos(),
+            cos_theta: d1.sin() * d2.sin() + d1.cos() * d2.cos() * delta_alpha.cos(),
+        }
+    }
+
+    fn sin_theta(&self) -> f64 {
+        (self.east * self.east + self.north * self.north).sqrt()
+    }
+
+    // the law of cosines loses everything below a few arcseconds (acos near 1) and the
+    // haversine form loses it near 180°, the atan2 form is well conditioned everywhere
+    fn separation(&self) -> f64 {
+        self.sin_theta().atan2(self.cos_theta)
+    }
+
+    fn position_angle(&self) -> f64 {
+        self.east.atan2(self.north).rem_euclid(2.0 * PI)
+    }
+
+    fn tangent(&self) -> Option<(f64, f64)> {
+        (self.cos_theta > 0.0).then(|| (self.east / self.cos_theta, self.north / self.cos_theta))
+    }
+}
+
+// great circle separation in radians, all inputs in radians (Vincenty's atan2 form)
+pub fn angular_separation(a1: f64, d1: f64, a2: f64, d2: f64) -> f64 {
+    Components::new(a1, d1, a2, d2).separation()
+}
+
+// position angle in radians in [0, 2π), measured from north through east
+pub fn position_angle(a1: f64, d1: f64, a2: f64, d2: f64) -> f64 {
+    Components::new(a1, d1, a2, d2).position_angle()
+}
+
+// gnomonic standard coordinates (ξ, η) in radians of the second point about the first,
+// None when the second point is on or behind the plane's horizon
+pub fn tangent_plane_offset(a1: f64, d1: f64, a2: f64, d2: f64) -> Option<(f64, f64)> {
+    Components::new(a1, d1, a2, d2).tangent()
+}
+
+fn radians_steps(trace: &mut Option<Trace>, a: &EquatorialCoord, b: &EquatorialCoord) {
+    note!(trace, "Convert values into radians");
+    note!(trace, "n = n * π/180");
+    value!(trace, ["α₁ = {} * π/180", a.ra.degrees()], ["{}", a.ra.radians()]);
+    value!(trace, ["δ₁ = {} * π/180", a.dec.degrees()], ["{}", a.dec.radians()]);
+    value!(trace, ["α₂ = {} * π/180", b.ra.degrees()], ["{}", b.ra.radians()]);
+    value!(trace, ["δ₂ = {} * π/180", b.dec.degrees()], ["{}", b.dec.radians()]);
+}
+
+// angular separation of two points, with the Vincenty derivation when show_work is set
+pub fn separation(a: &EquatorialCoord, b: &EquatorialCoord, show_work: bool) -> Derived<Angle> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+    radians_steps(&mut trace, a, b);
+    let c: Components = Components::new(a.ra.radians(), a.dec.radians(), b.ra.radians(), b.dec.radians());
+    let numerator: f64 = c.sin_theta();
+    note!(trace, "Apply the Vincenty formula");
+    note!(trace, "θ = atan2(√[(cos(δ₂)sin(Δα))² + (cos(δ₁)sin(δ₂) - sin(δ₁)cos(δ₂)cos(Δα))²], sin(δ₁)sin(δ₂) + cos(δ₁)cos(δ₂)cos(Δα))");
+    value!(trace, ["Δα = α₂ - α₁"], ["{}", c.delta_alpha]);
+    value!(trace, ["cos(δ₂)sin(Δα)"], ["{}", c.east]);
+    value!(trace, ["cos(δ₁)sin(δ₂) - sin(δ₁)cos(δ₂)cos(Δα)"], ["{}", c.north]);
+    value!(trace, ["numerator = √[({})² + ({})²]", c.east, c.north], ["{}", numerator]);
+    value!(trace, ["denominator = sin(δ₁)sin(δ₂) + cos(δ₁)cos(δ₂)cos(Δα)"], ["{}", c.cos_theta]);
+
+    let theta: Angle = Angle::from_radians(c.separation());
+    value!(trace, ["θ = atan2({}, {})", numerator, c.cos_theta], ["{}", theta.radians()]);
+
+    // Convert to degrees, arcminutes, arcseconds
+    let theta_deg: f64 = theta.degrees();
+    let theta_arcmin: f64 = (theta_deg - theta_deg.floor()) * 60.0;
+    let parts: Sexagesimal = theta.dms();
+    note!(trace, "Convert θ to degrees, arcminutes, and arcseconds");
+    note!(trace, "θ° = θ * 180/π");
+    value!(trace, ["θ° = {} * 180/π", theta.radians()], ["{}°", theta_deg]);
+    note!(trace, "Calculate arcminutes and arcseconds");
+    note!(trace, "θ' = (θ° - floor(θ°)) * 60");
+    value!(trace, ["θ' = ({} - floor({})) * 60", theta_deg, theta_deg], ["{}'", theta_arcmin]);
+    note!(trace, "θ\" = (θ' - floor(θ')) * 60");
+    value!(trace, ["θ\" = ({} - floor({})) * 60", theta_arcmin, theta_arcmin], ["{}", parts.seconds]);
+
+    Derived { value: theta, trace }
+}
+
+// position angle, separation and tangent-plane offsets of b about a
+pub fn offset(a: &EquatorialCoord, b: &EquatorialCoord, show_work: bool) -> Derived<Offset> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+    radians_steps(&mut trace, a, b);
+    let c: Components = Components::new(a.ra.radians(), a.dec.radians(), b.ra.radians(), b.dec.radians());
+    let (east, north, cos_theta): (f64, f64, f64) = (c.east, c.north, c.cos_theta);
+    note!(trace, "Components of the second point in the frame of the first");
+    value!(trace, ["Δα = α₂ - α₁"], ["{}", c.delta_alpha]);
+    value!(trace, ["E = cos(δ₂)sin(Δα)"], ["{}", east]);
+    value!(trace, ["N = cos(δ₁)sin(δ₂) - sin(δ₁)cos(δ₂)cos(Δα)"], ["{}", north]);
+    value!(trace, ["cos(θ) = sin(δ₁)sin(δ₂) + cos(δ₁)cos(δ₂)cos(Δα)"], ["{}", cos_theta]);
+
+    let pa: f64 = c.position_angle();
+    note!(trace, "Position angle, measured from north through east");
+    note!(trace, "PA = atan2(E, N)");
+    value!(trace, ["PA = atan2({}, {})", east, north], ["{} rad = {}°", pa, pa * (180.0 / PI)]);
+
+    let theta: f64 = c.separation();
+    note!(trace, "Separation");
+    note!(trace, "θ = atan2(√(E² + N²), cos(θ))");
+    value!(trace, ["θ"], ["{} rad = {}″", theta, theta * (180.0 / PI) * 3600.0]);
+
+    let tangent: Option<(Angle, Angle)> = match c.tangent() {
+        Some((xi, eta)) => {
+            note!(trace, "Project onto the tangent plane at the first point (gnomonic)");
+            note!(trace, "Δα·cos(δ) = E / cos(θ), Δδ = N / cos(θ)");
+            value!(trace, ["Δα·cos(δ) = {} / {}", east, cos_theta], ["{} rad", xi]);
+            value!(trace, ["Δδ = {} / {}", north, cos_theta], ["{} rad", eta]);
+            note!(trace, "Convert to arcseconds");
+            note!(trace, "n″ = n * 180/π * 3600");
+            Some((Angle::from_radians(xi), Angle::from_radians(eta)))
+        }
+        None => {
+            note!(trace, "θ ≥ 90°, the second point does not project onto the tangent plane of the first");
+            None
+        }
+    };
+
+    Derived {
+        value: Offset {
+            position_angle: Angle::from_radians(pa),
+            separation: Angle::from_radians(theta),
+            tangent,
+        },
+        trace,
+    }
+}
+
+// unit vector for a longitude/latitude pair in radians
+pub fn to_vector(lon: f64, lat: f64) -> [f64; 3] {
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+// longitude in [0, 2π) and latitude in radians for a (not necessarily unit) vector
+pub fn from_vector(v: [f64; 3]) -> (f64, f64) {
+    let lon: f64 = v[1].atan2(v[0]).rem_euclid(2.0 * PI);
+    let lat: f64 = v[2].atan2((v[0] * v[0] + v[1] * v[1]).sqrt());
+    (lon, lat)
+}
+
+pub fn mat_vec(m: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+pub fn mat_mul(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut out: [[f64; 3]; 3] = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+pub fn transpose(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    [
+        [m[0][0], m[1][0], m[2][0]],
+        [m[0][1], m[1][1], m[2][1]],
+        [m[0][2], m[1][2], m[2][2]],
+    ]
+}
+
+// rotations about the x, y and z axes by angle (radians), same sign convention as SOFA
+pub fn rot1(angle: f64) -> [[f64; 3]; 3] {
+    let (s, c): (f64, f64) = angle.sin_cos();
+    [[1.0, 0.0, 0.0], [0.0, c, s], [0.0, -s, c]]
+}
+
+pub fn rot2(angle: f64) -> [[f64; 3]; 3] {
+    let (s, c): (f64, f64) = angle.sin_cos();
+    [[c, 0.0, -s], [0.0, 1.0, 0.0], [s, 0.0, c]]
+}
+
+pub fn rot3(angle: f64) -> [[f64; 3]; 3] {
+    let (s, c): (f64, f64) = angle.sin_cos();
+    [[c, s, 0.0], [-s, c, 0.0], [0.0, 0.0, 1.0]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::Step;
+
+    const ARCSEC: f64 = PI / (180.0 * 3600.0);
+
+    #[test]
+    fn separation_sub_arcsecond_in_dec() {
+        let d1: f64 = 20f64.to_radians();
+        let theta: f64 = angular_separation(0.3, d1, 0.3, d1 + 0.5 * ARCSEC);
+        assert!((theta / ARCSEC - 0.5).abs() < 1e-6, "got {}″", theta / ARCSEC);
+    }
+
+    #[test]
+    fn separation_sub_arcsecond_in_ra() {
+        // at δ = 60° a 0.2″ step in α is a 0.1″ separation
+        let d: f64 = 60f64.to_radians();
+        let theta: f64 = angular_separation(1.0, d, 1.0 + 0.2 * ARCSEC, d);
+        assert!((theta / ARCSEC - 0.1).abs() < 1e-6, "got {}″", theta / ARCSEC);
+    }
+
+    #[test]
+    fn separation_close_pair_from_sexagesimal() {
+        // 05h34m31.940s +22°00′52.20″ vs 05h34m31.947s +22°00′52.30″
+        let a: EquatorialCoord = EquatorialCoord::parse("05h34m31.940s", "+22°00'52.20\"", AngleUnit::Degrees).unwrap();
+        let b: EquatorialCoord = EquatorialCoord::parse("05h34m31.947s", "+22°00'52.30\"", AngleUnit::Degrees).unwrap();
+        let dx: f64 = 0.007 * 15.0 * a.dec.radians().cos();
+        let expected: f64 = (dx * dx + 0.1 * 0.1).sqrt();
+        let theta: Derived<Angle> = separation(&a, &b, false);
+        assert!(theta.trace.is_none());
+        assert!((theta.value.arcseconds() - expected).abs() < 1e-5, "got {}″, expected {}″", theta.value.arcseconds(), expected);
+    }
+
+    #[test]
+    fn separation_near_antipodal() {
+        // (0°, 0°) to (180°, +1″) runs through the pole: 180° - 1″
+        let theta: f64 = angular_separation(0.0, 0.0, PI, ARCSEC);
+        assert!(((PI - theta) / ARCSEC - 1.0).abs() < 1e-4, "got {}″ short of 180°", (PI - theta) / ARCSEC);
+
+        let theta: f64 = angular_separation(0.0, 0.0, PI, 0.0);
+        assert!((theta - PI).abs() < 1e-12);
+    }
+
+    #[test]
+    fn separation_known_large_pair() {
+        // Betelgeuse to Rigel, matched against the law of cosines where it is well conditioned
+        let (a1, d1) = (88.792939f64.to_radians(), 7.407064f64.to_radians());
+        let (a2, d2) = (78.634467f64.to_radians(), (-8.201638f64).to_radians());
+        let expected: f64 = (d1.sin() * d2.sin() + d1.cos() * d2.cos() * (a2 - a1).cos()).acos();
+        let theta: f64 = angular_separation(a1, d1, a2, d2);
+        assert!((theta - expected).abs() < 1e-12);
+        assert!((theta.to_degrees() - 18.6).abs() < 0.05);
+    }
+
+    #[test]
+    fn separation_trace() {
+        let a: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(10.0), Angle::from_degrees(20.0));
+        let b: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(10.0), Angle::from_degrees(21.0));
+        let theta: Derived<Angle> = separation(&a, &b, true);
+        assert!((theta.value.degrees() - 1.0).abs() < 1e-12);
+        assert!(!theta.trace.unwrap().steps.is_empty());
+
+        // 1.5° apart: the arcminute step shows the fractional 30′, and the separation it
+        // traces is the one returned
+        let c: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(10.0), Angle::from_degrees(21.5));
+        let theta: Derived<Angle> = separation(&a, &c, true);
+        let steps: Vec<Step> = theta.trace.unwrap().steps;
+        let result = |prefix: &str| -> String {
+            steps
+                .iter()
+                .find_map(|step: &Step| match step {
+                    Step::Value { expr, result } if expr.starts_with(prefix) => Some(result.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        assert!((result("θ' =").trim_end_matches('\'').parse::<f64>().unwrap() - 30.0).abs() < 1e-6);
+        assert_eq!(result("θ = atan2").parse::<f64>().unwrap(), theta.value.radians());
+    }
+
+    #[test]
+    fn position_angle_cardinal_directions() {
+        let d: f64 = 30f64.to_radians();
+        let north: f64 = position_angle(1.0, d, 1.0, d + ARCSEC);
+        let east: f64 = position_angle(1.0, d, 1.0 + ARCSEC, d);
+        let south: f64 = position_angle(1.0, d, 1.0, d - ARCSEC);
+        let west: f64 = position_angle(1.0, d, 1.0 - ARCSEC, d);
+        assert!(north.abs() < 1e-9 || (north - 2.0 * PI).abs() < 1e-9);
+        assert!((east - PI / 2.0).abs() < 1e-5);
+        assert!((south - PI).abs() < 1e-9);
+        assert!((west - 3.0 * PI / 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn tangent_plane_offset_small_step() {
+        // 1″ in α at δ = 60° is 0.5″ east, 2″ in δ is 2″ north
+        let d: f64 = 60f64.to_radians();
+        let (xi, eta) = tangent_plane_offset(2.0, d, 2.0 + ARCSEC, d + 2.0 * ARCSEC).unwrap();
+        assert!((xi / ARCSEC - 0.5).abs() < 1e-4, "got {}″", xi / ARCSEC);
+        assert!((eta / ARCSEC - 2.0).abs() < 1e-4, "got {}″", eta / ARCSEC);
+        assert!(tangent_plane_offset(0.0, 0.0, PI, 0.0).is_none());
+    }
+
+    #[test]
+    fn offset_combines_the_pieces() {
+        let a: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(10.0), Angle::from_degrees(20.0));
+        let b: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(10.001), Angle::from_degrees(20.001));
+        let result: Offset = offset(&a, &b, false).value;
+        let (xi, eta) = result.tangent.unwrap();
+        assert!((xi.arcseconds() - 3.6 * 20f64.to_radians().cos()).abs() < 1e-3);
+        assert!((eta.arcseconds() - 3.6).abs() < 1e-3);
+        assert!((result.position_angle.degrees() - 43.2189).abs() < 1e-3);
+    }
+
+    #[test]
+    fn vectors_and_rotations() {
+        let (lon, lat) = from_vector(to_vector(4.0, -0.3));
+        assert!((lon - 4.0).abs() < 1e-12 && (lat + 0.3).abs() < 1e-12);
+        let m: [[f64; 3]; 3] = mat_mul(&rot3(0.7), &rot1(-0.2));
+        let identity: [[f64; 3]; 3] = mat_mul(&m, &transpose(&m));
+        for (i, row) in identity.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                assert!((cell - if i == j { 1.0 } else { 0.0 }).abs() < 1e-15);
+            }
+        }
+        // a positive rot3 turns the frame, so the vector's longitude drops
+        let (lon, _) = from_vector(mat_vec(&rot3(0.5), to_vector(1.0, 0.0)));
+        assert!((lon - 0.5).abs() < 1e-12);
+        let v: [f64; 3] = mat_vec(&rot2(PI / 2.0), [0.0, 0.0, 1.0]);
+        assert!((v[0] + 1.0).abs() < 1e-15);
+    }
+}
@@ -0,0 +1,157 @@
+// nearest-neighbour matching between two catalogs
+
+use crate::angle::{Angle, AngleUnit};
+use crate::coords::{angular_separation, to_vector};
+use crate::parse::{parse_coord, CoordKind};
+use crate::PI;
+
+// k-d tree over unit vectors, stored implicitly as a permutation of point indexes where each
+// slice's median splits its halves on axis depth % 3
+pub struct KdTree {
+    points: Vec<[f64; 3]>,
+    order: Vec<usize>,
+}
+
+impl KdTree {
+    pub fn new(points: Vec<[f64; 3]>) -> KdTree {
+        let mut order: Vec<usize> = (0..points.len()).collect();
+        KdTree::build(&points, &mut order, 0);
+        KdTree { points, order }
+    }
+
+    fn build(points: &[[f64; 3]], order: &mut [usize], depth: usize) {
+        if order.len() <= 1 {
+            return;
+        }
+        let axis: usize = depth % 3;
+        let mid: usize = order.len() / 2;
+        order.select_nth_unstable_by(mid, |a: &usize, b: &usize| points[*a][axis].total_cmp(&points[*b][axis]));
+        let (left, right): (&mut [usize], &mut [usize]) = order.split_at_mut(mid);
+        KdTree::build(points, left, depth + 1);
+        KdTree::build(points, &mut right[1..], depth + 1);
+    }
+
+    // indexes of all points within the chord length radius of target
+    pub fn within(&self, target: [f64; 3], radius: f64) -> Vec<usize> {
+        let mut found: Vec<usize> = Vec::new();
+        self.search(&self.order, 0, target, radius, &mut found);
+        found
+    }
+
+    fn search(&self, order: &[usize], depth: usize, target: [f64; 3], radius: f64, found: &mut Vec<usize>) {
+        if order.is_empty() {
+            return;
+        }
+        let mid: usize = order.len() / 2;
+        let point: [f64; 3] = self.points[order[mid]];
+        let dist2: f64 = (0..3).map(|i| (point[i] - target[i]).powi(2)).sum();
+        if dist2 <= radius * radius {
+            found.push(order[mid]);
+        }
+        let axis: usize = depth % 3;
+        if target[axis] - radius <= point[axis] {
+            self.search(&order[..mid], depth + 1, target, radius, found);
+        }
+        if target[axis] + radius >= point[axis] {
+            self.search(&order[mid + 1..], depth + 1, target, radius, found);
+        }
+    }
+}
+
+// Ra/Dec in radians for each row of a table, or why the row could not be parsed
+pub fn table_coords(rows: &[Vec<String>], ra_col: usize, dec_col: usize, bare: AngleUnit) -> Vec<Result<(f64, f64), String>> {
+    rows.iter()
+        .enumerate()
+        .map(|(n, row)| {
+            let field = |i: usize| row.get(i).map_or("", |f: &String| f.as_str());
+            let ra: Angle = parse_coord(field(ra_col), CoordKind::Ra, bare).map_err(|e| format!("row {}: {}", n + 1, e))?;
+            let dec: Angle = parse_coord(field(dec_col), CoordKind::Dec, bare).map_err(|e| format!("row {}: {}", n + 1, e))?;
+            Ok((ra.radians(), dec.radians()))
+        })
+        .collect()
+}
+
+// for each source in the first catalog, up to max_matches sources of the second within
+// radius (radians) as (index, separation), nearest first
+pub fn crossmatch(first: &[Result<(f64, f64), String>], second: &[(usize, f64, f64)], radius: f64, max_matches: usize) -> Vec<Result<Vec<(usize, f64)>, String>> {
+    let tree: KdTree = KdTree::new(second.iter().map(|(_, ra, dec)| to_vector(*ra, *dec)).collect());
+    // chord length of the search radius, capped at the diameter
+    let chord: f64 = 2.0 * (radius.min(PI) / 2.0).sin();
+    first
+        .iter()
+        .map(|source| {
+            let (ra, dec): (f64, f64) = source.clone()?;
+            let mut matches: Vec<(usize, f64)> = tree
+                .within(to_vector(ra, dec), chord)
+                .into_iter()
+                .map(|i: usize| (second[i].0, angular_separation(ra, dec, second[i].1, second[i].2)))
+                .filter(|(_, sep)| *sep <= radius)
+                .collect();
+            matches.sort_by(|a, b| a.1.total_cmp(&b.1));
+            matches.truncate(max_matches);
+            Ok(matches)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kd_tree_matches_brute_force() {
+        // small LCG so the test does not need a rand dependency
+        let mut seed: u64 = 12345;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let points: Vec<(usize, f64, f64)> = (0..2000)
+            .map(|i| (i, next() * 2.0 * PI, (2.0 * next() - 1.0).asin()))
+            .collect();
+        let sources: Vec<Result<(f64, f64), String>> = (0..200)
+            .map(|_| Ok((next() * 2.0 * PI, (2.0 * next() - 1.0).asin())))
+            .collect();
+        let radius: f64 = 3f64.to_radians();
+        let matches = crossmatch(&sources, &points, radius, usize::MAX);
+        for (source, found) in sources.iter().zip(matches) {
+            let (ra, dec) = source.clone().unwrap();
+            let mut expected: Vec<usize> = points
+                .iter()
+                .filter(|(_, pra, pdec)| angular_separation(ra, dec, *pra, *pdec) <= radius)
+                .map(|(i, _, _)| *i)
+                .collect();
+            let mut got: Vec<usize> = found.unwrap().iter().map(|(i, _)| *i).collect();
+            expected.sort();
+            got.sort();
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn kd_tree_within() {
+        let tree: KdTree = KdTree::new(vec![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        assert_eq!(tree.within([1.0, 0.0, 0.0], 0.1), vec![0]);
+        assert_eq!(tree.within([0.0, 0.0, -1.0], 1.0), Vec::<usize>::new());
+        assert_eq!(KdTree::new(Vec::new()).within([1.0, 0.0, 0.0], 2.0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn nearest_first_and_errors_kept() {
+        let rows: Vec<Vec<String>> = vec![
+            vec!["10".to_string(), "20".to_string()],
+            vec!["bad".to_string(), "20".to_string()],
+        ];
+        let first: Vec<Result<(f64, f64), String>> = table_coords(&rows, 0, 1, AngleUnit::Degrees);
+        assert!(first[1].as_ref().unwrap_err().starts_with("row 2:"));
+        let (ra, dec): (f64, f64) = first[0].clone().unwrap();
+        let arcsec: f64 = PI / (180.0 * 3600.0);
+        let second: Vec<(usize, f64, f64)> = vec![(7, ra, dec + 2.0 * arcsec), (9, ra, dec - arcsec)];
+        let matches = crossmatch(&first, &second, 5.0 * arcsec, 1);
+        let best: Vec<(usize, f64)> = matches[0].clone().unwrap();
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].0, 9);
+        assert!((best[0].1 / arcsec - 1.0).abs() < 1e-6);
+        assert!(matches[1].is_err());
+    }
+}
@@ -0,0 +1,358 @@
+// low precision Sun and Moon, lunar phase and twilight
+
+use crate::angle::Angle;
+use crate::coords::{angular_separation, from_vector, mat_vec, rot1, to_vector};
+use crate::observer::{equatorial_to_horizontal, gast, rise_transit_set, RiseSet, Site, Visibility};
+use crate::precession::{centuries_since_j2000, mean_obliquity, J2000_JD};
+use crate::time::{civil_to_jd, format_jd_iso, jd_to_civil, utc_to_tt};
+use crate::trace::{Derived, Trace};
+use crate::PI;
+
+pub const AU_KM: f64 = 149597870.7;
+pub const EARTH_RADIUS_KM: f64 = 6378.137;
+
+// ecliptic longitude/latitude of date -> Ra/Dec of date, radians
+pub fn ecliptic_to_equatorial(lambda: f64, beta: f64, eps: f64) -> (f64, f64) {
+    from_vector(mat_vec(&rot1(-eps), to_vector(lambda, beta)))
+}
+
+// apparent geocentric Sun, low precision (Astronomical Almanac, ~0.01° from 1950 to 2050).
+// returns (ecliptic longitude, Ra, Dec) in radians and the distance in AU
+pub fn sun_position(jd_utc: f64) -> (f64, f64, f64, f64) {
+    let n: f64 = utc_to_tt(jd_utc) - J2000_JD;
+    let mean_lon: f64 = 280.460 + 0.9856474 * n;
+    let g: f64 = (357.528 + 0.9856003 * n).to_radians();
+    let lambda: f64 = (mean_lon + 1.915 * g.sin() + 0.020 * (2.0 * g).sin()).rem_euclid(360.0).to_radians();
+    let r: f64 = 1.00014 - 0.01671 * g.cos() - 0.00014 * (2.0 * g).cos();
+    let eps: f64 = (23.439 - 0.0000004 * n).to_radians();
+    let (ra, dec): (f64, f64) = ecliptic_to_equatorial(lambda, 0.0, eps);
+    (lambda, ra, dec, r)
+}
+
+// geocentric Moon, low precision (Astronomical Almanac, ~0.3° in λ, 0.2° in β).
+// returns (ecliptic longitude, Ra, Dec) in radians and the distance in km
+pub fn moon_position(jd_utc: f64) -> (f64, f64, f64, f64) {
+    let t: f64 = centuries_since_j2000(utc_to_tt(jd_utc));
+    let s = |a: f64, b: f64| (a + b * t).to_radians().sin();
+    let c = |a: f64, b: f64| (a + b * t).to_radians().cos();
+    let lambda: f64 = 218.32 + 481267.881 * t + 6.29 * s(135.0, 477198.87) - 1.27 * s(259.3, -413335.36)
+        + 0.66 * s(235.7, 890534.22)
+        + 0.21 * s(269.9, 954397.74)
+        - 0.19 * s(357.5, 35999.05)
+        - 0.11 * s(186.5, 966404.03);
+    let beta: f64 = 5.13 * s(93.3, 483202.02) + 0.28 * s(228.2, 960400.89)
+        - 0.28 * s(318.3, 6003.15)
+        - 0.17 * s(217.6, -407332.21);
+    let parallax: f64 = 0.9508 + 0.0518 * c(135.0, 477198.87) + 0.0095 * c(259.3, -413335.36)
+        + 0.0078 * c(235.7, 890534.22)
+        + 0.0028 * c(269.9, 954397.74);
+    let lambda: f64 = lambda.rem_euclid(360.0).to_radians();
+    let beta: f64 = beta.to_radians();
+    let distance: f64 = EARTH_RADIUS_KM / parallax.to_radians().sin();
+    let (ra, dec): (f64, f64) = ecliptic_to_equatorial(lambda, beta, mean_obliquity(t));
+    (lambda, ra, dec, distance)
+}
+
+// geocentric altitude -> topocentric altitude for a body at distance_km, spherical Earth
+pub fn topocentric_altitude(alt: f64, distance_km: f64, elevation: f64) -> f64 {
+    let rho: f64 = (EARTH_RADIUS_KM + elevation / 1000.0) / distance_km;
+    (alt.sin() - rho).atan2(alt.cos())
+}
+
+// illuminated fraction, phase angle (radians) and whether the Moon is waxing
+pub fn moon_illumination(jd_utc: f64) -> (f64, f64, bool) {
+    let (sun_lon, sun_ra, sun_dec, sun_r): (f64, f64, f64, f64) = sun_position(jd_utc);
+    let (moon_lon, moon_ra, moon_dec, moon_r): (f64, f64, f64, f64) = moon_position(jd_utc);
+    let elongation: f64 = angular_separation(sun_ra, sun_dec, moon_ra, moon_dec);
+    let sun_km: f64 = sun_r * AU_KM;
+    let phase_angle: f64 = (sun_km * elongation.sin()).atan2(moon_r - sun_km * elongation.cos());
+    let waxing: bool = (moon_lon - sun_lon).rem_euclid(2.0 * PI) < PI;
+    ((1.0 + phase_angle.cos()) / 2.0, phase_angle, waxing)
+}
+
+pub fn moon_phase_name(jd_utc: f64) -> &'static str {
+    let (sun_lon, _, _, _): (f64, f64, f64, f64) = sun_position(jd_utc);
+    let (moon_lon, _, _, _): (f64, f64, f64, f64) = moon_position(jd_utc);
+    let age: f64 = (moon_lon - sun_lon).rem_euclid(2.0 * PI) * (180.0 / PI);
+    const NAMES: [&str; 8] = [
+        "new moon",
+        "waxing crescent",
+        "first quarter",
+        "waxing gibbous",
+        "full moon",
+        "waning gibbous",
+        "last quarter",
+        "waning crescent",
+    ];
+    NAMES[((age + 22.5) / 45.0) as usize % 8]
+}
+
+// next time after jd_start (within 24 h) the Sun's centre crosses altitude (radians),
+// None if it stays above or below all day
+pub fn sun_crossing(jd_start: f64, site: Site, altitude: f64, rising: bool) -> Option<f64> {
+    let mut t: f64 = jd_start + 0.5;
+    // the Sun moves ~1°/day, so recompute its position at the crossing until it settles
+    for _ in 0..4 {
+        let (_, ra, dec, _): (f64, f64, f64, f64) = sun_position(t);
+        match rise_transit_set(jd_start, site, ra, dec, altitude).visibility {
+            Visibility::RisesAndSets { rise, set } => t = if rising { rise } else { set },
+            _ => return None,
+        }
+    }
+    Some(t)
+}
+
+// Julian date (UTC) of local mean noon on the UTC calendar date of jd_utc
+pub fn local_noon(jd_utc: f64, site: Site) -> f64 {
+    let (year, month, day, _, _, _) = jd_to_civil(jd_utc);
+    civil_to_jd(year, month, day as f64 + 0.5) - site.lon.degrees() / 360.0
+}
+
+// the Sun's centre at sunset/sunrise and the three twilights
+pub const TWILIGHTS: [(&str, f64); 4] = [
+    ("sunset/sunrise", -0.833),
+    ("civil", -6.0),
+    ("nautical", -12.0),
+    ("astronomical", -18.0),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Twilight {
+    // Julian dates (UTC) the Sun goes below and comes back above the altitude
+    Night { dusk: f64, dawn: f64 },
+    AlwaysAbove,
+    AlwaysBelow,
+}
+
+// dusk and dawn through the Sun altitude (radians) for the night after local noon
+pub fn twilight(noon: f64, site: Site, altitude: f64) -> Twilight {
+    let dusk: Option<f64> = sun_crossing(noon, site, altitude, false);
+    let dawn: Option<f64> = sun_crossing(noon, site, altitude, true);
+    match (dusk, dawn) {
+        (Some(dusk), Some(dawn)) => Twilight::Night { dusk, dawn },
+        _ => {
+            // no crossing: the Sun stays on one side of this altitude all night
+            let (_, ra, dec, _): (f64, f64, f64, f64) = sun_position(noon + 0.5);
+            let midnight: RiseSet = rise_transit_set(noon, site, ra, dec, altitude);
+            if midnight.visibility == Visibility::Circumpolar {
+                Twilight::AlwaysAbove
+            } else {
+                Twilight::AlwaysBelow
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SunInfo {
+    // apparent place of date
+    pub ra: Angle,
+    pub dec: Angle,
+    pub distance_au: f64,
+    pub alt: Angle,
+    pub az: Angle,
+    // local mean noon the night is counted from, and the TWILIGHTS events for that night
+    pub noon: f64,
+    pub twilight: Vec<Twilight>,
+}
+
+pub fn sun(site: Site, jd_utc: f64, show_work: bool) -> Derived<SunInfo> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+
+    let (lambda, ra, dec, r): (f64, f64, f64, f64) = sun_position(jd_utc);
+    note!(trace, "Low precision solar coordinates, n = JD(TT) - 2451545.0");
+    value!(trace, ["n"], ["{}", utc_to_tt(jd_utc) - J2000_JD]);
+    note!(trace, "L = 280.460° + 0.9856474°n, g = 357.528° + 0.9856003°n");
+    note!(trace, "λ☉ = L + 1.915°sin(g) + 0.020°sin(2g), R = 1.00014 - 0.01671cos(g) - 0.00014cos(2g)");
+    value!(trace, ["λ☉"], ["{}°", lambda * (180.0 / PI)]);
+    value!(trace, ["R"], ["{} AU", r]);
+    note!(trace, "α = atan2(cos(ε)sin(λ☉), cos(λ☉)), δ = asin(sin(ε)sin(λ☉)), ε = 23.439° - 0.0000004°n");
+    value!(trace, ["α"], ["{}°", ra * (180.0 / PI)]);
+    value!(trace, ["δ"], ["{}°", dec * (180.0 / PI)]);
+
+    let ha: f64 = gast(jd_utc) + site.lon.radians() - ra;
+    let (alt, az): (f64, f64) = equatorial_to_horizontal(ha, dec, site.lat.radians());
+    note!(trace, "Altitude and azimuth (see `altaz`)");
+    value!(trace, ["H"], ["{}°", (ha * (180.0 / PI)).rem_euclid(360.0)]);
+    value!(trace, ["a"], ["{}°", alt * (180.0 / PI)]);
+    value!(trace, ["A"], ["{}°", az * (180.0 / PI)]);
+
+    let noon: f64 = local_noon(jd_utc, site);
+    note!(trace, "Twilight for the night after local mean noon, {} UTC", format_jd_iso(noon));
+    note!(trace, "iterate rise/set (see `riseset`) with the Sun's position at each crossing");
+    let events: Vec<Twilight> = TWILIGHTS
+        .iter()
+        .map(|(_, altitude)| {
+            let event: Twilight = twilight(noon, site, altitude * (PI / 180.0));
+            if let Twilight::Night { dusk, dawn } = event {
+                value!(trace, ["h = {}°: dusk, dawn", altitude], ["{}, {}", format_jd_iso(dusk), format_jd_iso(dawn)]);
+            } else {
+                value!(trace, ["h = {}°: dusk, dawn", altitude], ["none"]);
+            }
+            event
+        })
+        .collect();
+
+    Derived {
+        value: SunInfo {
+            ra: Angle::from_radians(ra),
+            dec: Angle::from_radians(dec),
+            distance_au: r,
+            alt: Angle::from_radians(alt),
+            az: Angle::from_radians(az),
+            noon,
+            twilight: events,
+        },
+        trace,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoonInfo {
+    // geocentric place of date
+    pub ra: Angle,
+    pub dec: Angle,
+    pub distance_km: f64,
+    // topocentric
+    pub alt: Angle,
+    pub az: Angle,
+    pub phase: &'static str,
+    pub waxing: bool,
+    pub illuminated: f64,
+    pub phase_angle: Angle,
+}
+
+pub fn moon(site: Site, jd_utc: f64, show_work: bool) -> Derived<MoonInfo> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+
+    let t: f64 = centuries_since_j2000(utc_to_tt(jd_utc));
+    let (lambda, ra, dec, distance): (f64, f64, f64, f64) = moon_position(jd_utc);
+    note!(trace, "Low precision lunar coordinates, T = {} centuries since J2000", t);
+    note!(trace, "λ☾ = 218.32° + 481267.881°T + 6.29°sin(135.0° + 477198.87°T) - 1.27°sin(259.3° - 413335.36°T) + …");
+    note!(trace, "β☾ = 5.13°sin(93.3° + 483202.02°T) + 0.28°sin(228.2° + 960400.89°T) - …");
+    note!(trace, "π☾ = 0.9508° + 0.0518°cos(135.0° + 477198.87°T) + …, Δ = R⊕ / sin(π☾)");
+    value!(trace, ["λ☾"], ["{}°", lambda * (180.0 / PI)]);
+    value!(trace, ["Δ"], ["{} km", distance]);
+    note!(trace, "Rotate by the obliquity of date (see `transform`)");
+    value!(trace, ["α"], ["{}°", ra * (180.0 / PI)]);
+    value!(trace, ["δ"], ["{}°", dec * (180.0 / PI)]);
+
+    let ha: f64 = gast(jd_utc) + site.lon.radians() - ra;
+    let (geo_alt, az): (f64, f64) = equatorial_to_horizontal(ha, dec, site.lat.radians());
+    let alt: f64 = topocentric_altitude(geo_alt, distance, site.elevation);
+    note!(trace, "Altitude and azimuth (see `altaz`), then the parallax of the observer");
+    note!(trace, "a' = atan2(sin(a) - ρ/Δ, cos(a))");
+    value!(trace, ["a"], ["{}°", geo_alt * (180.0 / PI)]);
+    value!(trace, ["a'"], ["{}°", alt * (180.0 / PI)]);
+    value!(trace, ["A"], ["{}°", az * (180.0 / PI)]);
+
+    let (fraction, phase_angle, waxing): (f64, f64, bool) = moon_illumination(jd_utc);
+    note!(trace, "Phase, with ψ the Sun-Moon elongation");
+    note!(trace, "i = atan2(R☉sin(ψ), Δ - R☉cos(ψ)), k = (1 + cos(i)) / 2");
+    value!(trace, ["i"], ["{}°", phase_angle * (180.0 / PI)]);
+    value!(trace, ["k"], ["{}", fraction]);
+
+    Derived {
+        value: MoonInfo {
+            ra: Angle::from_radians(ra),
+            dec: Angle::from_radians(dec),
+            distance_km: distance,
+            alt: Angle::from_radians(alt),
+            az: Angle::from_radians(az),
+            phase: moon_phase_name(jd_utc),
+            waxing,
+            illuminated: fraction,
+            phase_angle: Angle::from_radians(phase_angle),
+        },
+        trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(lat: f64, lon: f64) -> Site {
+        Site { lat: Angle::from_degrees(lat), lon: Angle::from_degrees(lon), elevation: 0.0 }
+    }
+
+    #[test]
+    fn sun_position_meeus() {
+        // Meeus example 25.a: 1992 October 13 0h TD, α = 198.38083°, δ = -7.78507°
+        let jd_utc: f64 = civil_to_jd(1992, 10, 13.0) - 59.0 / 86400.0;
+        let (_, ra, dec, r) = sun_position(jd_utc);
+        assert!((ra.to_degrees() - 198.38083).abs() < 0.02, "got {}", ra.to_degrees());
+        assert!((dec.to_degrees() + 7.78507).abs() < 0.02, "got {}", dec.to_degrees());
+        assert!((r - 0.99766).abs() < 1e-4, "got {}", r);
+    }
+
+    #[test]
+    fn moon_position_meeus() {
+        // Meeus example 47.a: 1992 April 12 0h TD, λ = 133.162655°, β = -3.229126°, Δ = 368409.7 km
+        let jd_utc: f64 = civil_to_jd(1992, 4, 12.0) - 59.0 / 86400.0;
+        let (lambda, ra, dec, distance) = moon_position(jd_utc);
+        assert!((lambda.to_degrees() - 133.162655).abs() < 0.3, "got {}", lambda.to_degrees());
+        assert!((ra.to_degrees() - 134.688470).abs() < 0.3, "got {}", ra.to_degrees());
+        assert!((dec.to_degrees() - 13.768368).abs() < 0.3, "got {}", dec.to_degrees());
+        assert!((distance - 368409.7).abs() < 1000.0, "got {}", distance);
+    }
+
+    #[test]
+    fn ecliptic_and_topocentric() {
+        // λ = 90° on the ecliptic is the solstice point, δ = ε
+        let eps: f64 = 23.44f64.to_radians();
+        let (ra, dec) = ecliptic_to_equatorial(PI / 2.0, 0.0, eps);
+        assert!((ra - PI / 2.0).abs() < 1e-12 && (dec - eps).abs() < 1e-12);
+        // the Moon on the horizon is lowered by its horizontal parallax, about 57′
+        let alt: f64 = topocentric_altitude(0.0, 384400.0, 0.0);
+        assert!((alt.to_degrees() + 0.951).abs() < 1e-3, "got {}", alt.to_degrees());
+    }
+
+    #[test]
+    fn moon_illumination_meeus() {
+        // Meeus example 48.a: 1992 April 12 0h TD, k = 0.6786
+        let (fraction, _, waxing) = moon_illumination(civil_to_jd(1992, 4, 12.0));
+        assert!((fraction - 0.6786).abs() < 0.01, "got {}", fraction);
+        assert!(waxing);
+        assert_eq!(moon_phase_name(civil_to_jd(1992, 4, 12.0)), "first quarter");
+        // full moon of 2024 April 23, 23:49 UTC
+        assert_eq!(moon_phase_name(civil_to_jd(2024, 4, 24.0)), "full moon");
+    }
+
+    #[test]
+    fn twilight_ordering() {
+        let site: Site = site(52.0, 5.0);
+        let noon: f64 = local_noon(civil_to_jd(2024, 12, 1.0), site);
+        let sunset: f64 = sun_crossing(noon, site, (-0.833f64).to_radians(), false).unwrap();
+        let dusk: f64 = sun_crossing(noon, site, (-18f64).to_radians(), false).unwrap();
+        let dawn: f64 = sun_crossing(noon, site, (-18f64).to_radians(), true).unwrap();
+        let sunrise: f64 = sun_crossing(noon, site, (-0.833f64).to_radians(), true).unwrap();
+        assert!(noon < sunset && sunset < dusk && dusk < dawn && dawn < sunrise);
+        // sunset in the Netherlands on 1 December is around 15:30 UTC
+        assert!(((sunset - civil_to_jd(2024, 12, 1.0)) * 24.0 - 15.5).abs() < 0.25);
+        // midsummer at 52°N never gets astronomically dark
+        let noon: f64 = local_noon(civil_to_jd(2024, 6, 21.0), site);
+        assert!(sun_crossing(noon, site, (-18f64).to_radians(), false).is_none());
+        assert_eq!(twilight(noon, site, (-18f64).to_radians()), Twilight::AlwaysAbove);
+    }
+
+    #[test]
+    fn polar_night() {
+        // at 78°N in December the Sun never comes up
+        let svalbard: Site = site(78.2, 15.6);
+        let info: SunInfo = sun(svalbard, civil_to_jd(2024, 12, 21.5), false).value;
+        assert!(info.alt.degrees() < 0.0);
+        assert_eq!(info.twilight[0], Twilight::AlwaysBelow);
+        assert!((info.noon - civil_to_jd(2024, 12, 21.5)).abs() < 0.1);
+    }
+
+    #[test]
+    fn moon_info_matches_parts() {
+        let jd: f64 = civil_to_jd(1992, 4, 12.0);
+        let info: Derived<MoonInfo> = moon(site(0.0, 0.0), jd, true);
+        let (fraction, _, _) = moon_illumination(jd);
+        assert_eq!(info.value.illuminated, fraction);
+        assert!(info.value.waxing);
+        assert!(info.trace.is_some());
+    }
+}
@@ -0,0 +1,66 @@
+// everything a command can fail with, and the exit code the CLI leaves with for each
+
+use crate::expr::ExprError;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    // a coordinate, time, site or other value that doesn't parse or is out of range
+    Input(String),
+    // a convert value that isn't a valid expression, shown with a caret under the bad token
+    Expression(ExprError),
+    // an unknown frame, time scale, unit, delimiter or output format
+    Usage(String),
+    // a table without the named columns or with rows that can't be read
+    Table(String),
+    // a file or stdin that can't be read, or stdout that can't be written
+    Io(String),
+}
+
+impl Error {
+    // 2 is what clap exits with for bad arguments, so usage errors match it
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Input(_) | Error::Expression(_) => 1,
+            Error::Usage(_) => 2,
+            Error::Table(_) => 3,
+            Error::Io(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Expression(e) => write!(f, "Invalid value: {}", e),
+            Error::Input(message) | Error::Usage(message) | Error::Table(message) | Error::Io(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ExprError> for Error {
+    fn from(e: ExprError) -> Error {
+        Error::Expression(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::evaluate;
+
+    #[test]
+    fn messages_and_exit_codes() {
+        let error: Error = evaluate("1 +").unwrap_err().into();
+        assert_eq!(error.to_string(), "Invalid value: expected a value but found end of input\n1 +\n   ^");
+        assert_eq!(error.exit_code(), 1);
+        assert_eq!(Error::Input("Invalid coordinate: 25h".to_string()).to_string(), "Invalid coordinate: 25h");
+        assert_eq!(Error::Usage(String::new()).exit_code(), 2);
+        assert_eq!(Error::Table(String::new()).exit_code(), 3);
+        assert_eq!(Error::Io(String::new()).exit_code(), 4);
+    }
+}
@@ -0,0 +1,463 @@
+// the showwork derivation as a LaTeX align* block or a Markdown document, for lecture notes.
+// steps are written in Unicode (α₁, √[…], x², °), which is translated to LaTeX math here
+
+use crate::render::{strip_ansi, Report};
+use crate::trace::Step;
+
+// one line of the document: a formula, or prose with bits of inline math
+#[derive(Clone, Debug, PartialEq)]
+pub enum Line {
+    Math(String),
+    Text(String),
+}
+
+// inputs, steps and then the result lines, classified and translated
+pub fn lines(report: &Report) -> (Vec<Line>, Vec<Line>) {
+    let mut work: Vec<Line> = report.inputs.iter().map(|i: &String| classify(i)).collect();
+    if let Some(trace) = &report.trace {
+        for step in &trace.steps {
+            work.push(match step {
+                Step::Note(text) => classify(text),
+                Step::Value { expr, result } => Line::Math(align(&format!("{} = {}", expr, result))),
+                Step::Matrix(m) => {
+                    let rows: Vec<String> = m.iter().map(|r: &[f64; 3]| format!("{} & {} & {}", r[0], r[1], r[2])).collect();
+                    Line::Math(format!("&\\begin{{pmatrix}} {} \\end{{pmatrix}}", rows.join(" \\\\ ")))
+                }
+            });
+        }
+    }
+    let results: Vec<Line> = report.lines.iter().map(|l: &String| classify(&strip_ansi(l))).collect();
+    (work, results)
+}
+
+pub fn latex(report: &Report) -> String {
+    let (work, results) = lines(report);
+    let mut entries: Vec<Line> = work;
+    if !entries.is_empty() {
+        entries.push(Line::Text("Result".to_string()));
+    }
+    entries.extend(results);
+
+    let mut out: String = String::from("\\begin{align*}\n");
+    for (i, entry) in entries.iter().enumerate() {
+        match entry {
+            Line::Text(text) => out.push_str(&format!("\\intertext{{{}}}\n", text_latex(text))),
+            Line::Math(math) => {
+                // amsmath wants \\ before an \intertext as well as between formulas
+                out.push_str(math);
+                out.push_str(if i + 1 < entries.len() { " \\\\\n" } else { "\n" });
+            }
+        }
+    }
+    out.push_str("\\end{align*}\n");
+    out
+}
+
+pub fn markdown(report: &Report) -> String {
+    let (work, results) = lines(report);
+    let mut out: String = String::new();
+    if !work.is_empty() {
+        out.push_str("## Working\n\n");
+        out.push_str(&markdown_blocks(&work));
+    }
+    out.push_str("## Result\n\n");
+    for line in results {
+        out.push_str(&match line {
+            Line::Math(math) => format!("- ${}$\n", math.replacen(" &=", " =", 1)),
+            Line::Text(text) => format!("- {}\n", text_markdown(&text)),
+        });
+    }
+    out
+}
+
+// runs of formulas in one $$ aligned block, prose as paragraphs
+fn markdown_blocks(lines: &[Line]) -> String {
+    let mut out: String = String::new();
+    let mut math: Vec<&str> = Vec::new();
+    for line in lines {
+        match line {
+            Line::Math(m) => math.push(m),
+            Line::Text(text) => {
+                out.push_str(&math_block(&math));
+                math.clear();
+                out.push_str(&format!("{}\n\n", text_markdown(text)));
+            }
+        }
+    }
+    out.push_str(&math_block(&math));
+    out
+}
+
+fn math_block(lines: &[&str]) -> String {
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!("$$\n\\begin{{aligned}}\n{}\n\\end{{aligned}}\n$$\n\n", lines.join(" \\\\\n"))
+}
+
+// a formula has an = and no run of plain words, e.g. "Rise when LST = α - H₀" is prose
+fn classify(line: &str) -> Line {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let prose: bool = words.windows(2).any(|pair: &[&str]| pair.iter().all(|w: &&str| is_word(w)));
+    if line.contains('=') && !prose {
+        Line::Math(align(line))
+    } else {
+        Line::Text(line.trim().to_string())
+    }
+}
+
+// at least three ASCII letters and maybe trailing punctuation, not a function call
+fn is_word(token: &str) -> bool {
+    let token: &str = token.trim_end_matches([',', '.', ':', ';', ')']).trim_start_matches('(');
+    token.len() >= 3 && token.chars().all(|c: char| c.is_ascii_alphabetic())
+}
+
+// the formula with its first = as the alignment point
+fn align(line: &str) -> String {
+    match line.split_once('=') {
+        Some((lhs, rhs)) => format!("{} &= {}", math_latex(lhs.trim()), math_latex(rhs.trim())),
+        None => math_latex(line),
+    }
+}
+
+fn greek(c: char) -> Option<&'static str> {
+    Some(match c {
+        'α' => "\\alpha",
+        'β' => "\\beta",
+        'γ' => "\\gamma",
+        'δ' => "\\delta",
+        'ε' => "\\varepsilon",
+        'ζ' => "\\zeta",
+        'η' => "\\eta",
+        'θ' => "\\theta",
+        'λ' => "\\lambda",
+        'μ' => "\\mu",
+        'ξ' => "\\xi",
+        'π' => "\\pi",
+        'ϖ' => "\\varpi",
+        'ρ' => "\\rho",
+        'σ' => "\\sigma",
+        'τ' => "\\tau",
+        'φ' => "\\phi",
+        'χ' => "\\chi",
+        'ψ' => "\\psi",
+        'ω' => "\\omega",
+        'Δ' => "\\Delta",
+        'Θ' => "\\Theta",
+        'Λ' => "\\Lambda",
+        'Π' => "\\Pi",
+        'Σ' => "\\Sigma",
+        'Φ' => "\\Phi",
+        'Ψ' => "\\Psi",
+        'Ω' => "\\Omega",
+        _ => return None,
+    })
+}
+
+fn symbol(c: char) -> Option<&'static str> {
+    Some(match c {
+        '*' | '·' => "\\cdot",
+        '×' => "\\times",
+        '≥' => "\\geq",
+        '≤' => "\\leq",
+        '≈' => "\\approx",
+        '→' => "\\to",
+        '±' => "\\pm",
+        '−' => "-",
+        '…' => "\\ldots",
+        '⌊' => "\\lfloor ",
+        '⌋' => "\\rfloor ",
+        '°' => "^\\circ",
+        '′' | '\'' => "'",
+        '″' | '"' => "''",
+        '☉' => "_\\odot",
+        // needs wasysym
+        '☾' => "_\\leftmoon",
+        '⊕' => "_\\oplus",
+        'ᵀ' => "^{\\mathsf{T}}",
+        '%' => "\\%",
+        '#' => "\\#",
+        '&' => "\\&",
+        '$' => "\\$",
+        '{' => "\\{",
+        '}' => "\\}",
+        _ => return None,
+    })
+}
+
+fn subscript(c: char) -> Option<char> {
+    "₀₁₂₃₄₅₆₇₈₉₊₋".chars().position(|s: char| s == c).map(|i: usize| "0123456789+-".chars().nth(i).unwrap())
+}
+
+fn superscript(c: char) -> Option<char> {
+    "⁰¹²³⁴⁵⁶⁷⁸⁹⁺⁻".chars().position(|s: char| s == c).map(|i: usize| "0123456789+-".chars().nth(i).unwrap())
+}
+
+// Unicode math as LaTeX math, e.g. √[(cos(δ₂))²] -> \sqrt{(\cos(\delta_{2}))^{2}}
+pub fn math_latex(math: &str) -> String {
+    let chars: Vec<char> = math.chars().collect();
+    let mut out: String = String::new();
+    // which open [ ( are the argument of a √
+    let mut roots: Vec<bool> = Vec::new();
+    let mut i: usize = 0;
+    while i < chars.len() {
+        let c: char = chars[i];
+        if c.is_ascii_alphabetic() {
+            let start: usize = i;
+            while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if word == "atan" && chars.get(i) == Some(&'2') {
+                out.push_str("\\operatorname{atan2}");
+                i += 1;
+                continue;
+            }
+            // a run of plain words, e.g. (east of north), is text so its spaces survive
+            let mut words: Vec<String> = vec![word.clone()];
+            while word.len() > 1 && chars.get(i) == Some(&' ') {
+                let end: usize = (i + 1..chars.len()).find(|j: &usize| !chars[*j].is_ascii_alphabetic()).unwrap_or(chars.len());
+                if end - (i + 1) < 2 || chars.get(end) == Some(&'(') {
+                    break;
+                }
+                words.push(chars[i + 1..end].iter().collect());
+                i = end;
+            }
+            if words.len() > 1 {
+                out.push_str(&format!("\\text{{{}}}", words.join(" ")));
+                continue;
+            }
+            out.push_str(&match word.as_str() {
+                "sin" | "cos" | "tan" | "ln" | "log" | "exp" => format!("\\{} ", word),
+                "mod" => "\\bmod ".to_string(),
+                "asin" | "acos" | "atan" => format!("\\arc{} ", &word[1..]),
+                _ if word.len() == 1 => word,
+                _ => format!("\\mathrm{{{}}}", word),
+            });
+            continue;
+        }
+        if subscript(c).is_some() {
+            let mut digits: String = String::new();
+            while let Some(d) = chars.get(i).and_then(|c: &char| subscript(*c)) {
+                digits.push(d);
+                i += 1;
+            }
+            out.push_str(&format!("_{{{}}}", digits));
+            continue;
+        }
+        if superscript(c).is_some() {
+            let mut digits: String = String::new();
+            while let Some(d) = chars.get(i).and_then(|c: &char| superscript(*c)) {
+                digits.push(d);
+                i += 1;
+            }
+            out.push_str(&format!("^{{{}}}", digits));
+            continue;
+        }
+        match c {
+            '√' if matches!(chars.get(i + 1), Some('[') | Some('(')) => {
+                out.push_str("\\sqrt{");
+                roots.push(true);
+                i += 2;
+                continue;
+            }
+            '√' => out.push_str("\\sqrt"),
+            '[' | '(' => {
+                roots.push(false);
+                out.push(c);
+            }
+            ']' | ')' => match roots.pop() {
+                Some(true) => out.push('}'),
+                _ => out.push(c),
+            },
+            '^' => {
+                // an ASCII power, x^-1.6364 -> x^{-1.6364}
+                let start: usize = i + 1;
+                let mut end: usize = start + usize::from(chars.get(start) == Some(&'-'));
+                while end < chars.len() && (chars[end].is_ascii_digit() || chars[end] == '.') {
+                    end += 1;
+                }
+                let power: String = chars[start..end].iter().collect();
+                out.push_str(&format!("^{{{}}}", power));
+                i = end;
+                continue;
+            }
+            '_' => {
+                // a_transit -> a_{\mathrm{transit}}
+                let start: usize = i + 1;
+                let mut end: usize = start;
+                while end < chars.len() && chars[end].is_ascii_alphanumeric() {
+                    end += 1;
+                }
+                let word: String = chars[start..end].iter().collect();
+                out.push_str(&format!("_{{\\mathrm{{{}}}}}", word));
+                i = end;
+                continue;
+            }
+            ' ' => {
+                // keep the gap between two values, 0.5 rad -> 0.5\ \mathrm{rad}
+                let before: Option<&char> = chars[..i].iter().rev().find(|c: &&char| **c != ' ');
+                let after: Option<&char> = chars.get(i + 1);
+                let unit: bool = before.is_some_and(|c: &char| c.is_ascii_alphanumeric() || "°′″".contains(*c)) && after.is_some_and(|c: &char| c.is_ascii_alphanumeric());
+                out.push_str(if unit { "\\ " } else { " " });
+            }
+            _ => match greek(c).or_else(|| symbol(c)) {
+                // a space so \alpha x doesn't run into \alphax
+                Some(latex) if latex.chars().last().is_some_and(|l: char| l.is_ascii_alphabetic()) => {
+                    out.push_str(latex);
+                    if chars.get(i + 1).is_some_and(|n: &char| n.is_ascii_alphanumeric()) {
+                        out.push(' ');
+                    }
+                }
+                Some(latex) => out.push_str(latex),
+                None => out.push(c),
+            },
+        }
+        i += 1;
+    }
+    out
+}
+
+// whether c belongs in inline math inside prose
+fn mathy(c: char) -> bool {
+    greek(c).is_some() || subscript(c).is_some() || superscript(c).is_some() || "°′″√≥≤≈→×·☉☾⊕ᵀ⌊⌋…".contains(c)
+}
+
+// runs of prose and inline math; a letter or digit right before a sub/superscript joins the math
+fn split_inline(text: &str) -> Vec<(bool, String)> {
+    let mut parts: Vec<(bool, String)> = Vec::new();
+    for c in text.chars() {
+        let is_math: bool = mathy(c);
+        match parts.last_mut() {
+            Some((true, run)) if is_math => run.push(c),
+            Some((false, run)) if !is_math => run.push(c),
+            Some((false, run)) if is_math && (subscript(c).is_some() || superscript(c).is_some() || c == '°') => {
+                let last: Option<char> = run.chars().last().filter(|l: &char| l.is_ascii_alphanumeric() || *l == '.');
+                match last {
+                    Some(_) => {
+                        // pull back the whole number or single letter the index belongs to
+                        let keep: usize = run.trim_end_matches(|l: char| l.is_ascii_digit() || l == '.').len();
+                        let keep: usize = if keep == run.len() { run.len() - 1 } else { keep };
+                        let moved: String = run.split_off(keep);
+                        parts.push((true, format!("{}{}", moved, c)));
+                    }
+                    None => parts.push((true, c.to_string())),
+                }
+            }
+            _ => parts.push((is_math, c.to_string())),
+        }
+    }
+    parts.retain(|(_, run)| !run.is_empty());
+    parts
+}
+
+// prose for \intertext: inline math in $…$, `code` in \texttt
+pub fn text_latex(text: &str) -> String {
+    let mut out: String = String::new();
+    for (is_math, run) in split_inline(text) {
+        if is_math {
+            out.push_str(&format!("${}$", math_latex(&run)));
+            continue;
+        }
+        let mut code: bool = false;
+        for c in run.chars() {
+            match c {
+                '`' => {
+                    out.push_str(if code { "}" } else { "\\texttt{" });
+                    code = !code;
+                }
+                '%' | '#' | '&' | '$' | '_' | '{' | '}' => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                '~' => out.push_str("\\textasciitilde{}"),
+                '^' => out.push_str("\\textasciicircum{}"),
+                '\\' => out.push_str("\\textbackslash{}"),
+                _ => out.push(c),
+            }
+        }
+        if code {
+            out.push('}');
+        }
+    }
+    out
+}
+
+// prose for markdown, inline math in $…$
+pub fn text_markdown(text: &str) -> String {
+    split_inline(text)
+        .into_iter()
+        .map(|(is_math, run)| if is_math { format!("${}$", math_latex(&run)) } else { run })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::Trace;
+
+    #[test]
+    fn unicode_to_latex() {
+        assert_eq!(math_latex("α₁"), "\\alpha_{1}");
+        assert_eq!(math_latex("√[(cos(δ₂)sin(Δα))²]"), "\\sqrt{(\\cos (\\delta_{2})\\sin (\\Delta\\alpha))^{2}}");
+        assert_eq!(math_latex("10 * π/180"), "10 \\cdot \\pi/180");
+        assert_eq!(math_latex("t⁻¹"), "t^{-1}");
+        assert_eq!(math_latex("(a + 6.07)^-1.63"), "(a + 6.07)^{-1.63}");
+        assert_eq!(math_latex("atan2(E, N)"), "\\operatorname{atan2}(E, N)");
+        assert_eq!(math_latex("0.5 rad"), "0.5\\ \\mathrm{rad}");
+        assert_eq!(math_latex("12.5°"), "12.5^\\circ");
+        assert_eq!(math_latex("0.98°n"), "0.98^\\circ n");
+        assert_eq!(math_latex("a_transit"), "a_{\\mathrm{transit}}");
+        assert_eq!(math_latex("L☉"), "L_\\odot");
+        assert_eq!(math_latex("42.9° (east of north)"), "42.9^\\circ (\\text{east of north})");
+        assert_eq!(math_latex("t (JD 2451545)"), "t (\\mathrm{JD}\\ 2451545)");
+        assert_eq!(math_latex("P(t₁)ᵀ"), "P(t_{1})^{\\mathsf{T}}");
+    }
+
+    #[test]
+    fn prose_and_formulas() {
+        assert_eq!(classify("Apply the Vincenty formula"), Line::Text("Apply the Vincenty formula".to_string()));
+        assert_eq!(classify("H = LST - α"), Line::Math("H &= \\mathrm{LST} - \\alpha".to_string()));
+        assert!(matches!(classify("Rise when LST = α - H₀, set when LST = α + H₀"), Line::Text(_)));
+        assert_eq!(text_latex("Convert θ to degrees"), "Convert $\\theta$ to degrees");
+        assert_eq!(text_latex("never drops below H₀ (see `riseset`), 100%"), "never drops below $H_{0}$ (see \\texttt{riseset}), 100\\%");
+        assert_eq!(text_markdown("above 20° for 3h"), "above $20^\\circ$ for 3h");
+    }
+
+    fn report() -> Report {
+        let mut trace: Trace = Trace::default();
+        trace.note("Convert values into radians".to_string());
+        trace.value("α₁ = 10 * π/180".to_string(), "0.17".to_string());
+        trace.matrix([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        let mut report: Report = Report { trace: Some(trace), ..Report::default() };
+        report.input("α₁ = 10".to_string());
+        report.line("θ = \x1b[1m0.5 rad\x1b[0m".to_string());
+        report
+    }
+
+    #[test]
+    fn latex_document() {
+        let out: String = latex(&report());
+        assert_eq!(
+            out,
+            "\\begin{align*}\n\
+             \\alpha_{1} &= 10 \\\\\n\
+             \\intertext{Convert values into radians}\n\
+             \\alpha_{1} &= 10 \\cdot \\pi/180 = 0.17 \\\\\n\
+             &\\begin{pmatrix} 1 & 0 & 0 \\\\ 0 & 1 & 0 \\\\ 0 & 0 & 1 \\end{pmatrix} \\\\\n\
+             \\intertext{Result}\n\
+             \\theta &= 0.5\\ \\mathrm{rad}\n\
+             \\end{align*}\n"
+        );
+    }
+
+    #[test]
+    fn markdown_document() {
+        let out: String = markdown(&report());
+        assert!(out.starts_with("## Working\n\n$$\n\\begin{aligned}\n\\alpha_{1} &= 10\n\\end{aligned}\n$$\n\nConvert values into radians\n\n$$"));
+        assert!(out.ends_with("## Result\n\n- $\\theta = 0.5\\ \\mathrm{rad}$\n"));
+        // without work there is only the result
+        let quiet: Report = Report { inputs: Vec::new(), trace: None, ..report() };
+        assert_eq!(markdown(&quiet), "## Result\n\n- $\\theta = 0.5\\ \\mathrm{rad}$\n");
+    }
+}
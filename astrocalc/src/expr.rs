@@ -0,0 +1,353 @@
+// a small calculator for angle input: arithmetic, parentheses, pi/tau and unit suffixes,
+// e.g. `3pi/2`, `2*pi - 0.1`, `45 + 30/60`, `12h + 30'`
+
+use crate::angle::{Angle, AngleUnit};
+use crate::PI;
+use std::fmt;
+use unicode_width::UnicodeWidthStr;
+
+// a plain number, or an angle if any part of the expression had a unit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quantity {
+    Number(f64),
+    Angle(Angle),
+}
+
+impl Quantity {
+    // an angle, reading a plain number in the given unit
+    pub fn angle(&self, bare: AngleUnit) -> Angle {
+        match self {
+            Quantity::Number(value) => bare.angle(*value),
+            Quantity::Angle(angle) => *angle,
+        }
+    }
+}
+
+// what went wrong and where, position being a byte offset into input
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExprError {
+    pub message: String,
+    pub input: String,
+    pub position: usize,
+}
+
+// the message, then the input with a caret under the offending token
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let indent: usize = UnicodeWidthStr::width(&self.input[..self.position]);
+        write!(f, "{}\n{}\n{}^", self.message, self.input, " ".repeat(indent))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Word(String),
+    Symbol(char),
+    End,
+}
+
+// a value with its power of angle: 0 for plain numbers, 1 for angles (held in radians)
+#[derive(Clone, Copy)]
+struct Value {
+    number: f64,
+    dimension: i32,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+}
+
+pub fn evaluate(input: &str) -> Result<Quantity, ExprError> {
+    let mut parser: Parser = Parser { input, tokens: tokenize(input)?, next: 0 };
+    let value: Value = parser.expression()?;
+    let (token, position): (Token, usize) = parser.peek();
+    if token != Token::End {
+        return Err(parser.error(format!("unexpected {}", describe(&token)), position));
+    }
+    match value.dimension {
+        0 => Ok(Quantity::Number(value.number)),
+        1 => Ok(Quantity::Angle(Angle::from_radians(value.number))),
+        _ => Err(parser.error("result is not an angle or a number".to_string(), 0)),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ExprError> {
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let chars: Vec<(usize, char)> = input.char_indices().collect();
+    let mut i: usize = 0;
+    while i < chars.len() {
+        let (start, c): (usize, char) = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end: usize = i;
+            while end < chars.len() && (chars[end].1.is_ascii_digit() || chars[end].1 == '.') {
+                end += 1;
+            }
+            // an exponent, only if digits follow so `2e` isn't swallowed
+            if end < chars.len() && matches!(chars[end].1, 'e' | 'E') {
+                let mut exp: usize = end + 1;
+                if exp < chars.len() && matches!(chars[exp].1, '+' | '-') {
+                    exp += 1;
+                }
+                if exp < chars.len() && chars[exp].1.is_ascii_digit() {
+                    end = exp;
+                    while end < chars.len() && chars[end].1.is_ascii_digit() {
+                        end += 1;
+                    }
+                }
+            }
+            let stop: usize = chars.get(end).map_or(input.len(), |(p, _)| *p);
+            let number: f64 = input[start..stop].parse().map_err(|_| ExprError {
+                message: format!("invalid number '{}'", &input[start..stop]),
+                input: input.to_string(),
+                position: start,
+            })?;
+            tokens.push((Token::Number(number), start));
+            i = end;
+        } else if c.is_alphabetic() {
+            let mut end: usize = i;
+            while end < chars.len() && chars[end].1.is_alphabetic() {
+                end += 1;
+            }
+            let stop: usize = chars.get(end).map_or(input.len(), |(p, _)| *p);
+            tokens.push((Token::Word(input[start..stop].to_lowercase()), start));
+            i = end;
+        } else if "+-−*×/÷()°'′\"″".contains(c) {
+            let symbol: char = match c {
+                '−' => '-',
+                '×' => '*',
+                '÷' => '/',
+                _ => c,
+            };
+            tokens.push((Token::Symbol(symbol), start));
+            i += 1;
+        } else {
+            return Err(ExprError {
+                message: format!("unexpected '{}'", c),
+                input: input.to_string(),
+                position: start,
+            });
+        }
+    }
+    tokens.push((Token::End, input.len()));
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(n) => format!("number {}", n),
+        Token::Word(w) => format!("'{}'", w),
+        Token::Symbol(c) => format!("'{}'", c),
+        Token::End => "end of input".to_string(),
+    }
+}
+
+// a unit suffix as radians per unit
+fn unit(token: &Token) -> Option<f64> {
+    match token {
+        Token::Word(w) => match w.as_str() {
+            "deg" | "d" => Some(PI / 180.0),
+            "rad" => Some(1.0),
+            "h" => Some(PI / 12.0),
+            _ => None,
+        },
+        Token::Symbol('°') => Some(PI / 180.0),
+        Token::Symbol('\'' | '′') => Some(PI / 180.0 / 60.0),
+        Token::Symbol('"' | '″') => Some(PI / 180.0 / 3600.0),
+        _ => None,
+    }
+}
+
+fn constant(token: &Token) -> Option<f64> {
+    match token {
+        Token::Word(w) => match w.as_str() {
+            "pi" | "π" => Some(PI),
+            "tau" | "τ" => Some(2.0 * PI),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> (Token, usize) {
+        self.tokens[self.next].clone()
+    }
+
+    fn advance(&mut self) -> (Token, usize) {
+        let token: (Token, usize) = self.peek();
+        if token.0 != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: String, position: usize) -> ExprError {
+        ExprError { message, input: self.input.to_string(), position }
+    }
+
+    // term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<Value, ExprError> {
+        let mut left: Value = self.term()?;
+        while let (Token::Symbol(op @ ('+' | '-')), position) = self.peek() {
+            self.advance();
+            let right: Value = self.term()?;
+            if left.dimension != right.dimension {
+                return Err(self.error(format!("cannot {} an angle and a plain number", if op == '+' { "add" } else { "subtract" }), position));
+            }
+            left.number = if op == '+' { left.number + right.number } else { left.number - right.number };
+        }
+        Ok(left)
+    }
+
+    // unary (('*' | '/') unary)*, a number, constant or bracket right after a value multiplies it (`3pi`)
+    fn term(&mut self) -> Result<Value, ExprError> {
+        let mut left: Value = self.unary()?;
+        loop {
+            let (token, position): (Token, usize) = self.peek();
+            match token {
+                Token::Symbol('*') => {
+                    self.advance();
+                    let right: Value = self.unary()?;
+                    left = Value { number: left.number * right.number, dimension: left.dimension + right.dimension };
+                }
+                Token::Symbol('/') => {
+                    self.advance();
+                    let right: Value = self.unary()?;
+                    if right.number == 0.0 {
+                        return Err(self.error("division by zero".to_string(), position));
+                    }
+                    left = Value { number: left.number / right.number, dimension: left.dimension - right.dimension };
+                }
+                Token::Number(_) | Token::Symbol('(') => {
+                    let right: Value = self.postfix()?;
+                    left = Value { number: left.number * right.number, dimension: left.dimension + right.dimension };
+                }
+                Token::Word(_) if constant(&token).is_some() => {
+                    let right: Value = self.postfix()?;
+                    left = Value { number: left.number * right.number, dimension: left.dimension + right.dimension };
+                }
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, ExprError> {
+        match self.peek().0 {
+            Token::Symbol('-') => {
+                self.advance();
+                let value: Value = self.unary()?;
+                Ok(Value { number: -value.number, dimension: value.dimension })
+            }
+            Token::Symbol('+') => {
+                self.advance();
+                self.unary()
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    // primary with an optional unit suffix
+    fn postfix(&mut self) -> Result<Value, ExprError> {
+        let value: Value = self.primary()?;
+        let (token, position): (Token, usize) = self.peek();
+        match unit(&token) {
+            Some(_) if value.dimension != 0 => Err(self.error(format!("{} after a value that already has a unit", describe(&token)), position)),
+            Some(scale) => {
+                self.advance();
+                Ok(Value { number: value.number * scale, dimension: 1 })
+            }
+            None => Ok(value),
+        }
+    }
+
+    // number | constant | '(' expression ')'
+    fn primary(&mut self) -> Result<Value, ExprError> {
+        let (token, position): (Token, usize) = self.advance();
+        match token {
+            Token::Number(number) => Ok(Value { number, dimension: 0 }),
+            Token::Symbol('(') => {
+                let value: Value = self.expression()?;
+                match self.advance() {
+                    (Token::Symbol(')'), _) => Ok(value),
+                    (other, at) => Err(self.error(format!("expected ')' but found {}", describe(&other)), at)),
+                }
+            }
+            Token::Word(ref w) => match constant(&token) {
+                Some(number) => Ok(Value { number, dimension: 0 }),
+                None => Err(self.error(format!("unknown name '{}'", w), position)),
+            },
+            other => Err(self.error(format!("expected a value but found {}", describe(&other)), position)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(input: &str) -> f64 {
+        match evaluate(input).unwrap() {
+            Quantity::Number(n) => n,
+            Quantity::Angle(a) => panic!("{} gave an angle {:?}", input, a),
+        }
+    }
+
+    fn degrees(input: &str) -> f64 {
+        match evaluate(input).unwrap() {
+            Quantity::Angle(a) => a.degrees(),
+            Quantity::Number(n) => panic!("{} gave a number {}", input, n),
+        }
+    }
+
+    #[test]
+    fn arithmetic_and_constants() {
+        assert_eq!(number("pi/4"), PI / 4.0);
+        assert_eq!(number("3pi/2"), 3.0 * PI / 2.0);
+        assert_eq!(number("2*pi - 0.1"), 2.0 * PI - 0.1);
+        assert_eq!(number("45 + 30/60"), 45.5);
+        assert_eq!(number("tau"), 2.0 * PI);
+        assert_eq!(number("-(1 + 2) * 3"), -9.0);
+        assert_eq!(number("2(3 + 1)"), 8.0);
+        assert_eq!(number("1.5e2 - 2E-1"), 149.8);
+        assert_eq!(number("1 - 2 - 3"), -4.0);
+        assert_eq!(number("8 / 4 / 2"), 1.0);
+        assert_eq!(number("π − 1"), PI - 1.0);
+    }
+
+    #[test]
+    fn unit_suffixes() {
+        assert!((degrees("45deg + 30'") - 45.5).abs() < 1e-12);
+        assert!((degrees("1h") - 15.0).abs() < 1e-12);
+        assert!((degrees("3600\"") - 1.0).abs() < 1e-12);
+        assert!((degrees("pi rad") - 180.0).abs() < 1e-12);
+        assert!((degrees("(10 + 20)°") - 30.0).abs() < 1e-12);
+        assert!((degrees("90deg / 2") - 45.0).abs() < 1e-12);
+        assert_eq!(number("90deg / 45deg"), 2.0);
+        let bare: Quantity = evaluate("90").unwrap();
+        assert!((bare.angle(AngleUnit::Degrees).radians() - PI / 2.0).abs() < 1e-15);
+        assert_eq!(bare.angle(AngleUnit::Radians).radians(), 90.0);
+    }
+
+    #[test]
+    fn errors_point_at_the_token() {
+        let error: ExprError = evaluate("2*pi - 0.1)").unwrap_err();
+        assert_eq!(error.position, 10);
+        assert_eq!(error.to_string(), "unexpected ')'\n2*pi - 0.1)\n          ^");
+        assert_eq!(evaluate("xpi").unwrap_err().message, "unknown name 'xpi'");
+        assert_eq!(evaluate("1 +").unwrap_err().position, 3);
+        assert_eq!(evaluate("(1 + 2").unwrap_err().message, "expected ')' but found end of input");
+        assert_eq!(evaluate("1 / 0").unwrap_err().position, 2);
+        assert_eq!(evaluate("1deg + 2").unwrap_err().position, 5);
+        assert_eq!(evaluate("1deg rad").unwrap_err().position, 5);
+        assert_eq!(evaluate("1deg * 1deg").unwrap_err().message, "result is not an angle or a number");
+        assert_eq!(evaluate("2 $ 3").unwrap_err().position, 2);
+        // the caret lines up under wide characters too
+        assert_eq!(evaluate("1° ?").unwrap_err().to_string(), "unexpected '?'\n1° ?\n   ^");
+        assert!(evaluate("").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+// rotations between the equatorial (ICRS), galactic and ecliptic frames
+
+use crate::angle::Angle;
+use crate::coords::{from_vector, mat_mul, mat_vec, to_vector, transpose};
+use crate::parse::CoordKind;
+use crate::trace::{Derived, Trace};
+use crate::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Frame {
+    Equatorial,
+    Galactic,
+    Ecliptic,
+}
+
+impl Frame {
+    pub fn parse(name: &str) -> Option<Frame> {
+        match name.to_lowercase().as_str() {
+            "equatorial" | "eq" | "icrs" | "j2000" | "radec" => Some(Frame::Equatorial),
+            "galactic" | "gal" => Some(Frame::Galactic),
+            "ecliptic" | "ecl" => Some(Frame::Ecliptic),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Frame::Equatorial => "equatorial",
+            Frame::Galactic => "galactic",
+            Frame::Ecliptic => "ecliptic",
+        }
+    }
+
+    // symbols for the longitude and latitude
+    pub fn symbols(&self) -> (&'static str, &'static str) {
+        match self {
+            Frame::Equatorial => ("α", "δ"),
+            Frame::Galactic => ("l", "b"),
+            Frame::Ecliptic => ("λ", "β"),
+        }
+    }
+
+    pub fn lon_kind(&self) -> CoordKind {
+        match self {
+            Frame::Equatorial => CoordKind::Ra,
+            _ => CoordKind::Lon,
+        }
+    }
+
+    // rotation taking an ICRS unit vector into this frame
+    #[allow(clippy::excessive_precision)]
+    pub fn icrs_rotation(&self) -> [[f64; 3]; 3] {
+        match self {
+            Frame::Equatorial => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            // Hipparcos definition of the galactic frame (ESA 1997, vol 1 sec 1.5.3)
+            Frame::Galactic => [
+                [-0.0548755604162154, -0.8734370902348850, -0.4838350155487132],
+                [0.4941094278755837, -0.4448296299600112, 0.7469822444972189],
+                [-0.8676661490190047, -0.1980763734312015, 0.4559837761750669],
+            ],
+            // rotation about x by the IAU 2006 J2000 mean obliquity, 84381.406″
+            Frame::Ecliptic => {
+                let eps: f64 = 84381.406 / 3600.0 * (PI / 180.0);
+                [[1.0, 0.0, 0.0], [0.0, eps.cos(), eps.sin()], [0.0, -eps.sin(), eps.cos()]]
+            }
+        }
+    }
+}
+
+// rotation matrix taking vectors in `from` into `to`
+pub fn frame_rotation(from: Frame, to: Frame) -> [[f64; 3]; 3] {
+    mat_mul(&to.icrs_rotation(), &transpose(&from.icrs_rotation()))
+}
+
+// converts a longitude/latitude pair in radians between frames
+pub fn transform_coords(from: Frame, to: Frame, lon: f64, lat: f64) -> (f64, f64) {
+    from_vector(mat_vec(&frame_rotation(from, to), to_vector(lon, lat)))
+}
+
+// longitude/latitude in `from` -> longitude/latitude in `to`
+pub fn transform(from: Frame, to: Frame, lon: Angle, lat: Angle, show_work: bool) -> Derived<(Angle, Angle)> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+    let (lon_sym, lat_sym): (&str, &str) = from.symbols();
+    let (out_lon_sym, out_lat_sym): (&str, &str) = to.symbols();
+
+    note!(trace, "Convert values into radians");
+    value!(trace, ["{}", lon_sym], ["{}", lon.radians()]);
+    value!(trace, ["{}", lat_sym], ["{}", lat.radians()]);
+
+    let v: [f64; 3] = to_vector(lon.radians(), lat.radians());
+    note!(trace, "Build the {} unit vector", from.name());
+    note!(trace, "v = (cos({1})cos({0}), cos({1})sin({0}), sin({1}))", lon_sym, lat_sym);
+    value!(trace, ["v"], ["({}, {}, {})", v[0], v[1], v[2]]);
+
+    let m: [[f64; 3]; 3] = frame_rotation(from, to);
+    note!(trace, "Rotate {} → {}", from.name(), to.name());
+    note!(trace, "v' = R v");
+    matrix!(trace, m);
+    let w: [f64; 3] = mat_vec(&m, v);
+    value!(trace, ["v'"], ["({}, {}, {})", w[0], w[1], w[2]]);
+
+    let (out_lon, out_lat): (f64, f64) = from_vector(w);
+    note!(trace, "Recover the angles");
+    note!(trace, "{} = atan2(v'y, v'x), {} = atan2(v'z, √(v'x² + v'y²))", out_lon_sym, out_lat_sym);
+    value!(trace, ["{}", out_lon_sym], ["{} rad = {}°", out_lon, out_lon * (180.0 / PI)]);
+    value!(trace, ["{}", out_lat_sym], ["{} rad = {}°", out_lat, out_lat * (180.0 / PI)]);
+
+    Derived { value: (Angle::from_radians(out_lon), Angle::from_radians(out_lat)), trace }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::angular_separation;
+
+    #[test]
+    fn galactic_centre_and_pole() {
+        let (ra, dec) = transform_coords(Frame::Galactic, Frame::Equatorial, 0.0, 0.0);
+        assert!((ra.to_degrees() - 266.40499).abs() < 1e-4, "got {}", ra.to_degrees());
+        assert!((dec.to_degrees() + 28.93617).abs() < 1e-4, "got {}", dec.to_degrees());
+
+        let (ra, dec) = transform_coords(Frame::Galactic, Frame::Equatorial, 0.0, PI / 2.0);
+        assert!((ra.to_degrees() - 192.85948).abs() < 1e-4, "got {}", ra.to_degrees());
+        assert!((dec.to_degrees() - 27.12825).abs() < 1e-4, "got {}", dec.to_degrees());
+    }
+
+    #[test]
+    fn ecliptic_round_trip() {
+        // the vernal equinox is on both the equator and the ecliptic, the NEP sits at α = 18h
+        let (lon, lat) = transform_coords(Frame::Ecliptic, Frame::Equatorial, 0.0, PI / 2.0);
+        assert!((lon.to_degrees() - 270.0).abs() < 1e-9);
+        assert!((lat.to_degrees() - (90.0 - 84381.406 / 3600.0)).abs() < 1e-9);
+
+        let (ra, dec) = (1.2, -0.4);
+        let (l, b) = transform_coords(Frame::Equatorial, Frame::Ecliptic, ra, dec);
+        let (l, b) = transform_coords(Frame::Ecliptic, Frame::Galactic, l, b);
+        let (ra2, dec2) = transform_coords(Frame::Galactic, Frame::Equatorial, l, b);
+        assert!(angular_separation(ra, dec, ra2, dec2) < 1e-12);
+    }
+
+    #[test]
+    fn frame_names() {
+        assert_eq!(Frame::parse("GAL"), Some(Frame::Galactic));
+        assert_eq!(Frame::parse("icrs"), Some(Frame::Equatorial));
+        assert_eq!(Frame::parse("supergalactic"), None);
+        assert_eq!(Frame::Ecliptic.symbols(), ("λ", "β"));
+        assert_eq!(Frame::Galactic.lon_kind(), CoordKind::Lon);
+    }
+
+    #[test]
+    fn transform_matches_raw_rotation() {
+        let result = transform(Frame::Equatorial, Frame::Galactic, Angle::from_degrees(83.633), Angle::from_degrees(22.0145), true);
+        let (l, b) = transform_coords(Frame::Equatorial, Frame::Galactic, 83.633f64.to_radians(), 22.0145f64.to_radians());
+        assert_eq!(result.value, (Angle::from_radians(l), Angle::from_radians(b)));
+        // the Crab nebula sits near l = 184.56°, b = -5.78°
+        assert!((result.value.0.degrees() - 184.557).abs() < 1e-2);
+        assert!((result.value.1.degrees() + 5.784).abs() < 1e-2);
+        assert!(result.trace.is_some());
+    }
+}
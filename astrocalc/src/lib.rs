@@ -0,0 +1,36 @@
+// astrocalc as a library: every calculation the CLI does, returning values (and, when asked
+// for, the derivation) instead of printing them
+
+pub const PI: f64 = std::f64::consts::PI;
+
+// first, so its macros are visible to the modules below
+#[macro_use]
+pub mod trace;
+
+pub mod angle;
+pub mod annual;
+pub mod atmosphere;
+pub mod config;
+pub mod convert;
+pub mod coords;
+pub mod crossmatch;
+pub mod error;
+pub mod ephemeris;
+pub mod export;
+pub mod expr;
+pub mod frames;
+pub mod motion;
+pub mod observer;
+pub mod orbit;
+pub mod parse;
+pub mod plan;
+pub mod planets;
+pub mod precession;
+pub mod render;
+pub mod table;
+pub mod time;
+
+pub use angle::Angle;
+pub use coords::{offset, separation, EquatorialCoord, Offset};
+pub use error::Error;
+pub use trace::{Derived, Step, Trace};
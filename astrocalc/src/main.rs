@@ -1,207 +1,2006 @@
-use clap::{Arg, Command};
-const PI:f32 = std::f32::consts::PI;
-use unicode_width::UnicodeWidthStr;
-use regex::Regex;
+use astrocalc::angle::{Angle, AngleUnit, Sexagesimal};
+use astrocalc::annual::{annual, AnnualPlace};
+use astrocalc::config::{config_path, Config, SitePreset, TargetPreset};
+use astrocalc::atmosphere::{refraction, AirmassModel, Refraction};
+use astrocalc::convert::{convert, convert_pair};
+use astrocalc::coords::{offset, separation, EquatorialCoord, Offset};
+use astrocalc::crossmatch::{crossmatch, table_coords};
+use astrocalc::error::Error;
+use astrocalc::ephemeris::{moon, sun, MoonInfo, SunInfo, Twilight, TWILIGHTS};
+use astrocalc::frames::{transform, Frame};
+use astrocalc::motion::{propagate, Astrometry};
+use astrocalc::orbit::{find_minor_body, minor_body, Brightness, MinorBody, MinorPosition, OrbitalElements};
+use astrocalc::observer::{horizontal, parse_site, rise_set, Horizontal, RiseSet, Site, Visibility, SIDEREAL_RATE};
+use astrocalc::plan::{altitude_chart, chart_symbol, clock, night, plan_target, Night, Target, TargetPlan};
+use astrocalc::planets::{elements_valid, planet, Planet, PlanetPosition, PLANETS};
+use astrocalc::parse::{parse_coord, CoordKind};
+use astrocalc::precession::{besselian_year, change_epoch, parse_epoch};
+use astrocalc::render::{bold, render_error, Format, Report};
+use astrocalc::table::{find_column, read_input, read_table, render_records, render_table};
+use astrocalc::time::{format_jd_iso, parse_time, time_scales, Instant, TimeScale, MJD_OFFSET, TT_MINUS_TAI};
+use astrocalc::PI;
+use clap::{Arg, ArgAction, Command};
+use std::path::PathBuf;
 
-fn strip_ansi(s: &str) -> String {
-    let ansi_regex: Regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
-    ansi_regex.replace_all(s, "").to_string()
+// parses α₁ δ₁ α₂ δ₂
+fn parse_coordinate_pair(bare: AngleUnit, values: [&str; 4]) -> Result<(EquatorialCoord, EquatorialCoord), String> {
+    Ok((
+        EquatorialCoord::parse(values[0], values[1], bare)?,
+        EquatorialCoord::parse(values[2], values[3], bare)?,
+    ))
 }
 
-fn gen_box(lines: &[String]) -> String {
-    let max_width: usize = lines.iter()
-        .map(|s: &String| UnicodeWidthStr::width(strip_ansi(s).as_str()))
-        .max()
-        .unwrap_or(0);
+fn echo_pair(report: &mut Report, values: [&str; 4], a: &EquatorialCoord, b: &EquatorialCoord) {
+    report.input(format!("α₁ = {} = {}°", values[0].trim(), a.ra.degrees()));
+    report.input(format!("δ₁ = {} = {}°", values[1].trim(), a.dec.degrees()));
+    report.input(format!("α₂ = {} = {}°", values[2].trim(), b.ra.degrees()));
+    report.input(format!("δ₂ = {} = {}°", values[3].trim(), b.dec.degrees()));
+}
+
+fn calc_distance(format: Format, bare: AngleUnit, show_work: bool, a1:String, d1:String, a2:String, d2:String) -> Result<String, Error> {
+    let values: [&str; 4] = [&a1, &d1, &a2, &d2];
+    let (a, b): (EquatorialCoord, EquatorialCoord) = parse_coordinate_pair(bare, values).map_err(|e| Error::Input(format!("Invalid coordinate: {}", e)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        echo_pair(&mut report, values, &a, &b);
+    }
+
+    let theta = separation(&a, &b, show_work);
+    report.trace = theta.trace;
+    let parts: Sexagesimal = theta.value.dms();
+    let dms: String = format!("{}°{}′{}″", parts.whole, parts.minutes, parts.seconds);
+    report.line(format!("θ = {}", bold(&theta.value.format_radians())));
+    report.line(format!("θ = {}", bold(&dms)));
+    report.line(format!("θ = {}", bold(&theta.value.format_arcseconds())));
+    report.field("separation_rad", theta.value.radians());
+    report.field("separation_deg", theta.value.degrees());
+    report.field("separation_dms", dms);
+    report.field("separation_arcsec", theta.value.arcseconds());
+    Ok(report.render(format))
+}
+
+// position angle (east of north) from the first point to the second and the tangent-plane
+// offsets of the second point around the first
+fn calc_offset(format: Format, bare: AngleUnit, show_work: bool, a1:String, d1:String, a2:String, d2:String) -> Result<String, Error> {
+    let values: [&str; 4] = [&a1, &d1, &a2, &d2];
+    let (a, b): (EquatorialCoord, EquatorialCoord) = parse_coordinate_pair(bare, values).map_err(|e| Error::Input(format!("Invalid coordinate: {}", e)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        echo_pair(&mut report, values, &a, &b);
+    }
+
+    let result = offset(&a, &b, show_work);
+    report.trace = result.trace;
+    let offset: Offset = result.value;
+    report.line(format!("PA = {} (east of north)", bold(&format!("{}°", offset.position_angle.degrees()))));
+    report.line(format!("θ = {}", bold(&format!("{}″", offset.separation.arcseconds()))));
+    match offset.tangent {
+        Some((xi, eta)) => {
+            report.line(format!("Δα·cos(δ) = {}", bold(&format!("{}″", xi.arcseconds()))));
+            report.line(format!("Δδ = {}", bold(&format!("{}″", eta.arcseconds()))));
+        }
+        None => report.line("Δα·cos(δ), Δδ undefined (θ ≥ 90°)".to_string()),
+    }
+    report.field("position_angle_deg", offset.position_angle.degrees());
+    report.field("separation_arcsec", offset.separation.arcseconds());
+    report.field("delta_ra_cos_dec_arcsec", offset.tangent.map(|(xi, _)| xi.arcseconds()));
+    report.field("delta_dec_arcsec", offset.tangent.map(|(_, eta)| eta.arcseconds()));
+    Ok(report.render(format))
+}
+
+fn calc_transform(format: Format, from: String, to: String, bare: AngleUnit, show_work: bool, lon: String, lat: String) -> Result<String, Error> {
+    let (from, to): (Frame, Frame) = match (Frame::parse(&from), Frame::parse(&to)) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return Err(Error::Usage("Invalid frame, use equatorial | galactic | ecliptic".to_string())),
+    };
+    let parsed: Result<(Angle, Angle), String> = parse_coord(&lon, from.lon_kind(), bare)
+        .and_then(|l: Angle| Ok((l, parse_coord(&lat, CoordKind::Dec, bare)?)));
+    let (lon_angle, lat_angle): (Angle, Angle) = parsed.map_err(|e| Error::Input(format!("Invalid coordinate: {}", e)))?;
+    let (lon_sym, lat_sym): (&str, &str) = from.symbols();
+    let (out_lon_sym, out_lat_sym): (&str, &str) = to.symbols();
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("{} = {} = {}°", lon_sym, lon.trim(), lon_angle.degrees()));
+        report.input(format!("{} = {} = {}°", lat_sym, lat.trim(), lat_angle.degrees()));
+    }
+
+    let result = transform(from, to, lon_angle, lat_angle, show_work);
+    report.trace = result.trace;
+    let (out_lon, out_lat): (Angle, Angle) = result.value;
+    report.line(if to == Frame::Equatorial {
+        format!("{} = {} ({}°)", out_lon_sym, bold(&out_lon.format_hms()), out_lon.degrees())
+    } else {
+        format!("{} = {}", out_lon_sym, bold(&format!("{}°", out_lon.degrees())))
+    });
+    report.line(format!("{} = {} ({}°)", out_lat_sym, bold(&out_lat.format_dms()), out_lat.degrees()));
+    report.field("frame", to.name());
+    report.field("lon_deg", out_lon.degrees());
+    report.field("lat_deg", out_lat.degrees());
+    Ok(report.render(format))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn calc_epoch(format: Format, from: String, to: String, bare: AngleUnit, show_work: bool, ra: String, dec: String, nutate: bool) -> Result<String, Error> {
+    let coord: EquatorialCoord = EquatorialCoord::parse(&ra, &dec, bare).map_err(|e| Error::Input(format!("Invalid coordinate: {}", e)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("α = {} = {}°", ra.trim(), coord.ra.degrees()));
+        report.input(format!("δ = {} = {}°", dec.trim(), coord.dec.degrees()));
+        report.input(format!("t₁ = {}, t₂ = {}", from.trim(), to.trim()));
+    }
+
+    let result = change_epoch(&from, &to, nutate, &coord, show_work).map_err(Error::Input)?;
+    report.trace = result.trace;
+    let out: EquatorialCoord = result.value;
+    let place: &str = if nutate { "true" } else { "mean" };
+    let system: &str = if besselian_year(&to).is_some() { " (FK4)" } else { "" };
+    report.line(format!("{} place, equinox {}{}", place, to.trim(), system));
+    report.line(format!("α = {} ({}°)", bold(&out.ra.format_hms()), out.ra.degrees()));
+    report.line(format!("δ = {} ({}°)", bold(&out.dec.format_dms()), out.dec.degrees()));
+    report.field("place", place);
+    report.field("equinox", to.trim());
+    report.field("ra_deg", out.ra.degrees());
+    report.field("dec_deg", out.dec.degrees());
+    report.field("ra_hms", out.ra.format_hms());
+    report.field("dec_dms", out.dec.format_dms());
+    Ok(report.render(format))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn calc_propagate(format: Format, from: String, to: String, bare: AngleUnit, show_work: bool, values: [String; 4], parallax: Option<String>, rv: Option<String>) -> Result<String, Error> {
+    let jd_from: f64 = parse_epoch(&from).map_err(Error::Input)?;
+    let jd_to: f64 = parse_epoch(&to).map_err(Error::Input)?;
+    let coord: EquatorialCoord = EquatorialCoord::parse(&values[0], &values[1], bare).map_err(|e| Error::Input(format!("Invalid coordinate: {}", e)))?;
+    // ζ₀ = vr ϖ / A, so a radial velocity without a parallax would do nothing
+    if rv.is_some() && parallax.is_none() {
+        return Err(Error::Usage("--rv needs --parallax".to_string()));
+    }
+    let number = |name: &str, value: Option<&String>| -> Result<f64, Error> {
+        match value {
+            Some(v) => v.trim().parse::<f64>().map_err(|_| Error::Input(format!("Invalid {}: {}", name, v))),
+            None => Ok(0.0),
+        }
+    };
+    let star: Astrometry = Astrometry {
+        coord,
+        pm_ra_cos_dec: number("proper motion μα*", Some(&values[2]))?,
+        pm_dec: number("proper motion μδ", Some(&values[3]))?,
+        parallax: number("parallax", parallax.as_ref())?,
+        radial_velocity: number("radial velocity", rv.as_ref())?,
+    };
+    if star.parallax < 0.0 {
+        return Err(Error::Input(format!("Invalid parallax: {} mas, must not be negative", star.parallax)));
+    }
+    let years: f64 = (jd_to - jd_from) / 365.25;
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("α = {} = {}°, δ = {} = {}° at {}", values[0].trim(), coord.ra.degrees(), values[1].trim(), coord.dec.degrees(), from.trim()));
+        report.input(format!("μα* = {} mas/yr, μδ = {} mas/yr", star.pm_ra_cos_dec, star.pm_dec));
+        report.input(format!("ϖ = {} mas, vr = {} km/s", star.parallax, star.radial_velocity));
+        report.input(format!("t = (JD {} - JD {}) / 365.25 = {} yr", jd_to, jd_from, years));
+    }
+
+    let result = propagate(&star, years, show_work);
+    report.trace = result.trace;
+    let out: Astrometry = result.value;
+    report.line(format!("position at {}", to.trim()));
+    report.line(format!("α = {} ({}°)", bold(&out.coord.ra.format_hms()), out.coord.ra.degrees()));
+    report.line(format!("δ = {} ({}°)", bold(&out.coord.dec.format_dms()), out.coord.dec.degrees()));
+    report.line(format!("μα* = {} mas/yr, μδ = {} mas/yr", bold(&format!("{:.4}", out.pm_ra_cos_dec)), bold(&format!("{:.4}", out.pm_dec))));
+    if star.parallax > 0.0 {
+        report.line(format!("ϖ = {} mas, vr = {} km/s", bold(&format!("{:.4}", out.parallax)), bold(&format!("{:.4}", out.radial_velocity))));
+    }
+    report.field("epoch", to.trim());
+    report.field("ra_deg", out.coord.ra.degrees());
+    report.field("dec_deg", out.coord.dec.degrees());
+    report.field("ra_hms", out.coord.ra.format_hms());
+    report.field("dec_dms", out.coord.dec.format_dms());
+    report.field("pm_ra_cos_dec_mas_yr", out.pm_ra_cos_dec);
+    report.field("pm_dec_mas_yr", out.pm_dec);
+    report.field("parallax_mas", out.parallax);
+    report.field("radial_velocity_km_s", out.radial_velocity);
+    Ok(report.render(format))
+}
+
+fn calc_annual(format: Format, bare: AngleUnit, show_work: bool, ra: String, dec: String, time: String, parallax: Option<String>) -> Result<String, Error> {
+    let coord: EquatorialCoord = EquatorialCoord::parse(&ra, &dec, bare).map_err(|e| Error::Input(format!("Invalid coordinate: {}", e)))?;
+    let jd_utc: f64 = parse_time(&time).map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+    let parallax_mas: f64 = match parallax {
+        Some(p) => p.trim().parse::<f64>().ok().filter(|p: &f64| *p >= 0.0).ok_or_else(|| Error::Input(format!("Invalid parallax: {}", p)))?,
+        None => 0.0,
+    };
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("α = {} = {}° (J2000)", ra.trim(), coord.ra.degrees()));
+        report.input(format!("δ = {} = {}° (J2000)", dec.trim(), coord.dec.degrees()));
+        report.input(format!("ϖ = {} mas", parallax_mas));
+        report.input(format!("t = {} UTC (JD {})", format_jd_iso(jd_utc), jd_utc));
+    }
+
+    let result = annual(&coord, parallax_mas, jd_utc, show_work);
+    report.trace = result.trace;
+    let place: AnnualPlace = result.value;
+    // shifts on the sky in arcseconds, Δα cos δ and Δδ
+    let shift = |a: &EquatorialCoord, b: &EquatorialCoord| -> (f64, f64) {
+        ((b.ra - a.ra).wrapped().arcseconds() * a.dec.cos(), (b.dec - a.dec).arcseconds())
+    };
+    let (par_ra, par_dec): (f64, f64) = shift(&coord, &place.parallax);
+    let (ab_ra, ab_dec): (f64, f64) = shift(&place.parallax, &place.apparent);
+    let out: EquatorialCoord = place.apparent;
+    report.line(format!("α = {} ({}°)", bold(&out.ra.format_hms()), out.ra.degrees()));
+    report.line(format!("δ = {} ({}°)", bold(&out.dec.format_dms()), out.dec.degrees()));
+    report.line(format!("parallax:   Δα cos δ = {:+.4}″, Δδ = {:+.4}″", par_ra, par_dec));
+    report.line(format!("aberration: Δα cos δ = {:+.4}″, Δδ = {:+.4}″", ab_ra, ab_dec));
+    report.field("ra_deg", out.ra.degrees());
+    report.field("dec_deg", out.dec.degrees());
+    report.field("ra_hms", out.ra.format_hms());
+    report.field("dec_dms", out.dec.format_dms());
+    report.field("parallax_ra_cos_dec_arcsec", par_ra);
+    report.field("parallax_dec_arcsec", par_dec);
+    report.field("aberration_ra_cos_dec_arcsec", ab_ra);
+    report.field("aberration_dec_arcsec", ab_dec);
+    Ok(report.render(format))
+}
+
+fn calc_time(format: Format, show_work: bool, input: String, scale: String, longitude: Option<String>) -> Result<String, Error> {
+    let scale: TimeScale = TimeScale::parse(&scale).ok_or_else(|| Error::Usage("Invalid time scale, use utc | tai | tt".to_string()))?;
+    let jd: f64 = parse_time(&input).map_err(Error::Input)?;
+    let longitude: Option<Angle> = match longitude {
+        Some(l) => {
+            Some(parse_coord(&l, CoordKind::SiteLon, AngleUnit::Degrees).map_err(|e| Error::Input(format!("Invalid longitude: {}", e)))?)
+        }
+        None => None,
+    };
+
+    let result = time_scales(jd, scale, longitude, show_work);
+    let mut report: Report = Report { trace: result.trace, ..Report::default() };
+    let instant: Instant = result.value;
+    report.line(format!("UTC = {}", bold(&format_jd_iso(instant.utc))));
+    report.line(format!("TAI = {}", bold(&format_jd_iso(instant.tai))));
+    report.line(format!("TT  = {}", bold(&format_jd_iso(instant.tt))));
+    report.line(format!("JD  = {} (UTC), {:.6} (TT)", bold(&format!("{:.6}", instant.utc)), instant.tt));
+    report.line(format!("MJD = {} (UTC), {:.6} (TT)", bold(&format!("{:.6}", instant.utc - MJD_OFFSET)), instant.tt - MJD_OFFSET));
+    report.line(format!("TAI - UTC = {} s, TT - TAI = {} s", instant.leap_seconds, TT_MINUS_TAI));
+    report.line(format!("GMST = {}", bold(&instant.gmst.format_hms())));
+    if let (Some(lst), Some(longitude)) = (instant.lst, longitude) {
+        report.line(format!("LST = {} (λ = {}°)", bold(&lst.format_hms()), longitude.degrees()));
+    }
+    report.field("utc", format_jd_iso(instant.utc));
+    report.field("tai", format_jd_iso(instant.tai));
+    report.field("tt", format_jd_iso(instant.tt));
+    report.field("jd_utc", instant.utc);
+    report.field("jd_tt", instant.tt);
+    report.field("mjd_utc", instant.utc - MJD_OFFSET);
+    report.field("mjd_tt", instant.tt - MJD_OFFSET);
+    report.field("tai_minus_utc_s", instant.leap_seconds);
+    report.field("gmst_deg", instant.gmst.degrees());
+    report.field("lst_deg", instant.lst.map(|lst: Angle| lst.degrees()));
+    Ok(report.render(format))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn calc_altaz(format: Format, bare: AngleUnit, show_work: bool, ra: String, dec: String, lat: String, lon: String, time: String, elevation: Option<String>) -> Result<String, Error> {
+    let parsed: Result<(EquatorialCoord, Site, f64), String> = (|| {
+        Ok((
+            EquatorialCoord::parse(&ra, &dec, bare)?,
+            parse_site(&lat, &lon, elevation.as_deref())?,
+            parse_time(&time)?,
+        ))
+    })();
+    let (coord, site, jd_utc): (EquatorialCoord, Site, f64) = parsed.map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("α = {} = {}° (J2000)", ra.trim(), coord.ra.degrees()));
+        report.input(format!("δ = {} = {}° (J2000)", dec.trim(), coord.dec.degrees()));
+        report.input(format!("φ = {}°, λ = {}°, h = {} m", site.lat.degrees(), site.lon.degrees(), site.elevation));
+        report.input(format!("t = {} UTC (JD {})", format_jd_iso(jd_utc), jd_utc));
+    }
 
-    let mut output: String = String::new();
-    output.push_str(&format!("┏{}┓\n", "━".repeat(max_width)));
+    let result = horizontal(&coord, site, jd_utc, show_work);
+    report.trace = result.trace;
+    let h: Horizontal = result.value;
+    report.line(format!("alt = {} ({}°)", bold(&h.alt.format_dms()), h.alt.degrees()));
+    report.line(format!("az = {} (north through east)", bold(&format!("{}°", h.az.degrees()))));
+    report.line(format!("H = {}", bold(&h.hour_angle.format_signed_hms())));
+    report.line(format!("LST = {}", bold(&h.lst.format_hms())));
+    match h.airmass {
+        Some(x) => report.line(format!("X = {}", bold(&format!("{:.4}", x)))),
+        None => report.line("X = below horizon".to_string()),
+    }
+    report.line(format!("q = {}", bold(&format!("{}°", h.parallactic_angle.degrees()))));
+    if let Some(dip) = h.dip {
+        report.line(format!("horizon dip = {}", bold(&format!("{:.3}°", dip.degrees()))));
+    }
+    report.field("alt_deg", h.alt.degrees());
+    report.field("az_deg", h.az.degrees());
+    report.field("hour_angle_deg", h.hour_angle.wrapped().degrees());
+    report.field("lst_deg", h.lst.degrees());
+    report.field("airmass", h.airmass);
+    report.field("parallactic_angle_deg", h.parallactic_angle.degrees());
+    report.field("dip_deg", h.dip.map(|dip: Angle| dip.degrees()));
+    Ok(report.render(format))
+}
 
-    for line in lines {
-        let visible_width: usize = UnicodeWidthStr::width(strip_ansi(line).as_str());
-        let padding: usize = max_width - visible_width;
-        output.push_str(&format!("┃{}{}┃\n", line, " ".repeat(padding)));
+fn calc_refraction(format: Format, show_work: bool, altitude: String, apparent: bool, pressure: String, temperature: String) -> Result<String, Error> {
+    let alt: f64 = parse_coord(&altitude, CoordKind::Dec, AngleUnit::Degrees).map_err(|e| Error::Input(format!("Invalid altitude: {}", e)))?.degrees();
+    // both formulas diverge a few degrees below the horizon
+    if !(-1.0..=90.0).contains(&alt) {
+        return Err(Error::Input(format!("Invalid altitude: {}, refraction needs -1° to 90°", altitude.trim())));
+    }
+    let pressure_hpa: f64 = pressure.trim().parse::<f64>().ok().filter(|p: &f64| *p >= 0.0).ok_or_else(|| Error::Input(format!("Invalid pressure: {}", pressure)))?;
+    let temperature_c: f64 = temperature.trim().parse::<f64>().ok().filter(|t: &f64| *t > -273.0).ok_or_else(|| Error::Input(format!("Invalid temperature: {}", temperature)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("{} = {} = {}°", if apparent { "h'" } else { "h" }, altitude.trim(), alt));
+        report.input(format!("P = {} hPa, T = {} °C", pressure_hpa, temperature_c));
+    }
+
+    let result = refraction(alt, apparent, pressure_hpa, temperature_c, show_work);
+    report.trace = result.trace;
+    let r: Refraction = result.value;
+    report.line(format!("true altitude = {} ({}°)", bold(&Angle::from_degrees(r.true_alt).format_dms()), r.true_alt));
+    report.line(format!("apparent altitude = {} ({}°)", bold(&Angle::from_degrees(r.apparent_alt).format_dms()), r.apparent_alt));
+    report.line(format!("R = {} ({:.2}″, {})", bold(&format!("{:.4}′", r.refraction)), r.refraction * 60.0, if apparent { "Bennett" } else { "Saemundsson" }));
+    for (model, x) in &r.airmass {
+        match x {
+            Some(x) => report.line(format!("X = {} ({})", bold(&format!("{:.4}", x)), model.name())),
+            None => report.line(format!("X = below horizon ({})", model.name())),
+        }
     }
+    report.field("true_alt_deg", r.true_alt);
+    report.field("apparent_alt_deg", r.apparent_alt);
+    report.field("refraction_arcmin", r.refraction);
+    report.field("pressure_hpa", pressure_hpa);
+    report.field("temperature_c", temperature_c);
+    for (model, x) in &r.airmass {
+        let key: &str = match model {
+            AirmassModel::PlaneParallel => "airmass_plane_parallel",
+            AirmassModel::KastenYoung => "airmass_kasten_young",
+            AirmassModel::Pickering => "airmass_pickering",
+        };
+        report.field(key, *x);
+    }
+    Ok(report.render(format))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn calc_riseset(format: Format, bare: AngleUnit, show_work: bool, ra: String, dec: String, lat: String, lon: String, date: String, horizon: String) -> Result<String, Error> {
+    let parsed: Result<(EquatorialCoord, Site, f64, Angle), String> = (|| {
+        Ok((
+            EquatorialCoord::parse(&ra, &dec, bare)?,
+            parse_site(&lat, &lon, None)?,
+            parse_time(&date)?,
+            parse_coord(&horizon, CoordKind::Dec, AngleUnit::Degrees)?,
+        ))
+    })();
+    let (coord, site, jd_start, horizon): (EquatorialCoord, Site, f64, Angle) = parsed.map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("α = {} = {}° (J2000)", ra.trim(), coord.ra.degrees()));
+        report.input(format!("δ = {} = {}° (J2000)", dec.trim(), coord.dec.degrees()));
+        report.input(format!("φ = {}°, λ = {}°, h₀ = {}°", site.lat.degrees(), site.lon.degrees(), horizon.degrees()));
+        report.input(format!("search window: 24 h from {} UTC", format_jd_iso(jd_start)));
+    }
+
+    let result = rise_set(&coord, site, jd_start, horizon, show_work);
+    report.trace = result.trace;
+    let result: RiseSet = result.value;
+    let (rise, set): (Option<f64>, Option<f64>) = match result.visibility {
+        Visibility::RisesAndSets { rise, set } => {
+            // rise and set are each the next crossing, so set may come first in the window
+            let up: f64 = (set - rise).rem_euclid(1.0 / SIDEREAL_RATE) * 360.0;
+            report.line(format!("rise    = {} UTC", bold(&format_jd_iso(rise))));
+            report.line(format!("transit = {} UTC", bold(&format_jd_iso(result.transit))));
+            report.line(format!("set     = {} UTC", bold(&format_jd_iso(set))));
+            report.line(format!("above {}° for {}", horizon.degrees(), Angle::from_degrees(up).format_hms()));
+            report.field("visibility", "rises and sets");
+            report.field("hours_up", up / 15.0);
+            (Some(rise), Some(set))
+        }
+        Visibility::Circumpolar => {
+            report.line(format!("circumpolar, always above {}°", horizon.degrees()));
+            report.line(format!("transit = {} UTC", bold(&format_jd_iso(result.transit))));
+            report.field("visibility", "circumpolar");
+            report.field("hours_up", 24.0);
+            (None, None)
+        }
+        Visibility::NeverRises => {
+            report.line(format!("never rises above {}°", horizon.degrees()));
+            report.line(format!("transit = {} UTC", bold(&format_jd_iso(result.transit))));
+            report.field("visibility", "never rises");
+            report.field("hours_up", 0.0);
+            (None, None)
+        }
+    };
+    report.line(format!("transit altitude = {}", bold(&Angle::from_radians(result.transit_alt).format_dms())));
+    report.field("rise", rise.map(format_jd_iso));
+    report.field("transit", format_jd_iso(result.transit));
+    report.field("set", set.map(format_jd_iso));
+    report.field("transit_alt_deg", result.transit_alt * (180.0 / PI));
+    Ok(report.render(format))
+}
 
-    output.push_str(&format!("┗{}┛", "━".repeat(max_width)));
-    output
+fn parse_body_args(lat: &str, lon: &str, time: &str, elevation: Option<&str>) -> Result<(Site, f64), String> {
+    Ok((parse_site(lat, lon, elevation)?, parse_time(time)?))
 }
 
+fn calc_sun(format: Format, show_work: bool, lat: String, lon: String, time: String, elevation: Option<String>) -> Result<String, Error> {
+    let (site, jd_utc): (Site, f64) = parse_body_args(&lat, &lon, &time, elevation.as_deref()).map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("φ = {}°, λ = {}°, t = {} UTC", site.lat.degrees(), site.lon.degrees(), format_jd_iso(jd_utc)));
+    }
 
-fn calc_distance(isdeg:String, showwork:String, a1:f32, d1:f32, a2:f32, d2:f32) {
-    macro_rules! verbose {
-        ($($arg:tt)*) => {
-            if showwork == "true" {
-                println!($($arg)*);
+    let result = sun(site, jd_utc, show_work);
+    report.trace = result.trace;
+    let info: SunInfo = result.value;
+    report.line(format!("α = {}, δ = {} (apparent)", bold(&info.ra.format_hms()), bold(&info.dec.format_dms())));
+    report.line(format!("R = {} AU", bold(&format!("{:.6}", info.distance_au))));
+    report.line(format!("alt = {}, az = {}", bold(&info.alt.format_dms()), bold(&format!("{:.2}°", info.az.degrees()))));
+    report.line(format!("night after local noon, {} UTC", format_jd_iso(info.noon)));
+    report.field("ra_deg", info.ra.degrees());
+    report.field("dec_deg", info.dec.degrees());
+    report.field("distance_au", info.distance_au);
+    report.field("alt_deg", info.alt.degrees());
+    report.field("az_deg", info.az.degrees());
+    report.field("noon", format_jd_iso(info.noon));
+    for ((name, altitude), event) in TWILIGHTS.iter().zip(&info.twilight) {
+        report.line(match event {
+            Twilight::Night { dusk, dawn } => {
+                format!("{:<14} {} → {}", name, bold(&format_jd_iso(*dusk)), bold(&format_jd_iso(*dawn)))
             }
+            Twilight::AlwaysAbove => format!("{:<14} Sun stays above {}° all night", name, altitude),
+            Twilight::AlwaysBelow => format!("{:<14} Sun stays below {}° all day", name, altitude),
+        });
+        // sunset/sunrise -> sunset and sunrise, the twilights -> civil_dusk and civil_dawn
+        let (dusk_name, dawn_name): (String, String) = match name.split_once('/') {
+            Some((dusk, dawn)) => (dusk.to_string(), dawn.to_string()),
+            None => (format!("{}_dusk", name), format!("{}_dawn", name)),
+        };
+        let (dusk, dawn): (Option<String>, Option<String>) = match event {
+            Twilight::Night { dusk, dawn } => (Some(format_jd_iso(*dusk)), Some(format_jd_iso(*dawn))),
+            _ => (None, None),
         };
+        report.field(&dusk_name, dusk);
+        report.field(&dawn_name, dawn);
     }
-    let mut a1: f32 = a1;
-    let mut d1: f32 = d1;
-    let mut a2: f32 = a2;
-    let mut d2: f32 = d2;
-    verbose!("α₁ = {}", a1);
-    verbose!("δ₁ = {}", d1);
-    verbose!("α₂ = {}", a2);
-    verbose!("δ₂ = {}", d2);
-
-    if isdeg == "true" {
-        verbose!("Convert values into radians");
-        verbose!("n = n * π/180");
-       
-        verbose!("\tα₁ = {} * π/180 = \x1b[1m\x1b[37m{}\x1b[0m", a1, a1 * (PI / 180.0));
-        a1 = a1 * (PI / 180.0);
-
-        verbose!("\tδ₁ = {} * π/180 = \x1b[1m\x1b[37m{}\x1b[0m", d1, d1 * (PI / 180.0));
-        d1 = d1 * (PI / 180.0);
-
-        verbose!("\tα₂ = {} * π/180 = \x1b[1m\x1b[37m{}\x1b[0m", a2, a2 * (PI / 180.0));
-        a2 = a2 * (PI / 180.0);
-
-        verbose!("\tδ₂ = {} * π/180 = \x1b[1m\x1b[37m{}\x1b[0m", d2, d2 * (PI / 180.0));
-        d2 = d2 * (PI / 180.0);
-    }
-    verbose!("Apply Spherical law of Cosines");
-    verbose!("cos(θ) = sin(δ₁)sin(δ₂) + cos(δ₁)cos(δ₂)cos(α₂ - α₁)");
-    verbose!("\tsin(δ₁)sin(δ₂) = \x1b[1m\x1b[37m{}\x1b[0m", d1.sin() * d2.sin());
-    verbose!("\tcos(δ₁)cos(δ₂)cos(α₂ - α₁) = \x1b[1m\x1b[37m{}\x1b[0m", d1.cos() * d2.cos() * (a2 - a1).cos());
-    verbose!("\tcos(θ) = \x1b[1m\x1b[37m{}\x1b[0m", (d1.sin() * d2.sin()) + (d1.cos() * d2.cos() * (a2 - a1).cos()));
-    
-    let cos_theta:f32 = (d1.sin() * d2.sin()) + (d1.cos() * d2.cos() * (a2 - a1).cos());
-    verbose!("θ = cos⁻¹(θ)");
-    let theta:f32 = cos_theta.acos();
-    
-    
-    // Convert to degrees, arcminutes, arcseconds
-    verbose!("Convert θ to degrees, arcminutes, and arcseconds");
-    verbose!("θ° = θ * 180/π");
-    let theta_deg: f32 = theta * (180.0 / PI);
-    verbose!("\tθ° = {} * 180/π = \x1b[1m\x1b[37m{}\x1b[0m°", theta, theta_deg);
-    
-    let theta_arcmin: f32 = (theta_deg - theta_deg.floor()) * 60.0;
-    let theta_arcsec: f32 = (theta_arcmin - theta_arcmin.floor()) * 60.0;
-    
-    verbose!("Calculate arcminutes and arcseconds");
-    verbose!("θ' = (θ° - floor(θ°)) * 60");
-    verbose!("\tθ' = ({} - floor({})) * 60 = \x1b[1m\x1b[37m{}\x1b[0m'", theta_deg, theta_deg, theta_arcmin.floor());
-    
-    verbose!("θ\" = (θ' - floor(θ')) * 60");
-    verbose!("\tθ\" = ({} - floor({})) * 60 = \x1b[1m\x1b[37m{}\x1b[0m", theta_arcmin, theta_arcmin, theta_arcsec);
-    let theta_output_rad: String = format!("θ = \x1b[1m\x1b[37m{}\x1b[0m rad", theta);
-
-let theta_output_deg: String = format!(
-    "θ = \x1b[1m\x1b[37m{}°{}′{}″\x1b[0m",
-    theta_deg.floor(),
-    theta_arcmin.floor(),
-    theta_arcsec
-);
-
-    print!("{}",gen_box(&[theta_output_rad, theta_output_deg]));
-    // α δ θ ₁ ₂ π ″
-
-}
-
-fn convert(conversion: String, input: String) {
-    if conversion == "deg-rad" {
-        let deg: f32 = input.trim().parse().expect("Invalid degree value");
-        let coeff: f32 = deg / 180.0;
-        print!("{}",gen_box(&[format!("{}π rad", coeff)]));
-    } else if conversion == "rad-deg" {
-        if input.contains("pi") {
-            let coeff_str: String = input.replace("pi", "").trim().to_string();
-            let coeff: f32 = if coeff_str.is_empty() {
-                1.0
-            } else {
-                coeff_str.parse().expect("Invalid coefficient for pi")
-            };
-            let deg: f32 = coeff * 180.0;
-            print!("{}",gen_box(&[format!("{}°", deg)]));
-        } else {
-            let rad: f32 = input.trim().parse().expect("Invalid radian value");
-            let deg: f32 = rad * 180.0 / PI;
-            print!("{}",gen_box(&[format!("{}°", deg)]));
+    Ok(report.render(format))
+}
+
+fn calc_moon(format: Format, show_work: bool, lat: String, lon: String, time: String, elevation: Option<String>) -> Result<String, Error> {
+    let (site, jd_utc): (Site, f64) = parse_body_args(&lat, &lon, &time, elevation.as_deref()).map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("φ = {}°, λ = {}°, t = {} UTC", site.lat.degrees(), site.lon.degrees(), format_jd_iso(jd_utc)));
+    }
+
+    let result = moon(site, jd_utc, show_work);
+    report.trace = result.trace;
+    let info: MoonInfo = result.value;
+    report.line(format!("α = {}, δ = {} (geocentric)", bold(&info.ra.format_hms()), bold(&info.dec.format_dms())));
+    report.line(format!("Δ = {} km", bold(&format!("{:.0}", info.distance_km))));
+    report.line(format!("alt = {}, az = {} (topocentric)", bold(&info.alt.format_dms()), bold(&format!("{:.2}°", info.az.degrees()))));
+    report.line(format!("phase = {} ({})", bold(info.phase), if info.waxing { "waxing" } else { "waning" }));
+    report.line(format!("illuminated = {}", bold(&format!("{:.1}%", info.illuminated * 100.0))));
+    report.field("ra_deg", info.ra.degrees());
+    report.field("dec_deg", info.dec.degrees());
+    report.field("distance_km", info.distance_km);
+    report.field("alt_deg", info.alt.degrees());
+    report.field("az_deg", info.az.degrees());
+    report.field("phase", info.phase);
+    report.field("waxing", info.waxing);
+    report.field("illuminated", info.illuminated);
+    report.field("phase_angle_deg", info.phase_angle.degrees());
+    Ok(report.render(format))
+}
+
+// where a major planet is at a UTC time, from the compiled-in mean elements
+fn calc_planet(format: Format, show_work: bool, name: String, time: String) -> Result<String, Error> {
+    let names: Vec<&str> = PLANETS.iter().map(|p: &Planet| p.name()).collect();
+    let body: Planet = Planet::parse(&name)
+        .ok_or_else(|| Error::Usage(format!("Unknown planet: {}, use one of {}", name.trim(), names.join(", ").to_lowercase())))?;
+    let jd_utc: f64 = parse_time(&time).map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("t = {} UTC (JD {})", format_jd_iso(jd_utc), jd_utc));
+    }
+
+    let result = planet(body, jd_utc, show_work);
+    report.trace = result.trace;
+    let p: PlanetPosition = result.value;
+    let side: &str = if p.elongation >= 0.0 { "east" } else { "west" };
+    report.line(format!("{}, {} UTC", body.name(), format_jd_iso(jd_utc)));
+    if !elements_valid(jd_utc) {
+        report.line("warning: outside 1800-2050, the mean elements can be off by more than a few arcminutes".to_string());
+    }
+    report.line(format!("α = {}, δ = {} (J2000, astrometric)", bold(&p.coord.ra.format_hms()), bold(&p.coord.dec.format_dms())));
+    report.line(format!("Δ = {} AU, r = {:.6} AU", bold(&format!("{:.6}", p.delta)), p.r));
+    report.line(format!("heliocentric λ = {:.4}°, β = {:.4}°", p.helio_lon.to_degrees(), p.helio_lat.to_degrees()));
+    report.line(format!("elongation = {} {}, phase angle = {:.2}°", bold(&format!("{:.2}°", p.elongation.abs().to_degrees())), side, p.phase_angle.to_degrees()));
+    report.line(format!("illuminated = {}, magnitude = {}", bold(&format!("{:.1}%", p.illuminated * 100.0)), bold(&format!("{:+.2}", p.magnitude))));
+    report.field("planet", body.name());
+    report.field("utc", format_jd_iso(jd_utc));
+    report.field("ra_deg", p.coord.ra.degrees());
+    report.field("dec_deg", p.coord.dec.degrees());
+    report.field("ra_hms", p.coord.ra.format_hms());
+    report.field("dec_dms", p.coord.dec.format_dms());
+    report.field("distance_au", p.delta);
+    report.field("sun_distance_au", p.r);
+    report.field("helio_lon_deg", p.helio_lon.to_degrees());
+    report.field("helio_lat_deg", p.helio_lat.to_degrees());
+    report.field("geo_lon_deg", p.geo_lon.to_degrees());
+    report.field("geo_lat_deg", p.geo_lat.to_degrees());
+    report.field("elongation_deg", p.elongation.to_degrees());
+    report.field("phase_angle_deg", p.phase_angle.to_degrees());
+    report.field("illuminated", p.illuminated);
+    report.field("magnitude", p.magnitude);
+    report.field("elements_valid", elements_valid(jd_utc));
+    Ok(report.render(format))
+}
+
+fn calc_orbit(format: Format, show_work: bool, file: String, object: String, time: String) -> Result<String, Error> {
+    let data: String = read_input(Some(&file))?;
+    let body: MinorBody = find_minor_body(&data, &object).map_err(|e| Error::Input(format!("Invalid input: {}: {}", file, e)))?;
+    let jd_utc: f64 = parse_time(&time).map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+    let mut report: Report = Report::default();
+    if show_work {
+        report.input(format!("{} from {}", body.name, file));
+        report.input(format!("t = {} UTC (JD {})", format_jd_iso(jd_utc), jd_utc));
+    }
+
+    let result = minor_body(&body, jd_utc, show_work);
+    report.trace = result.trace;
+    let p: MinorPosition = result.value;
+    let el: OrbitalElements = body.elements;
+    let side: &str = if p.elongation >= 0.0 { "east" } else { "west" };
+    let [x, y, z]: [f64; 3] = p.state.position;
+    let [vx, vy, vz]: [f64; 3] = p.state.velocity;
+    report.line(format!("{}, {} UTC", body.name, format_jd_iso(jd_utc)));
+    report.line(format!("α = {}, δ = {} (J2000, astrometric)", bold(&p.coord.ra.format_hms()), bold(&p.coord.dec.format_dms())));
+    report.line(format!("Δ = {} AU, r = {:.6} AU", bold(&format!("{:.6}", p.delta)), p.r));
+    report.line(format!("elongation = {} {}, phase angle = {:.2}°", bold(&format!("{:.2}°", p.elongation.abs().to_degrees())), side, p.phase_angle.to_degrees()));
+    if let Some(m) = p.magnitude {
+        let kind: &str = if matches!(body.brightness, Brightness::Comet { .. }) { "total magnitude" } else { "magnitude" };
+        report.line(format!("{} = {}", kind, bold(&format!("{:+.2}", m))));
+    }
+    report.line(format!("{} orbit, q = {:.6} AU, e = {:.6}, i = {:.4}°", el.conic().name(), el.q, el.e, el.inclination.to_degrees()));
+    report.line(format!("x, y, z = {:.6}, {:.6}, {:.6} AU (J2000 ecliptic)", x, y, z));
+    report.line(format!("vx, vy, vz = {:.8}, {:.8}, {:.8} AU/day", vx, vy, vz));
+    report.field("object", body.name.as_str());
+    report.field("utc", format_jd_iso(jd_utc));
+    report.field("ra_deg", p.coord.ra.degrees());
+    report.field("dec_deg", p.coord.dec.degrees());
+    report.field("ra_hms", p.coord.ra.format_hms());
+    report.field("dec_dms", p.coord.dec.format_dms());
+    report.field("distance_au", p.delta);
+    report.field("sun_distance_au", p.r);
+    report.field("elongation_deg", p.elongation.to_degrees());
+    report.field("phase_angle_deg", p.phase_angle.to_degrees());
+    report.field("magnitude", p.magnitude);
+    report.field("orbit", el.conic().name());
+    report.field("perihelion_au", el.q);
+    report.field("eccentricity", el.e);
+    report.field("inclination_deg", el.inclination.to_degrees());
+    report.field("node_deg", el.node.to_degrees());
+    report.field("perihelion_deg", el.perihelion.to_degrees());
+    report.field("perihelion_jd", el.perihelion_time);
+    report.field("x_au", x);
+    report.field("y_au", y);
+    report.field("z_au", z);
+    report.field("vx_au_day", vx);
+    report.field("vy_au_day", vy);
+    report.field("vz_au_day", vz);
+    Ok(report.render(format))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn calc_plan(format: Format, bare: AngleUnit, input: String, lat: String, lon: String, date: String, min_alt: String, chart: bool, columns: [String; 3], delimiter: Option<String>) -> Result<String, Error> {
+    let parsed: Result<(Site, f64, Angle), String> = (|| {
+        Ok((
+            parse_site(&lat, &lon, None)?,
+            parse_time(&date)?,
+            parse_coord(&min_alt, CoordKind::Dec, AngleUnit::Degrees)?,
+        ))
+    })();
+    let (site, jd_utc, min_alt): (Site, f64, Angle) = parsed.map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+    let (headers, rows): (Vec<String>, Vec<Vec<String>>) = read_table(&read_input(Some(&input))?, delimiter.as_deref())?;
+    // the coordinate columns can be left out, or left blank, for targets named in the config file
+    let name_index: usize = find_column(&headers, &columns[0])?;
+    let coord_indexes: Option<(usize, usize)> = find_column(&headers, &columns[1]).and_then(|ra: usize| Ok((ra, find_column(&headers, &columns[2])?))).ok();
+    let mut config: Option<Config> = None;
+    let mut targets: Vec<Target> = Vec::new();
+    for (n, row) in rows.iter().enumerate() {
+        let field = |i: usize| row.get(i).map_or("", |f: &String| f.trim());
+        let name: &str = field(name_index);
+        let coord: EquatorialCoord = match coord_indexes.map(|(ra, dec): (usize, usize)| (field(ra), field(dec))) {
+            Some((ra, dec)) if !ra.is_empty() || !dec.is_empty() => EquatorialCoord::parse(ra, dec, bare),
+            _ => match cached_config(&mut config)?.target(name) {
+                Some((_, preset)) => preset.coord(),
+                None => Err(format!("no coordinates, and no target named '{}' in the config file", name)),
+            },
+        }
+        .map_err(|e| Error::Input(format!("Invalid coordinate: row {}: {}", n + 1, e)))?;
+        targets.push(Target { name: name.to_string(), coord });
+    }
+
+    let night: Night = night(site, jd_utc);
+    let min_alt_deg: f64 = min_alt.degrees();
+    let min_alt: f64 = min_alt.radians();
+    let plans: Vec<TargetPlan> = targets.iter().map(|t: &Target| plan_target(t, site, &night, min_alt)).collect();
+    let event = |plan: &TargetPlan| -> (Option<f64>, Option<f64>) {
+        match plan.rise_set.visibility {
+            Visibility::RisesAndSets { rise, set } => (Some(rise), Some(set)),
+            _ => (None, None),
         }
+    };
+    let visibility = |plan: &TargetPlan| -> &str {
+        match plan.rise_set.visibility {
+            Visibility::RisesAndSets { .. } => "rises and sets",
+            Visibility::Circumpolar => "circumpolar",
+            Visibility::NeverRises => "never rises",
+        }
+    };
+
+    if matches!(format, Format::Json | Format::Csv) {
+        let records: Vec<Vec<(String, serde_json::Value)>> = targets
+            .iter()
+            .zip(&plans)
+            .map(|(target, plan)| {
+                let (rise, set): (Option<f64>, Option<f64>) = event(plan);
+                vec![
+                    ("name".to_string(), target.name.clone().into()),
+                    ("ra_deg".to_string(), target.coord.ra.degrees().into()),
+                    ("dec_deg".to_string(), target.coord.dec.degrees().into()),
+                    ("visibility".to_string(), visibility(plan).into()),
+                    ("rise".to_string(), rise.map(format_jd_iso).into()),
+                    ("transit".to_string(), format_jd_iso(plan.rise_set.transit).into()),
+                    ("set".to_string(), set.map(format_jd_iso).into()),
+                    ("transit_alt_deg".to_string(), plan.rise_set.transit_alt.to_degrees().into()),
+                    ("dark_hours".to_string(), plan.dark_hours.into()),
+                    ("max_dark_alt_deg".to_string(), plan.best_alt.map(f64::to_degrees).into()),
+                    ("min_airmass".to_string(), plan.min_airmass.into()),
+                    ("moon_separation_deg".to_string(), plan.moon_separation.to_degrees().into()),
+                ]
+            })
+            .collect();
+        return render_records(if format == Format::Json { "json" } else { "csv" }, &records);
+    }
+
+    let mut report: Report = Report::default();
+    report.line(format!("night of {} at φ = {}°, λ = {}°", &format_jd_iso(night.noon)[..10], site.lat.degrees(), site.lon.degrees()));
+    let mut sky: Vec<String> = Vec::new();
+    if night.end - night.start < 1.0 {
+        sky.push(format!("sunset {} UTC, sunrise {} UTC", clock(night.start), clock(night.end)));
+    }
+    sky.push(match night.dark {
+        Some((dusk, dawn)) if dawn - dusk < 1.0 => format!("astronomical darkness {} → {} UTC", bold(&clock(dusk)), bold(&clock(dawn))),
+        Some(_) => "dark all day".to_string(),
+        None => "no astronomical darkness".to_string(),
+    });
+    report.line(sky.join(", "));
+    report.line(format!("Moon {:.0}% illuminated ({})", night.moon_illuminated * 100.0, night.moon_phase));
+    report.line(String::new());
+    // the chart symbol goes before the name
+    let width: usize = targets.iter().map(|t: &Target| t.name.chars().count()).max().unwrap_or(0).max(6) + if chart { 2 } else { 0 };
+    let dark_heading: String = format!(">{}° dark", min_alt_deg);
+    report.line(format!("{:<width$}  rise   transit  set    max alt  {:>9}  X min  Moon", "target", dark_heading, width = width));
+    for (index, (target, plan)) in targets.iter().zip(&plans).enumerate() {
+        let (rise, set): (Option<f64>, Option<f64>) = event(plan);
+        let or_dash = |jd: Option<f64>| jd.map_or("  —  ".to_string(), clock);
+        let label: String = if chart { format!("{} {}", chart_symbol(index), target.name) } else { target.name.clone() };
+        report.line(format!(
+            "{:<width$}  {}  {}    {}  {:>7}  {}  {:>5}  {:>3.0}°",
+            label,
+            or_dash(rise),
+            clock(plan.rise_set.transit),
+            or_dash(set),
+            plan.best_alt.map_or("—".to_string(), |alt: f64| format!("{:.1}°", alt.to_degrees())),
+            bold(&format!("{:>9}", format!("{:.1} h", plan.dark_hours))),
+            plan.min_airmass.map_or("—".to_string(), |x: f64| format!("{:.2}", x)),
+            plan.moon_separation.to_degrees(),
+            width = width,
+        ));
+    }
+    if chart {
+        report.line(String::new());
+        let altitudes: Vec<&[f64]> = plans.iter().map(|p: &TargetPlan| p.altitudes.as_slice()).collect();
+        for line in altitude_chart(&night, &altitudes, min_alt) {
+            report.line(line);
+        }
+    }
+    Ok(report.render(format))
+}
+
+// separation for every row of a table, same parsing and formula as `distance`
+fn calc_batch(bare: AngleUnit, input: Option<String>, columns: [String; 4], output: String, delimiter: Option<String>) -> Result<String, Error> {
+    let data: String = read_input(input.as_deref())?;
+    let (headers, rows): (Vec<String>, Vec<Vec<String>>) = read_table(&data, delimiter.as_deref())?;
+    let mut indexes: [usize; 4] = [0; 4];
+    for (index, name) in indexes.iter_mut().zip(columns.iter()) {
+        *index = find_column(&headers, name)?;
+    }
+    let values: Vec<Result<Vec<f64>, String>> = rows
+        .iter()
+        .enumerate()
+        .map(|(n, row)| {
+            let fields: Vec<&str> = indexes.iter().map(|i: &usize| row.get(*i).map_or("", |f: &String| f.as_str())).collect();
+            let (a, b): (EquatorialCoord, EquatorialCoord) = parse_coordinate_pair(bare, [fields[0], fields[1], fields[2], fields[3]])
+                .map_err(|e| format!("row {}: {}", n + 1, e))?;
+            let theta: Angle = separation(&a, &b, false).value;
+            Ok(vec![theta.degrees(), theta.arcseconds()])
+        })
+        .collect();
+    render_table(&output, &headers, &rows, &["separation_deg", "separation_arcsec"], &values)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn calc_crossmatch(format: Format, bare: AngleUnit, first: String, second: String, radius: String, columns: [String; 4], neighbors: String, output: String, delimiter: Option<String>) -> Result<String, Error> {
+    let radius_arcsec: f64 = radius
+        .trim()
+        .trim_end_matches(['″', '"'])
+        .parse()
+        .ok()
+        .filter(|r: &f64| r.is_finite() && *r > 0.0)
+        .ok_or_else(|| Error::Input(format!("Invalid radius: {}, give it in arcseconds", radius)))?;
+    let neighbors: usize = neighbors
+        .parse()
+        .ok()
+        .filter(|n: &usize| *n > 0)
+        .ok_or_else(|| Error::Input(format!("Invalid number of neighbours: {}", neighbors)))?;
+
+    let (headers1, rows1): (Vec<String>, Vec<Vec<String>>) = read_table(&read_input(Some(&first))?, delimiter.as_deref())?;
+    let (headers2, rows2): (Vec<String>, Vec<Vec<String>>) = read_table(&read_input(Some(&second))?, delimiter.as_deref())?;
+    let coords1: Vec<Result<(f64, f64), String>> =
+        table_coords(&rows1, find_column(&headers1, &columns[0])?, find_column(&headers1, &columns[1])?, bare);
+    // second-catalog rows that don't parse are left out of the match and listed at the end
+    let mut coords2: Vec<(usize, f64, f64)> = Vec::new();
+    let mut skipped: Vec<(usize, String)> = Vec::new();
+    for (i, c) in table_coords(&rows2, find_column(&headers2, &columns[2])?, find_column(&headers2, &columns[3])?, bare).into_iter().enumerate() {
+        match c {
+            Ok((ra, dec)) => coords2.push((i, ra, dec)),
+            Err(e) => skipped.push((i, format!("{}: {}", second, e))),
+        }
+    }
+
+    let radius_rad: f64 = Angle::from_arcseconds(radius_arcsec).radians();
+    let matches: Vec<Result<Vec<(usize, f64)>, String>> = crossmatch(&coords1, &coords2, radius_rad, neighbors);
+
+    // one output row per matched pair, unmatched sources keep empty second-catalog columns
+    let mut headers: Vec<String> = headers1.iter().map(|h: &String| format!("1_{}", h)).collect();
+    headers.extend(headers2.iter().map(|h: &String| format!("2_{}", h)));
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut values: Vec<Result<Vec<f64>, String>> = Vec::new();
+    let mut matched: usize = 0;
+    for (row1, found) in rows1.iter().zip(matches) {
+        let mut left: Vec<String> = row1.clone();
+        left.resize(headers1.len(), String::new());
+        match found {
+            Ok(found) if !found.is_empty() => {
+                matched += 1;
+                for (rank, (index, sep)) in found.iter().enumerate() {
+                    let mut row: Vec<String> = left.clone();
+                    row.extend(rows2[*index].iter().cloned());
+                    row.resize(headers.len(), String::new());
+                    rows.push(row);
+                    values.push(Ok(vec![Angle::from_radians(*sep).arcseconds(), (rank + 1) as f64]));
+                }
+            }
+            Ok(_) => {
+                left.resize(headers.len(), String::new());
+                rows.push(left);
+                values.push(Err(format!("no match within {}″", radius_arcsec)));
+            }
+            Err(e) => {
+                left.resize(headers.len(), String::new());
+                rows.push(left);
+                values.push(Err(e));
+            }
+        }
+    }
+    for (index, e) in &skipped {
+        let mut row: Vec<String> = vec![String::new(); headers1.len()];
+        row.extend(rows2[*index].iter().cloned());
+        row.resize(headers.len(), String::new());
+        rows.push(row);
+        values.push(Err(e.clone()));
+    }
+    let table: String = render_table(&output, &headers, &rows, &["separation_arcsec", "rank"], &values)?;
+
+    // the table is the output, the summary goes beside it on stderr in the same format as errors
+    let mut report: Report = Report::default();
+    report.line(format!("{} of {} sources matched within {}″", bold(&matched.to_string()), rows1.len(), radius_arcsec));
+    if !skipped.is_empty() {
+        report.line(format!("unreadable in {}: {}, listed at the end of the table", second, skipped.len()));
+    }
+    report.field("matched", matched);
+    report.field("sources", rows1.len());
+    report.field("radius_arcsec", radius_arcsec);
+    report.field("skipped", skipped.len());
+    // the box has no trailing newline, and the table must not start on its last line
+    let summary: String = report.render(format);
+    if format == Format::Box {
+        eprintln!("{}", summary);
     } else {
-        print!("{}", gen_box(&[format!("Invalid conversion type")]))
+        eprint!("{}", summary);
     }
+    Ok(table)
 }
 
-fn main() {
-    let matches: clap::ArgMatches = Command::new("astrocalc")
+// `convert value from to`, or the older `convert rad-deg|deg-rad value`
+fn calc_convert(format: Format, value: String, from: String, to: Option<String>) -> Result<String, Error> {
+    let (input, result): (&String, String) = match &to {
+        Some(to) => (&value, convert(&value, &from, to)?),
+        None => (&from, convert_pair(&value, &from)?),
+    };
+    let mut report: Report = Report::default();
+    report.line(result.clone());
+    report.field("input", input.as_str());
+    report.field("result", result);
+    Ok(report.render(format))
+}
+
+// batch and crossmatch write a table, --output or else json when --format json asks for it
+fn table_output(args: &clap::ArgMatches, format: Format) -> String {
+    match args.get_one::<String>("output") {
+        Some(output) => output.clone(),
+        None if format == Format::Json => "json".to_string(),
+        None => "csv".to_string(),
+    }
+}
+
+// --deg/--rad: what bare numeric coordinates are in, sexagesimal and unit-suffixed ones are unaffected
+fn unit_args() -> [Arg; 2] {
+    [
+        Arg::new("deg")
+            .long("deg")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("rad")
+            .help("bare numeric coordinates are in degrees (the default)"),
+        Arg::new("rad")
+            .long("rad")
+            .action(ArgAction::SetTrue)
+            .help("bare numeric coordinates are in radians"),
+    ]
+}
+
+fn bare_unit(args: &clap::ArgMatches) -> AngleUnit {
+    if args.get_flag("rad") {
+        AngleUnit::Radians
+    } else {
+        AngleUnit::Degrees
+    }
+}
+
+fn show_work_arg() -> Arg {
+    Arg::new("show_work")
+        .long("show-work")
+        .short('w')
+        .action(ArgAction::SetTrue)
+        .help("show the calculations used")
+}
+
+fn show_work(args: &clap::ArgMatches, format: Format) -> bool {
+    args.get_flag("show_work") || format.shows_work()
+}
+
+fn coordinate_pair_args() -> [Arg; 7] {
+    let [deg, rad]: [Arg; 2] = unit_args();
+    [
+        deg,
+        rad,
+        show_work_arg(),
+        Arg::new("alpha1")
+            .required(true)
+            .allow_hyphen_values(true)
+            .help("Ra of first coordinate"),
+        Arg::new("delta1")
+            .required(true)
+            .allow_hyphen_values(true)
+            .help("Dec of first coordinate"),
+        Arg::new("alpha2")
+            .required(true)
+            .allow_hyphen_values(true)
+            .help("Ra of second coordinate"),
+        Arg::new("delta2")
+            .required(true)
+            .allow_hyphen_values(true)
+            .help("Dec of second coordinate"),
+    ]
+}
+
+// --site: a preset from the config file in place of lat and lon, see expand_presets
+fn site_arg() -> Arg {
+    Arg::new("site")
+        .long("site")
+        .help("named site from the config file in place of the observer's lat and lon, see `astrocalc site list`")
+}
+
+fn unknown_site(name: &str) -> Error {
+    Error::Usage(format!("unknown site: {}, see `astrocalc site list`", name))
+}
+
+// the config is read the first time a name needs looking up
+fn cached_config(config: &mut Option<Config>) -> Result<&Config, Error> {
+    if config.is_none() {
+        *config = Some(Config::load(&config_path()?)?);
+    }
+    Ok(config.as_ref().unwrap())
+}
+
+// positional ids a target name can stand in for, with the Dec it fills too
+const TARGET_SLOTS: [(&str, &str); 3] = [("ra", "dec"), ("alpha1", "delta1"), ("alpha2", "delta2")];
+
+// rewrites the command line before clap sees it: a preset target name where an Ra goes becomes
+// its Ra and Dec, and --site NAME becomes the site's lat and lon (and --elevation where the
+// subcommand has one and it wasn't given). anything without a preset is left as it was typed
+fn expand_presets(command: &Command, argv: Vec<String>, config: &mut Option<Config>) -> Result<Vec<String>, Error> {
+    // --format is the only option that comes before the subcommand
+    let mut start: usize = 1;
+    while start < argv.len() && argv[start].starts_with("--format") {
+        start += if argv[start] == "--format" { 2 } else { 1 };
+    }
+    let Some(sub) = argv.get(start).and_then(|name: &String| command.find_subcommand(name)) else {
+        return Ok(argv);
+    };
+    if argv[start + 1..].iter().any(|token: &String| token == "--") {
+        return Ok(argv);
+    }
+    let defined = |token: &str| -> Option<&Arg> {
+        match token.strip_prefix("--") {
+            Some(long) => sub.get_arguments().find(|a: &&Arg| a.get_long() == long.split('=').next()),
+            None => sub.get_arguments().find(|a: &&Arg| a.get_short().is_some() && a.get_short() == token.chars().nth(1)),
+        }
+    };
+
+    // options keep their values, anything not shaped like an option (negative numbers included)
+    // is positional
+    let mut options: Vec<String> = Vec::new();
+    let mut positionals: Vec<String> = Vec::new();
+    let mut given: Vec<String> = Vec::new();
+    let mut site: Option<String> = None;
+    let mut tokens = argv[start + 1..].iter();
+    while let Some(token) = tokens.next() {
+        let is_option: bool = token.starts_with("--") || token.starts_with('-') && token.chars().nth(1).is_some_and(|c: char| c.is_ascii_alphabetic());
+        if !is_option {
+            positionals.push(token.clone());
+            continue;
+        }
+        let arg: Option<&Arg> = defined(token);
+        let inline: bool = token.contains('=') || !token.starts_with("--") && token.chars().count() > 2;
+        let takes_value: bool = token == "--format" || arg.is_some_and(|a: &Arg| a.get_action().takes_values());
+        let value: Option<String> = if takes_value && !inline { tokens.next().cloned() } else { None };
+        match arg.map(|a: &Arg| a.get_id().as_str()) {
+            Some("site") => site = value.or_else(|| token.split_once('=').map(|(_, v)| v.to_string())),
+            id => {
+                given.extend(id.map(str::to_string));
+                options.push(token.clone());
+                options.extend(value);
+            }
+        }
+    }
+
+    let slots: Vec<&str> = sub.get_positionals().map(|a: &Arg| a.get_id().as_str()).collect();
+    let mut changed: bool = site.is_some();
+    let mut expanded: Vec<String> = Vec::new();
+    let mut remaining = positionals.into_iter();
+    let mut slot: usize = 0;
+    while slot < slots.len() {
+        if let (Some(name), "lat") = (&site, slots[slot]) {
+            let (_, preset): (&String, &SitePreset) = cached_config(config)?.site(name).ok_or_else(|| unknown_site(name))?;
+            expanded.extend([preset.lat.clone(), preset.lon.clone()]);
+            slot += 2;
+            continue;
+        }
+        let Some(token) = remaining.next() else { break };
+        let dec_slot: Option<&str> = TARGET_SLOTS.iter().find(|(ra, _)| *ra == slots[slot]).map(|(_, dec)| *dec);
+        if dec_slot.is_some() && slots.get(slot + 1).copied() == dec_slot && parse_coord(&token, CoordKind::Ra, AngleUnit::Degrees).is_err() {
+            if let Some((_, preset)) = cached_config(config)?.target(&token) {
+                // with a unit, so --rad doesn't change what the preset's numbers mean
+                let coord: EquatorialCoord = preset.coord().map_err(Error::Input)?;
+                expanded.extend([format!("{}deg", coord.ra.degrees()), format!("{}deg", coord.dec.degrees())]);
+                changed = true;
+                slot += 2;
+                continue;
+            }
+        }
+        expanded.push(token);
+        slot += 1;
+    }
+    expanded.extend(remaining);
+    if !changed {
+        return Ok(argv);
+    }
+
+    // the site's elevation, and its longitude where the subcommand only takes that (time)
+    if let Some(name) = &site {
+        let (_, preset): (&String, &SitePreset) = cached_config(config)?.site(name).ok_or_else(|| unknown_site(name))?;
+        let wanted = |id: &str| -> bool { sub.get_arguments().any(|a: &Arg| a.get_id() == id) && !given.iter().any(|g: &String| g == id) };
+        if wanted("elevation") && preset.elevation != 0.0 {
+            options.push(format!("--elevation={}", preset.elevation));
+        }
+        if wanted("longitude") {
+            options.push(format!("--longitude={}", preset.lon));
+        }
+    }
+    let mut result: Vec<String> = argv[..=start].to_vec();
+    result.extend(options);
+    result.extend(expanded);
+    Ok(result)
+}
+
+// errors from before clap has parsed the command line still follow --format
+fn requested_format(argv: &[String]) -> Format {
+    let value: Option<&str> = argv.iter().enumerate().find_map(|(i, token)| match token.strip_prefix("--format") {
+        Some("") => argv.get(i + 1).map(String::as_str),
+        Some(rest) => rest.strip_prefix('='),
+        None => None,
+    });
+    value.and_then(Format::parse).unwrap_or(Format::Box)
+}
+
+fn calc_site(format: Format, action: &str, name: Option<String>, lat: Option<String>, lon: Option<String>, elevation: Option<String>) -> Result<String, Error> {
+    let path: PathBuf = config_path()?;
+    let mut config: Config = Config::load(&path)?;
+    let name: String = name.unwrap_or_default();
+    let mut report: Report = Report::default();
+    match action {
+        "add" => {
+            let elevation: f64 = match elevation {
+                Some(e) => e.trim().parse::<f64>().map_err(|_| Error::Input(format!("Invalid input: elevation '{}' is not a number", e)))?,
+                None => 0.0,
+            };
+            let preset: SitePreset = SitePreset { lat: lat.unwrap_or_default(), lon: lon.unwrap_or_default(), elevation };
+            let replaced: bool = config.add_site(&name, preset.clone()).map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+            config.save(&path)?;
+            let site: Site = preset.site().map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+            report.line(format!("{} site {}: φ = {}°, λ = {}°, h = {} m", if replaced { "updated" } else { "added" }, bold(&name), site.lat.degrees(), site.lon.degrees(), elevation));
+            report.field("name", name);
+            report.field("lat_deg", site.lat.degrees());
+            report.field("lon_deg", site.lon.degrees());
+            report.field("elevation_m", elevation);
+        }
+        "remove" => {
+            config.remove_site(&name).ok_or_else(|| unknown_site(&name))?;
+            config.save(&path)?;
+            report.line(format!("removed site {}", bold(&name)));
+            report.field("name", name);
+        }
+        _ => {
+            if matches!(format, Format::Json | Format::Csv) {
+                let records: Vec<Vec<(String, serde_json::Value)>> = config
+                    .sites
+                    .iter()
+                    .map(|(name, preset)| {
+                        vec![
+                            ("name".to_string(), name.clone().into()),
+                            ("lat".to_string(), preset.lat.clone().into()),
+                            ("lon".to_string(), preset.lon.clone().into()),
+                            ("elevation_m".to_string(), preset.elevation.into()),
+                        ]
+                    })
+                    .collect();
+                return render_records(if format == Format::Json { "json" } else { "csv" }, &records);
+            }
+            let width: usize = config.sites.keys().map(|n: &String| n.chars().count()).max().unwrap_or(0);
+            for (name, preset) in &config.sites {
+                report.line(format!("{}  φ = {}, λ = {}, h = {} m", bold(&format!("{:<width$}", name, width = width)), preset.lat, preset.lon, preset.elevation));
+            }
+            if config.sites.is_empty() {
+                report.line("no sites yet, add one with `astrocalc site add`".to_string());
+            }
+            report.line(format!("config: {}", path.display()));
+        }
+    }
+    Ok(report.render(format))
+}
+
+fn calc_target(format: Format, action: &str, name: Option<String>, ra: Option<String>, dec: Option<String>) -> Result<String, Error> {
+    let path: PathBuf = config_path()?;
+    let mut config: Config = Config::load(&path)?;
+    let name: String = name.unwrap_or_default();
+    let unknown_target = |name: &str| Error::Usage(format!("unknown target: {}, see `astrocalc target list`", name));
+    let mut report: Report = Report::default();
+    match action {
+        "add" => {
+            let preset: TargetPreset = TargetPreset { ra: ra.unwrap_or_default(), dec: dec.unwrap_or_default() };
+            let replaced: bool = config.add_target(&name, preset.clone()).map_err(|e| Error::Input(format!("Invalid input: {}", e)))?;
+            config.save(&path)?;
+            let coord: EquatorialCoord = preset.coord().map_err(|e| Error::Input(format!("Invalid coordinate: {}", e)))?;
+            report.line(format!(
+                "{} target {}: α = {}, δ = {}",
+                if replaced { "updated" } else { "added" },
+                bold(&name),
+                coord.ra.format_hms(),
+                coord.dec.format_dms()
+            ));
+            report.field("name", name);
+            report.field("ra_deg", coord.ra.degrees());
+            report.field("dec_deg", coord.dec.degrees());
+        }
+        "remove" => {
+            config.remove_target(&name).ok_or_else(|| unknown_target(&name))?;
+            config.save(&path)?;
+            report.line(format!("removed target {}", bold(&name)));
+            report.field("name", name);
+        }
+        _ => {
+            if matches!(format, Format::Json | Format::Csv) {
+                let records: Vec<Vec<(String, serde_json::Value)>> = config
+                    .targets
+                    .iter()
+                    .map(|(name, preset)| vec![("name".to_string(), name.clone().into()), ("ra".to_string(), preset.ra.clone().into()), ("dec".to_string(), preset.dec.clone().into())])
+                    .collect();
+                return render_records(if format == Format::Json { "json" } else { "csv" }, &records);
+            }
+            let width: usize = config.targets.keys().map(|n: &String| n.chars().count()).max().unwrap_or(0);
+            for (name, preset) in &config.targets {
+                report.line(format!("{}  α = {}, δ = {}", bold(&format!("{:<width$}", name, width = width)), preset.ra, preset.dec));
+            }
+            if config.targets.is_empty() {
+                report.line("no targets yet, add one with `astrocalc target add`".to_string());
+            }
+            report.line(format!("config: {}", path.display()));
+        }
+    }
+    Ok(report.render(format))
+}
+
+fn cli() -> Command {
+    Command::new("astrocalc")
         .version("1.0")
         .author("beanfrog")
         .about("perform astronomical calculations and conversions")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .global(true)
+                .default_value("box")
+                .value_parser(["box", "plain", "json", "csv", "latex", "markdown"])
+                .help("output format: box | plain (no box or colours) | json (with the showwork steps) | csv | latex | markdown (the showwork steps as an align* block or a document with math)"),
+        )
         .subcommand(
             Command::new("distance")
                 .about("calculate approximate distance or angular separation between 2 Ra/Dec points")
                 .alias("d")
+                .args(coordinate_pair_args()),
+        )
+        .subcommand(
+            Command::new("offset")
+                .about("calculate the position angle and tangent-plane offsets from the first Ra/Dec point to the second")
+                .alias("pa")
+                .args(coordinate_pair_args()),
+        )
+        .subcommand(
+            Command::new("transform")
+                .about("convert coordinates between the equatorial (J2000/ICRS), galactic and ecliptic frames")
+                .alias("t")
                 .arg(
-                    Arg::new("isdeg")
+                    Arg::new("from")
                         .required(true)
-                        .help("Are the following values in degrees? (false = radians)"),
+                        .help("frame of the input: equatorial | galactic | ecliptic"),
                 )
                 .arg(
-                    Arg::new("showwork")
+                    Arg::new("to")
                         .required(true)
-                        .help("Show the calculations used?"),
+                        .help("frame of the output: equatorial | galactic | ecliptic"),
                 )
+                .args(unit_args())
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("lon")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Ra, l or λ of the coordinate"),
+                )
+                .arg(
+                    Arg::new("lat")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Dec, b or β of the coordinate"),
+                ),
+        )
+        .subcommand(
+            Command::new("epoch")
+                .about("precess Ra/Dec from one epoch to another (IAU 2006), optionally with nutation")
+                .alias("e")
                 .arg(
-                    Arg::new("alpha1")
+                    Arg::new("from")
                         .required(true)
-                        .value_parser(clap::value_parser!(f32))
-                        .help("Ra of first coordinate"),
+                        .help("epoch of the input, e.g. J2000, B1950, J2025.5 or now. B epochs are FK4 places, converted to FK5 (E-terms removed, zero FK5 proper motion assumed)"),
                 )
                 .arg(
-                    Arg::new("delta1")
+                    Arg::new("to")
                         .required(true)
-                        .value_parser(clap::value_parser!(f32))
-                        .help("Dec of first coordinate"),
+                        .help("epoch of the output, e.g. J2000, B1950, J2025.5 or now"),
                 )
+                .args(unit_args())
+                .arg(show_work_arg())
                 .arg(
-                    Arg::new("alpha2")
+                    Arg::new("ra")
                         .required(true)
-                        .value_parser(clap::value_parser!(f32))
-                        .help("Ra of second coordinate"),
+                        .allow_hyphen_values(true)
+                        .help("Ra at the input epoch"),
                 )
                 .arg(
-                    Arg::new("delta2")
+                    Arg::new("dec")
                         .required(true)
-                        .value_parser(clap::value_parser!(f32))
-                        .help("Dec of second coordinate"),
+                        .allow_hyphen_values(true)
+                        .help("Dec at the input epoch"),
+                )
+                .arg(
+                    Arg::new("nutation")
+                        .long("nutation")
+                        .short('n')
+                        .action(ArgAction::SetTrue)
+                        .help("apply nutation at the output epoch (true equator and equinox of date)"),
+                ),
+        )
+        .subcommand(
+            Command::new("propagate")
+                .about("move Ra/Dec and proper motion to another epoch with rigorous space motion (perspective acceleration included)")
+                .alias("pm")
+                .arg(
+                    Arg::new("to")
+                        .required(true)
+                        .help("epoch of the output, e.g. J2024, J2030.5 or now"),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .default_value("J2000")
+                        .help("catalogue epoch of the input, e.g. J2016 for Gaia DR3, J1991.25 for Hipparcos"),
+                )
+                .args(unit_args())
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("ra")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Ra at the catalogue epoch"),
+                )
+                .arg(
+                    Arg::new("dec")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Dec at the catalogue epoch"),
+                )
+                .arg(
+                    Arg::new("pmra")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("proper motion in Ra, μα* = μα cos δ, in mas/yr"),
+                )
+                .arg(
+                    Arg::new("pmdec")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("proper motion in Dec in mas/yr"),
+                )
+                .arg(
+                    Arg::new("parallax")
+                        .long("parallax")
+                        .short('p')
+                        .help("parallax in mas (default 0: no perspective effects)"),
+                )
+                .arg(
+                    Arg::new("rv")
+                        .long("rv")
+                        .allow_hyphen_values(true)
+                        .help("radial velocity in km/s, positive receding (needs --parallax)"),
+                ),
+        )
+        .subcommand(
+            Command::new("annual")
+                .about("apply annual parallax and aberration to a J2000 catalogue Ra/Dec at a UTC time")
+                .alias("ab")
+                .args(unit_args())
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("ra")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("J2000/ICRS Ra"),
+                )
+                .arg(
+                    Arg::new("dec")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("J2000/ICRS Dec"),
+                )
+                .arg(
+                    Arg::new("time")
+                        .required(true)
+                        .help("UTC time, same formats as time, e.g. 2024-03-20T21:15 or now"),
+                )
+                .arg(
+                    Arg::new("parallax")
+                        .long("parallax")
+                        .short('p')
+                        .help("parallax in mas (default 0: aberration only)"),
+                ),
+        )
+        .subcommand(
+            Command::new("time")
+                .about("convert between civil dates, Julian dates, UTC/TAI/TT and sidereal time")
+                .alias("j")
+                .arg(site_arg())
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("input")
+                        .required(true)
+                        .help("date and time, e.g. 2024-03-20T21:15:00, JD2460390.5, MJD60390 or now"),
+                )
+                .arg(
+                    Arg::new("scale")
+                        .long("scale")
+                        .short('s')
+                        .default_value("utc")
+                        .help("time scale of the input: utc | tai | tt"),
+                )
+                .arg(
+                    Arg::new("longitude")
+                        .long("longitude")
+                        .short('l')
+                        .allow_hyphen_values(true)
+                        .help("observer longitude in degrees, east positive, for local sidereal time"),
+                ),
+        )
+        .subcommand(
+            Command::new("altaz")
+                .about("altitude, azimuth, hour angle, airmass and parallactic angle of a J2000 Ra/Dec for an observer")
+                .alias("aa")
+                .arg(site_arg())
+                .args(unit_args())
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("ra")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Ra of the target (J2000)"),
+                )
+                .arg(
+                    Arg::new("dec")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Dec of the target (J2000)"),
+                )
+                .arg(
+                    Arg::new("lat")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer latitude in degrees, north positive"),
+                )
+                .arg(
+                    Arg::new("lon")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer longitude in degrees, east positive"),
+                )
+                .arg(
+                    Arg::new("time")
+                        .required(true)
+                        .help("UTC time, e.g. 2024-03-20T21:15:00, JD2460390.5 or now"),
+                )
+                .arg(
+                    Arg::new("elevation")
+                        .long("elevation")
+                        .short('e')
+                        .help("observer elevation in metres"),
+                ),
+        )
+        .subcommand(
+            Command::new("refraction")
+                .about("atmospheric refraction of an altitude and its airmass in several models")
+                .alias("rf")
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("altitude")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("true (airless) altitude in degrees, or the observed one with --apparent"),
+                )
+                .arg(
+                    Arg::new("apparent")
+                        .long("apparent")
+                        .short('a')
+                        .action(ArgAction::SetTrue)
+                        .help("the altitude is as observed, correct it with Bennett's formula instead of Saemundsson's"),
+                )
+                .arg(
+                    Arg::new("pressure")
+                        .long("pressure")
+                        .short('P')
+                        .default_value("1010")
+                        .help("air pressure at the observer in hPa"),
+                )
+                .arg(
+                    Arg::new("temperature")
+                        .long("temperature")
+                        .short('T')
+                        .allow_hyphen_values(true)
+                        .default_value("10")
+                        .help("air temperature at the observer in °C"),
+                ),
+        )
+        .subcommand(
+            Command::new("riseset")
+                .about("rise, transit and set times of a J2000 Ra/Dec for an observer")
+                .alias("rs")
+                .arg(site_arg())
+                .args(unit_args())
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("ra")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Ra of the target (J2000)"),
+                )
+                .arg(
+                    Arg::new("dec")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Dec of the target (J2000)"),
+                )
+                .arg(
+                    Arg::new("lat")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer latitude in degrees, north positive"),
+                )
+                .arg(
+                    Arg::new("lon")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer longitude in degrees, east positive"),
+                )
+                .arg(
+                    Arg::new("date")
+                        .required(true)
+                        .help("UTC start of the 24 h search window, e.g. 2024-03-20 or 2024-03-20T18:00"),
+                )
+                .arg(
+                    Arg::new("horizon")
+                        .long("horizon")
+                        .short('H')
+                        .allow_hyphen_values(true)
+                        .default_value("-0.5667")
+                        .help("altitude of the horizon in degrees, e.g. -0.5667 for refraction or a telescope limit"),
+                ),
+        )
+        .subcommand(
+            Command::new("sun")
+                .about("apparent position of the Sun for an observer and the twilight times of the night")
+                .alias("s")
+                .arg(site_arg())
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("lat")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer latitude in degrees, north positive"),
+                )
+                .arg(
+                    Arg::new("lon")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer longitude in degrees, east positive"),
+                )
+                .arg(
+                    Arg::new("time")
+                        .required(true)
+                        .help("UTC time, e.g. 2024-03-20T21:15:00, JD2460390.5 or now"),
+                )
+                .arg(
+                    Arg::new("elevation")
+                        .long("elevation")
+                        .short('e')
+                        .help("observer elevation in metres"),
+                ),
+        )
+        .subcommand(
+            Command::new("moon")
+                .about("position, phase and illumination of the Moon for an observer")
+                .alias("m")
+                .arg(site_arg())
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("lat")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer latitude in degrees, north positive"),
+                )
+                .arg(
+                    Arg::new("lon")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer longitude in degrees, east positive"),
+                )
+                .arg(
+                    Arg::new("time")
+                        .required(true)
+                        .help("UTC time, e.g. 2024-03-20T21:15:00, JD2460390.5 or now"),
+                )
+                .arg(
+                    Arg::new("elevation")
+                        .long("elevation")
+                        .short('e')
+                        .help("observer elevation in metres"),
+                ),
+        )
+        .subcommand(
+            Command::new("planet")
+                .about("position, distance, elongation, phase and magnitude of a major planet from mean orbital elements")
+                .alias("p")
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("mercury | venus | mars | jupiter | saturn | uranus | neptune"),
+                )
+                .arg(
+                    Arg::new("time")
+                        .required(true)
+                        .help("UTC time, same formats as time, e.g. 2024-03-20T21:15 or now"),
+                ),
+        )
+        .subcommand(
+            Command::new("orbit")
+                .about("position, distance and magnitude of an asteroid or comet from MPC orbital elements")
+                .alias("o")
+                .arg(show_work_arg())
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .help("file of MPCORB.DAT or CometEls.txt lines, - for stdin"),
+                )
+                .arg(
+                    Arg::new("object")
+                        .required(true)
+                        .help("name or designation, matched anywhere in the line ignoring case, e.g. ceres or 1P"),
+                )
+                .arg(
+                    Arg::new("time")
+                        .required(true)
+                        .help("UTC time, same formats as time, e.g. 2024-03-20T21:15 or now"),
+                ),
+        )
+        .subcommand(
+            Command::new("plan")
+                .about("observability of a target list over a night: rise/transit/set, time above a minimum altitude in darkness, airmass and Moon distance")
+                .alias("pl")
+                .arg(site_arg())
+                .args(unit_args())
+                .arg(
+                    Arg::new("targets")
+                        .required(true)
+                        .help("CSV/TSV file of targets with a header row, - for stdin"),
+                )
+                .arg(
+                    Arg::new("lat")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer latitude in degrees, north positive"),
+                )
+                .arg(
+                    Arg::new("lon")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("observer longitude in degrees, east positive"),
+                )
+                .arg(
+                    Arg::new("date")
+                        .required(true)
+                        .help("UTC date of the evening the night starts, e.g. 2024-03-20"),
+                )
+                .arg(
+                    Arg::new("min_alt")
+                        .long("min-alt")
+                        .short('m')
+                        .default_value("30")
+                        .help("minimum useful altitude in degrees"),
+                )
+                .arg(
+                    Arg::new("chart")
+                        .long("chart")
+                        .short('c')
+                        .action(ArgAction::SetTrue)
+                        .help("draw the altitude of every target through the night"),
+                )
+                .arg(Arg::new("name").long("name").default_value("name").help("column with the target name"))
+                .arg(Arg::new("ra").long("ra").default_value("ra").help("column with the Ra (J2000)"))
+                .arg(Arg::new("dec").long("dec").default_value("dec").help("column with the Dec (J2000)"))
+                .arg(
+                    Arg::new("delimiter")
+                        .long("delimiter")
+                        .short('d')
+                        .help("input delimiter, e.g. , ; or tab (default: tab if the header has one, else comma)"),
+                ),
+        )
+        .subcommand(
+            Command::new("site")
+                .about("named observer sites in the config file, for --site in place of lat and lon")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("add a site, or replace one of the same name")
+                        .arg(Arg::new("name").required(true).help("name to use with --site, e.g. mauna-kea"))
+                        .arg(
+                            Arg::new("lat")
+                                .required(true)
+                                .allow_hyphen_values(true)
+                                .help("latitude in degrees or dd:mm:ss, north positive"),
+                        )
+                        .arg(
+                            Arg::new("lon")
+                                .required(true)
+                                .allow_hyphen_values(true)
+                                .help("longitude in degrees or dd:mm:ss, east positive"),
+                        )
+                        .arg(
+                            Arg::new("elevation")
+                                .long("elevation")
+                                .short('e')
+                                .allow_hyphen_values(true)
+                                .help("height above sea level in metres"),
+                        ),
+                )
+                .subcommand(Command::new("list").about("list the sites"))
+                .subcommand(
+                    Command::new("remove")
+                        .about("remove a site")
+                        .alias("rm")
+                        .arg(Arg::new("name").required(true).help("name of the site")),
+                ),
+        )
+        .subcommand(
+            Command::new("target")
+                .about("named targets in the config file, usable wherever an Ra and Dec go")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("add a target, or replace one of the same name")
+                        .arg(Arg::new("name").required(true).help("name to use in place of the Ra and Dec, e.g. M31"))
+                        .arg(
+                            Arg::new("ra")
+                                .required(true)
+                                .allow_hyphen_values(true)
+                                .help("J2000 Ra in degrees or hh:mm:ss"),
+                        )
+                        .arg(
+                            Arg::new("dec")
+                                .required(true)
+                                .allow_hyphen_values(true)
+                                .help("J2000 Dec in degrees or dd:mm:ss"),
+                        ),
+                )
+                .subcommand(Command::new("list").about("list the targets"))
+                .subcommand(
+                    Command::new("remove")
+                        .about("remove a target")
+                        .alias("rm")
+                        .arg(Arg::new("name").required(true).help("name of the target")),
+                ),
+        )
+        .subcommand(
+            Command::new("batch")
+                .about("angular separation for every row of a CSV/TSV file, written as CSV or JSON")
+                .alias("b")
+                .args(unit_args())
+                .arg(
+                    Arg::new("input")
+                        .help("CSV/TSV file with a header row, - or nothing for stdin"),
+                )
+                .arg(Arg::new("ra1").long("ra1").default_value("ra1").help("column with the Ra of the first coordinate"))
+                .arg(Arg::new("dec1").long("dec1").default_value("dec1").help("column with the Dec of the first coordinate"))
+                .arg(Arg::new("ra2").long("ra2").default_value("ra2").help("column with the Ra of the second coordinate"))
+                .arg(Arg::new("dec2").long("dec2").default_value("dec2").help("column with the Dec of the second coordinate"))
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("output format: csv | json (default: json with --format json, otherwise csv)"),
+                )
+                .arg(
+                    Arg::new("delimiter")
+                        .long("delimiter")
+                        .short('d')
+                        .help("input delimiter, e.g. , ; or tab (default: tab if the header has one, else comma)"),
+                ),
+        )
+        .subcommand(
+            Command::new("crossmatch")
+                .about("match every source of one CSV/TSV catalog to its nearest neighbours in another")
+                .alias("x")
+                .args(unit_args())
+                .arg(Arg::new("first").required(true).help("catalog to find matches for"))
+                .arg(Arg::new("second").required(true).help("catalog to search"))
+                .arg(Arg::new("radius").required(true).help("match radius in arcseconds"))
+                .arg(Arg::new("ra").long("ra").default_value("ra").help("Ra column of the first catalog"))
+                .arg(Arg::new("dec").long("dec").default_value("dec").help("Dec column of the first catalog"))
+                .arg(Arg::new("ra2").long("ra2").help("Ra column of the second catalog (default: same as --ra)"))
+                .arg(Arg::new("dec2").long("dec2").help("Dec column of the second catalog (default: same as --dec)"))
+                .arg(
+                    Arg::new("neighbors")
+                        .long("neighbors")
+                        .short('k')
+                        .default_value("1")
+                        .help("number of nearest matches to keep per source"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("output format: csv | json (default: json with --format json, otherwise csv)"),
+                )
+                .arg(
+                    Arg::new("delimiter")
+                        .long("delimiter")
+                        .short('d')
+                        .help("input delimiter, e.g. , ; or tab (default: tab if the header has one, else comma)"),
                 ),
         )
         .subcommand (
             Command::new("convert")
-            .about("various conversions")
+            .about("convert angles, distances and parallaxes between units")
             .alias("c")
             .arg(
-                Arg::new("conversion")
+                Arg::new("value")
                 .required(true)
-                .help("rad-deg | deg-rad")
+                .allow_hyphen_values(true)
+                .help("value to convert, e.g. 90, \"3pi/2\", \"45 + 30/60\" or -05:23:27.6")
             )
-            .arg (
-                Arg::new("input")
+            .arg(
+                Arg::new("from")
                 .required(true)
-                .help("value to convert")
+                // the value in the older `convert rad-deg <value>` form
+                .allow_hyphen_values(true)
+                .help("unit of the value: deg rad pi h arcmin arcsec mas dms hms km au ly pc")
+            )
+            .arg(
+                Arg::new("to")
+                .help("unit to convert to, same choices as from")
             )
         )
-        .get_matches();
+}
+
+fn main() {
+    let command: Command = cli();
+    let argv: Vec<String> = std::env::args().collect();
+    let argv: Vec<String> = match expand_presets(&command, argv.clone(), &mut None) {
+        Ok(expanded) => expanded,
+        Err(e) => {
+            eprint!("{}", render_error(requested_format(&argv), &e.to_string()));
+            std::process::exit(e.exit_code());
+        }
+    };
+    let matches: clap::ArgMatches = command.get_matches_from(argv);
+    let format: Format = Format::parse(matches.get_one::<String>("format").unwrap()).unwrap();
 
-    match matches.subcommand() {
+    let result: Result<String, Error> = match matches.subcommand() {
         Some(("distance", args)) => {
-            let isdeg: &String = args.get_one::<String>("isdeg").unwrap();
-            let showwork: &String = args.get_one::<String>("showwork").unwrap();
-            let alpha1: &f32 = args.get_one::<f32>("alpha1").unwrap();
-            let delta1: &f32 = args.get_one::<f32>("delta1").unwrap();
-            let alpha2: &f32 = args.get_one::<f32>("alpha2").unwrap();
-            let delta2: &f32 = args.get_one::<f32>("delta2").unwrap();
-            calc_distance(isdeg.clone(),showwork.clone(), *alpha1, *delta1, *alpha2, *delta2)
+            let alpha1: &String = args.get_one::<String>("alpha1").unwrap();
+            let delta1: &String = args.get_one::<String>("delta1").unwrap();
+            let alpha2: &String = args.get_one::<String>("alpha2").unwrap();
+            let delta2: &String = args.get_one::<String>("delta2").unwrap();
+            calc_distance(format, bare_unit(args), show_work(args, format), alpha1.clone(), delta1.clone(), alpha2.clone(), delta2.clone())
         }
-        Some (("convert", args)) => {
-            let conversion: &String = args.get_one::<String>("conversion").unwrap();
+        Some(("offset", args)) => {
+            let alpha1: &String = args.get_one::<String>("alpha1").unwrap();
+            let delta1: &String = args.get_one::<String>("delta1").unwrap();
+            let alpha2: &String = args.get_one::<String>("alpha2").unwrap();
+            let delta2: &String = args.get_one::<String>("delta2").unwrap();
+            calc_offset(format, bare_unit(args), show_work(args, format), alpha1.clone(), delta1.clone(), alpha2.clone(), delta2.clone())
+        }
+        Some(("transform", args)) => {
+            let from: &String = args.get_one::<String>("from").unwrap();
+            let to: &String = args.get_one::<String>("to").unwrap();
+            let lon: &String = args.get_one::<String>("lon").unwrap();
+            let lat: &String = args.get_one::<String>("lat").unwrap();
+            calc_transform(format, from.clone(), to.clone(), bare_unit(args), show_work(args, format), lon.clone(), lat.clone())
+        }
+        Some(("epoch", args)) => {
+            let from: &String = args.get_one::<String>("from").unwrap();
+            let to: &String = args.get_one::<String>("to").unwrap();
+            let ra: &String = args.get_one::<String>("ra").unwrap();
+            let dec: &String = args.get_one::<String>("dec").unwrap();
+            let nutate: bool = args.get_flag("nutation");
+            calc_epoch(format, from.clone(), to.clone(), bare_unit(args), show_work(args, format), ra.clone(), dec.clone(), nutate)
+        }
+        Some(("propagate", args)) => {
+            let from: &String = args.get_one::<String>("from").unwrap();
+            let to: &String = args.get_one::<String>("to").unwrap();
+            let values: [String; 4] = ["ra", "dec", "pmra", "pmdec"].map(|name: &str| args.get_one::<String>(name).unwrap().clone());
+            let parallax: Option<&String> = args.get_one::<String>("parallax");
+            let rv: Option<&String> = args.get_one::<String>("rv");
+            calc_propagate(format, from.clone(), to.clone(), bare_unit(args), show_work(args, format), values, parallax.cloned(), rv.cloned())
+        }
+        Some(("annual", args)) => {
+            let ra: &String = args.get_one::<String>("ra").unwrap();
+            let dec: &String = args.get_one::<String>("dec").unwrap();
+            let time: &String = args.get_one::<String>("time").unwrap();
+            let parallax: Option<&String> = args.get_one::<String>("parallax");
+            calc_annual(format, bare_unit(args), show_work(args, format), ra.clone(), dec.clone(), time.clone(), parallax.cloned())
+        }
+        Some(("time", args)) => {
             let input: &String = args.get_one::<String>("input").unwrap();
-            convert(conversion.clone(), input.clone());
+            let scale: &String = args.get_one::<String>("scale").unwrap();
+            let longitude: Option<&String> = args.get_one::<String>("longitude");
+            calc_time(format, show_work(args, format), input.clone(), scale.clone(), longitude.cloned())
+        }
+        Some(("altaz", args)) => {
+            let ra: &String = args.get_one::<String>("ra").unwrap();
+            let dec: &String = args.get_one::<String>("dec").unwrap();
+            let lat: &String = args.get_one::<String>("lat").unwrap();
+            let lon: &String = args.get_one::<String>("lon").unwrap();
+            let time: &String = args.get_one::<String>("time").unwrap();
+            let elevation: Option<&String> = args.get_one::<String>("elevation");
+            calc_altaz(format, bare_unit(args), show_work(args, format), ra.clone(), dec.clone(), lat.clone(), lon.clone(), time.clone(), elevation.cloned())
+        }
+        Some(("refraction", args)) => {
+            let altitude: &String = args.get_one::<String>("altitude").unwrap();
+            let pressure: &String = args.get_one::<String>("pressure").unwrap();
+            let temperature: &String = args.get_one::<String>("temperature").unwrap();
+            calc_refraction(format, show_work(args, format), altitude.clone(), args.get_flag("apparent"), pressure.clone(), temperature.clone())
+        }
+        Some(("riseset", args)) => {
+            let ra: &String = args.get_one::<String>("ra").unwrap();
+            let dec: &String = args.get_one::<String>("dec").unwrap();
+            let lat: &String = args.get_one::<String>("lat").unwrap();
+            let lon: &String = args.get_one::<String>("lon").unwrap();
+            let date: &String = args.get_one::<String>("date").unwrap();
+            let horizon: &String = args.get_one::<String>("horizon").unwrap();
+            calc_riseset(format, bare_unit(args), show_work(args, format), ra.clone(), dec.clone(), lat.clone(), lon.clone(), date.clone(), horizon.clone())
+        }
+        Some(("sun", args)) => {
+            let lat: &String = args.get_one::<String>("lat").unwrap();
+            let lon: &String = args.get_one::<String>("lon").unwrap();
+            let time: &String = args.get_one::<String>("time").unwrap();
+            let elevation: Option<&String> = args.get_one::<String>("elevation");
+            calc_sun(format, show_work(args, format), lat.clone(), lon.clone(), time.clone(), elevation.cloned())
+        }
+        Some(("moon", args)) => {
+            let lat: &String = args.get_one::<String>("lat").unwrap();
+            let lon: &String = args.get_one::<String>("lon").unwrap();
+            let time: &String = args.get_one::<String>("time").unwrap();
+            let elevation: Option<&String> = args.get_one::<String>("elevation");
+            calc_moon(format, show_work(args, format), lat.clone(), lon.clone(), time.clone(), elevation.cloned())
+        }
+        Some(("planet", args)) => {
+            let name: &String = args.get_one::<String>("name").unwrap();
+            let time: &String = args.get_one::<String>("time").unwrap();
+            calc_planet(format, show_work(args, format), name.clone(), time.clone())
+        }
+        Some(("orbit", args)) => {
+            let file: &String = args.get_one::<String>("file").unwrap();
+            let object: &String = args.get_one::<String>("object").unwrap();
+            let time: &String = args.get_one::<String>("time").unwrap();
+            calc_orbit(format, show_work(args, format), file.clone(), object.clone(), time.clone())
+        }
+        Some(("plan", args)) => {
+            let targets: &String = args.get_one::<String>("targets").unwrap();
+            let lat: &String = args.get_one::<String>("lat").unwrap();
+            let lon: &String = args.get_one::<String>("lon").unwrap();
+            let date: &String = args.get_one::<String>("date").unwrap();
+            let min_alt: &String = args.get_one::<String>("min_alt").unwrap();
+            let columns: [String; 3] = ["name", "ra", "dec"].map(|c: &str| args.get_one::<String>(c).unwrap().clone());
+            let delimiter: Option<&String> = args.get_one::<String>("delimiter");
+            calc_plan(format, bare_unit(args), targets.clone(), lat.clone(), lon.clone(), date.clone(), min_alt.clone(), args.get_flag("chart"), columns, delimiter.cloned())
+        }
+        Some(("site", args)) => {
+            let (action, args): (&str, &clap::ArgMatches) = args.subcommand().unwrap();
+            let get = |id: &str| args.try_get_one::<String>(id).ok().flatten().cloned();
+            calc_site(format, action, get("name"), get("lat"), get("lon"), get("elevation"))
+        }
+        Some(("target", args)) => {
+            let (action, args): (&str, &clap::ArgMatches) = args.subcommand().unwrap();
+            let get = |id: &str| args.try_get_one::<String>(id).ok().flatten().cloned();
+            calc_target(format, action, get("name"), get("ra"), get("dec"))
+        }
+        Some(("batch", args)) => {
+            let input: Option<&String> = args.get_one::<String>("input");
+            let columns: [String; 4] = ["ra1", "dec1", "ra2", "dec2"].map(|c: &str| args.get_one::<String>(c).unwrap().clone());
+            let output: String = table_output(args, format);
+            let delimiter: Option<&String> = args.get_one::<String>("delimiter");
+            calc_batch(bare_unit(args), input.cloned(), columns, output, delimiter.cloned())
+        }
+        Some(("crossmatch", args)) => {
+            let first: &String = args.get_one::<String>("first").unwrap();
+            let second: &String = args.get_one::<String>("second").unwrap();
+            let radius: &String = args.get_one::<String>("radius").unwrap();
+            let ra: &String = args.get_one::<String>("ra").unwrap();
+            let dec: &String = args.get_one::<String>("dec").unwrap();
+            let ra2: &String = args.get_one::<String>("ra2").unwrap_or(ra);
+            let dec2: &String = args.get_one::<String>("dec2").unwrap_or(dec);
+            let neighbors: &String = args.get_one::<String>("neighbors").unwrap();
+            let output: String = table_output(args, format);
+            let delimiter: Option<&String> = args.get_one::<String>("delimiter");
+            let columns: [String; 4] = [ra.clone(), dec.clone(), ra2.clone(), dec2.clone()];
+            calc_crossmatch(format, bare_unit(args), first.clone(), second.clone(), radius.clone(), columns, neighbors.clone(), output, delimiter.cloned())
+        }
+        Some (("convert", args)) => {
+            let value: &String = args.get_one::<String>("value").unwrap();
+            let from: &String = args.get_one::<String>("from").unwrap();
+            let to: Option<&String> = args.get_one::<String>("to");
+            calc_convert(format, value.clone(), from.clone(), to.cloned())
+        }
+        // clap requires a subcommand, this is only reached if one is added without a branch here
+        _ => Err(Error::Usage("no subcommand given, see `astrocalc --help`".to_string())),
+    };
+    match result {
+        Ok(text) => print!("{}", text),
+        Err(e) => {
+            eprint!("{}", render_error(format, &e.to_string()));
+            std::process::exit(e.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn presets() -> Option<Config> {
+        let data: &str = "[sites.home]\nlat = 52\nlon = -1.5\nelevation = 120\n\n[targets.m31]\nra = 10.6846\ndec = 41.2692\n\n[targets.vega]\nra = \"18:36:56.3\"\ndec = 38.7837\n";
+        Some(Config::parse(data).unwrap())
+    }
+
+    #[test]
+    fn targets_keep_their_units() {
+        // preset numbers are degrees, whatever --rad says about the numbers typed
+        let argv: Vec<String> = expand_presets(&cli(), args("astrocalc distance --rad m31 vega"), &mut presets()).unwrap();
+        let matches: clap::ArgMatches = cli().try_get_matches_from(&argv).unwrap();
+        let (_, sub): (&str, &clap::ArgMatches) = matches.subcommand().unwrap();
+        let value = |id: &str| sub.get_one::<String>(id).unwrap().clone();
+        let named: EquatorialCoord = EquatorialCoord::parse(&value("alpha2"), &value("delta2"), bare_unit(sub)).unwrap();
+        let typed: EquatorialCoord = EquatorialCoord::parse("18:36:56.3", "38.7837", AngleUnit::Degrees).unwrap();
+        assert!((named.ra.radians() - typed.ra.radians()).abs() < 1e-14 && (named.dec.radians() - typed.dec.radians()).abs() < 1e-14);
+        assert!(calc_distance(Format::Json, bare_unit(sub), false, value("alpha1"), value("delta1"), value("alpha2"), value("delta2")).is_ok());
+
+        // coordinates and unknown names are left as typed
+        let argv: Vec<String> = args("astrocalc distance 10 20 andromeda 21");
+        assert_eq!(expand_presets(&cli(), argv.clone(), &mut presets()).unwrap(), argv);
+    }
+
+    #[test]
+    fn sites_fill_lat_lon_and_more() {
+        let argv: Vec<String> = expand_presets(&cli(), args("astrocalc altaz M31 --site home now"), &mut presets()).unwrap();
+        assert_eq!(argv[1..], args("altaz --elevation=120 10.6846deg 41.2692deg 52 -1.5 now")[..]);
+        assert!(cli().try_get_matches_from(&argv).is_ok());
+        // an elevation given wins, and time takes only the longitude
+        let argv: Vec<String> = expand_presets(&cli(), args("astrocalc sun --site home -e 5 now"), &mut presets()).unwrap();
+        assert_eq!(argv[1..], args("sun -e 5 52 -1.5 now")[..]);
+        let argv: Vec<String> = expand_presets(&cli(), args("astrocalc --format json time now --site home"), &mut presets()).unwrap();
+        assert_eq!(argv[3..], args("time --longitude=-1.5 now")[..]);
+        assert!(matches!(expand_presets(&cli(), args("astrocalc moon --site nowhere now"), &mut presets()), Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn crossmatch_radius_must_be_positive() {
+        let columns = || ["ra".to_string(), "dec".to_string(), "ra".to_string(), "dec".to_string()];
+        for radius in ["-5", "0", "nan", "inf", "5m"] {
+            let result = calc_crossmatch(Format::Json, AngleUnit::Degrees, "a.csv".to_string(), "b.csv".to_string(), radius.to_string(), columns(), "1".to_string(), "csv".to_string(), None);
+            assert!(matches!(result, Err(Error::Input(message)) if message.starts_with("Invalid radius")), "{}", radius);
+        }
+    }
+
+    #[test]
+    fn propagate_rejects_rv_without_parallax() {
+        let values = |pm_ra: &str| ["10".to_string(), "20".to_string(), pm_ra.to_string(), "100".to_string()];
+        let propagate = |pm_ra: &str, parallax: Option<&str>, rv: Option<&str>| {
+            calc_propagate(Format::Json, "J2020".to_string(), "J2030".to_string(), AngleUnit::Degrees, false, values(pm_ra), parallax.map(str::to_string), rv.map(str::to_string))
+        };
+        assert!(matches!(propagate("100", None, Some("30")), Err(Error::Usage(_))));
+        assert!(propagate("100", Some("50"), Some("30")).is_ok());
+        assert!(propagate("100", None, None).is_ok());
+        match propagate("fast", None, None) {
+            Err(Error::Input(message)) => assert!(message.contains("μα*")),
+            _ => panic!("expected an input error"),
         }
-        _ => println!("No subcommand was used."),
     }
 }
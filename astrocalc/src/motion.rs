@@ -0,0 +1,139 @@
+// rigorous propagation of a star's position and proper motion between epochs, treating its
+// space motion as uniform and straight (ESA 1997, The Hipparcos Catalogue vol. 1, §1.5.5)
+
+use crate::coords::EquatorialCoord;
+use crate::trace::{Derived, Trace};
+
+// the astronomical unit in km·yr/s, converts parallax × radial velocity to radial proper motion
+pub const AU_KM_YR_PER_S: f64 = 4.740470446;
+pub const MAS_TO_RAD: f64 = crate::PI / (180.0 * 3600.0 * 1000.0);
+
+// a catalogue entry: position, proper motions (μα* = μα cos δ) and parallax in mas/yr and mas,
+// radial velocity in km/s
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Astrometry {
+    pub coord: EquatorialCoord,
+    pub pm_ra_cos_dec: f64,
+    pub pm_dec: f64,
+    pub parallax: f64,
+    pub radial_velocity: f64,
+}
+
+// unit vectors towards increasing α and δ at a position (the normal triad p, q)
+fn triad(coord: &EquatorialCoord) -> ([f64; 3], [f64; 3]) {
+    let (sa, ca): (f64, f64) = (coord.ra.sin(), coord.ra.cos());
+    let (sd, cd): (f64, f64) = (coord.dec.sin(), coord.dec.cos());
+    ([-sa, ca, 0.0], [-sd * ca, -sd * sa, cd])
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// the star `years` Julian years after its catalogue epoch. without a parallax the radial
+// velocity can't be used and the motion is along a great circle at constant rate
+pub fn propagate(star: &Astrometry, years: f64, show_work: bool) -> Derived<Astrometry> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+    let t: f64 = years;
+
+    note!(trace, "Normal triad at the catalogue position");
+    note!(trace, "p = (-sin(α), cos(α), 0), q = (-sin(δ)cos(α), -sin(δ)sin(α), cos(δ)), r = (cos(δ)cos(α), cos(δ)sin(α), sin(δ))");
+    let r: [f64; 3] = star.coord.to_vector();
+    let (p, q): ([f64; 3], [f64; 3]) = triad(&star.coord);
+    let mu0: [f64; 3] = [0, 1, 2].map(|i: usize| p[i] * star.pm_ra_cos_dec + q[i] * star.pm_dec);
+    value!(trace, ["μ₀ = p μα* + q μδ"], ["({}, {}, {}) mas/yr", mu0[0], mu0[1], mu0[2]]);
+
+    note!(trace, "Radial proper motion, the fractional change in distance per year");
+    let zeta0: f64 = star.radial_velocity * star.parallax / AU_KM_YR_PER_S;
+    value!(trace, ["ζ₀ = vr ϖ / A = {} * {} / {}", star.radial_velocity, star.parallax, AU_KM_YR_PER_S], ["{} mas/yr", zeta0]);
+
+    // everything in radians from here, so μ²t² is dimensionless
+    let mu0: [f64; 3] = mu0.map(|m: f64| m * MAS_TO_RAD);
+    let zeta0: f64 = zeta0 * MAS_TO_RAD;
+    let mu0_sq: f64 = dot(mu0, mu0);
+    note!(trace, "Distance factor after t years");
+    note!(trace, "f = [1 + 2ζ₀t + (μ₀² + ζ₀²)t²]^-1/2");
+    let f: f64 = 1.0 / (1.0 + 2.0 * zeta0 * t + (mu0_sq + zeta0 * zeta0) * t * t).sqrt();
+    value!(trace, ["t"], ["{} yr", t]);
+    value!(trace, ["f"], ["{}", f]);
+
+    note!(trace, "Propagated direction and proper motion");
+    note!(trace, "u = [r(1 + ζ₀t) + μ₀t] f");
+    let u: [f64; 3] = [0, 1, 2].map(|i: usize| (r[i] * (1.0 + zeta0 * t) + mu0[i] * t) * f);
+    value!(trace, ["u"], ["({}, {}, {})", u[0], u[1], u[2]]);
+    note!(trace, "μ = [μ₀(1 + ζ₀t) - rμ₀²t] f³");
+    let mu: [f64; 3] = [0, 1, 2].map(|i: usize| (mu0[i] * (1.0 + zeta0 * t) - r[i] * mu0_sq * t) * f.powi(3));
+    let zeta: f64 = (zeta0 + (mu0_sq + zeta0 * zeta0) * t) * f * f;
+
+    let coord: EquatorialCoord = EquatorialCoord::from_vector(u);
+    let (p, q): ([f64; 3], [f64; 3]) = triad(&coord);
+    let parallax: f64 = star.parallax * f;
+    let out: Astrometry = Astrometry {
+        coord,
+        pm_ra_cos_dec: dot(p, mu) / MAS_TO_RAD,
+        pm_dec: dot(q, mu) / MAS_TO_RAD,
+        parallax,
+        // ζ = vr ϖ / A again, undefined without a parallax so the input is kept
+        radial_velocity: if parallax > 0.0 { zeta / MAS_TO_RAD * AU_KM_YR_PER_S / parallax } else { star.radial_velocity },
+    };
+    note!(trace, "α = atan2(u_y, u_x), δ = atan2(u_z, √(u_x² + u_y²)), μα* = p·μ, μδ = q·μ at the new position");
+    value!(trace, ["α"], ["{}°", out.coord.ra.degrees()]);
+    value!(trace, ["δ"], ["{}°", out.coord.dec.degrees()]);
+    value!(trace, ["μα*, μδ"], ["{}, {} mas/yr", out.pm_ra_cos_dec, out.pm_dec]);
+    if star.parallax > 0.0 {
+        note!(trace, "ϖ = ϖ₀ f, vr = ζ A / ϖ with ζ = [ζ₀ + (μ₀² + ζ₀²)t] f²");
+        value!(trace, ["ϖ"], ["{} mas", out.parallax]);
+        value!(trace, ["vr"], ["{} km/s", out.radial_velocity]);
+    }
+
+    Derived { value: out, trace }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Angle;
+    use crate::coords::angular_separation;
+
+    // Barnard's star from the Hipparcos catalogue, epoch J1991.25
+    fn barnard() -> Astrometry {
+        Astrometry {
+            coord: EquatorialCoord::new(Angle::from_degrees(269.45402305), Angle::from_degrees(4.66828815)),
+            pm_ra_cos_dec: -797.84,
+            pm_dec: 10326.93,
+            parallax: 549.01,
+            radial_velocity: -110.6,
+        }
+    }
+
+    #[test]
+    fn barnards_star_to_j2000() {
+        // SIMBAD J2000 position 17 57 48.50 +04 41 36.2, to the precision of the old catalogue
+        let out: Astrometry = propagate(&barnard(), 8.75, false).value;
+        let expected: EquatorialCoord = EquatorialCoord::new(Angle::from_hms(17.0, 57.0, 48.50), Angle::from_dms(false, 4.0, 41.0, 36.2));
+        let error: f64 = angular_separation(out.coord.ra.radians(), out.coord.dec.radians(), expected.ra.radians(), expected.dec.radians());
+        assert!(Angle::from_radians(error).arcseconds() < 0.1, "off by {}″", Angle::from_radians(error).arcseconds());
+        // perspective acceleration: the star gets closer and its motion speeds up
+        assert!(out.parallax > 549.01 && out.pm_dec > 10326.93);
+        // and part of the transverse velocity turns into radial, dvr/dt ≈ vt²/d ≈ 0.005 km/s/yr
+        assert!((out.radial_velocity - -110.6 - 8.75 * 0.005).abs() < 0.005, "got {}", out.radial_velocity);
+    }
+
+    #[test]
+    fn round_trip_and_linear_limit() {
+        let star: Astrometry = barnard();
+        let there: Astrometry = propagate(&star, 1000.0, false).value;
+        let back: Astrometry = propagate(&there, -1000.0, false).value;
+        let error: f64 = angular_separation(back.coord.ra.radians(), back.coord.dec.radians(), star.coord.ra.radians(), star.coord.dec.radians());
+        assert!(error < 1e-12);
+        assert!((back.pm_dec - star.pm_dec).abs() < 1e-6 && (back.parallax - star.parallax).abs() < 1e-6);
+        assert!((back.radial_velocity - star.radial_velocity).abs() < 1e-6);
+
+        // with no parallax, one year moves the star by its proper motion
+        let distant: Astrometry = Astrometry { parallax: 0.0, radial_velocity: 0.0, pm_ra_cos_dec: 0.0, ..star };
+        let out: Derived<Astrometry> = propagate(&distant, 1.0, true);
+        assert!((out.value.coord.dec.arcseconds() - star.coord.dec.arcseconds() - 10.32693).abs() < 1e-6);
+        assert_eq!(out.value.radial_velocity, 0.0);
+        assert!(out.trace.is_some());
+    }
+}
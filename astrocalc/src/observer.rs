@@ -0,0 +1,330 @@
+// where a target is in the sky of an observer: alt/az, airmass and rise/transit/set
+
+use crate::angle::{Angle, AngleUnit};
+use crate::atmosphere::AirmassModel;
+use crate::coords::EquatorialCoord;
+use crate::parse::{parse_coord, CoordKind};
+use crate::precession::{centuries_since_j2000, mean_obliquity, nutation, precess, J2000_JD};
+use crate::time::{format_jd_iso, gmst, utc_to_tt};
+use crate::trace::{Derived, Trace};
+use crate::PI;
+
+// sidereal days per solar day
+pub const SIDEREAL_RATE: f64 = 1.00273790935;
+
+// observer location, longitude east positive, elevation in metres
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Site {
+    pub lat: Angle,
+    pub lon: Angle,
+    pub elevation: f64,
+}
+
+pub fn parse_site(lat: &str, lon: &str, elevation: Option<&str>) -> Result<Site, String> {
+    let elevation: f64 = match elevation {
+        Some(e) => e.trim().parse().map_err(|_| format!("invalid elevation: {}", e))?,
+        None => 0.0,
+    };
+    Ok(Site {
+        lat: parse_coord(lat, CoordKind::SiteLat, AngleUnit::Degrees)?,
+        lon: parse_coord(lon, CoordKind::SiteLon, AngleUnit::Degrees)?,
+        elevation,
+    })
+}
+
+// Greenwich apparent sidereal time in radians, GMST plus the equation of the equinoxes
+pub fn gast(jd_utc: f64) -> f64 {
+    let t: f64 = centuries_since_j2000(utc_to_tt(jd_utc));
+    let (dpsi, deps): (f64, f64) = nutation(t);
+    (gmst(jd_utc) + dpsi * (mean_obliquity(t) + deps).cos()).rem_euclid(2.0 * PI)
+}
+
+// J2000 mean Ra/Dec -> true Ra/Dec of date, radians
+pub fn j2000_to_date(jd_utc: f64, ra: f64, dec: f64) -> (f64, f64) {
+    precess(J2000_JD, utc_to_tt(jd_utc), true, ra, dec)
+}
+
+// hour angle, Dec and latitude in radians -> (altitude, azimuth from north through east)
+pub fn equatorial_to_horizontal(ha: f64, dec: f64, lat: f64) -> (f64, f64) {
+    let alt: f64 = (lat.sin() * dec.sin() + lat.cos() * dec.cos() * ha.cos()).asin();
+    let az: f64 = (-dec.cos() * ha.sin())
+        .atan2(dec.sin() * lat.cos() - dec.cos() * ha.cos() * lat.sin())
+        .rem_euclid(2.0 * PI);
+    (alt, az)
+}
+
+// angle between the directions to the zenith and to the pole at the target, radians
+pub fn parallactic_angle(ha: f64, dec: f64, lat: f64) -> f64 {
+    ha.sin().atan2(lat.tan() * dec.cos() - dec.sin() * ha.cos())
+}
+
+// Kasten & Young (1989) relative airmass of a true altitude in radians, None below the horizon
+pub fn airmass(alt: f64) -> Option<f64> {
+    AirmassModel::KastenYoung.airmass(alt * (180.0 / PI))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Horizontal {
+    pub alt: Angle,
+    // north through east
+    pub az: Angle,
+    pub hour_angle: Angle,
+    // local apparent sidereal time
+    pub lst: Angle,
+    pub airmass: Option<f64>,
+    pub parallactic_angle: Angle,
+    // depression of the sea horizon, when the site has an elevation
+    pub dip: Option<Angle>,
+}
+
+// alt/az and friends of a J2000 position seen from site at jd_utc
+pub fn horizontal(coord: &EquatorialCoord, site: Site, jd_utc: f64, show_work: bool) -> Derived<Horizontal> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+
+    let (ra_date, dec_date): (f64, f64) = j2000_to_date(jd_utc, coord.ra.radians(), coord.dec.radians());
+    note!(trace, "Precess and nutate J2000 → true equator and equinox of date (see `epoch --nutation`)");
+    value!(trace, ["α"], ["{}°", ra_date * (180.0 / PI)]);
+    value!(trace, ["δ"], ["{}°", dec_date * (180.0 / PI)]);
+
+    let lst: f64 = (gast(jd_utc) + site.lon.radians()).rem_euclid(2.0 * PI);
+    note!(trace, "Local apparent sidereal time (see `time`)");
+    note!(trace, "LST = GMST + Δψcos(ε) + λ");
+    value!(trace, ["LST"], ["{}°", lst * (180.0 / PI)]);
+
+    let ha: f64 = lst - ra_date;
+    note!(trace, "Hour angle");
+    note!(trace, "H = LST - α");
+    value!(trace, ["H = {} - {}", lst * (180.0 / PI), ra_date * (180.0 / PI)], ["{}°", ha * (180.0 / PI)]);
+
+    let lat: f64 = site.lat.radians();
+    let (alt, az): (f64, f64) = equatorial_to_horizontal(ha, dec_date, lat);
+    note!(trace, "Altitude and azimuth (north through east)");
+    note!(trace, "sin(a) = sin(φ)sin(δ) + cos(φ)cos(δ)cos(H)");
+    value!(trace, ["a"], ["{}°", alt * (180.0 / PI)]);
+    note!(trace, "A = atan2(-cos(δ)sin(H), sin(δ)cos(φ) - cos(δ)cos(H)sin(φ))");
+    value!(trace, ["A"], ["{}°", az * (180.0 / PI)]);
+
+    let q: f64 = parallactic_angle(ha, dec_date, lat);
+    note!(trace, "Parallactic angle");
+    note!(trace, "q = atan2(sin(H), tan(φ)cos(δ) - sin(δ)cos(H))");
+    value!(trace, ["q"], ["{}°", q * (180.0 / PI)]);
+
+    let x: Option<f64> = airmass(alt);
+    if let Some(x) = x {
+        note!(trace, "Airmass (Kasten & Young 1989)");
+        note!(trace, "X = 1 / (sin(a) + 0.50572(a° + 6.07995)^-1.6364)");
+        value!(trace, ["X"], ["{}", x]);
+    }
+
+    let dip: Option<Angle> = (site.elevation > 0.0).then(|| {
+        // depression of the sea horizon seen from height h, ignoring refraction
+        let dip: f64 = (2.0 * site.elevation / 6378137.0).sqrt();
+        note!(trace, "Dip of the horizon from h = {} m", site.elevation);
+        note!(trace, "dip = √(2h / R⊕)");
+        value!(trace, ["dip"], ["{}°", dip * (180.0 / PI)]);
+        Angle::from_radians(dip)
+    });
+
+    Derived {
+        value: Horizontal {
+            alt: Angle::from_radians(alt),
+            az: Angle::from_radians(az),
+            hour_angle: Angle::from_radians(ha),
+            lst: Angle::from_radians(lst),
+            airmass: x,
+            parallactic_angle: Angle::from_radians(q),
+            dip,
+        },
+        trace,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Visibility {
+    // Julian dates (UTC) of the crossings of the horizon altitude
+    RisesAndSets { rise: f64, set: f64 },
+    Circumpolar,
+    NeverRises,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RiseSet {
+    // Julian date (UTC) of the upper transit and the altitude there, radians
+    pub transit: f64,
+    pub transit_alt: f64,
+    pub visibility: Visibility,
+}
+
+// first rise, upper transit and set of a fixed target in the 24 h from jd_start (UTC).
+// Ra/Dec of date and horizon altitude in radians
+pub fn rise_transit_set(jd_start: f64, site: Site, ra: f64, dec: f64, horizon: f64) -> RiseSet {
+    let lat: f64 = site.lat.radians();
+    let lst_start: f64 = gast(jd_start) + site.lon.radians();
+    // days until the local sidereal time next equals the given value
+    let next = |target_lst: f64| -> f64 {
+        jd_start + (target_lst - lst_start).rem_euclid(2.0 * PI) / (2.0 * PI * SIDEREAL_RATE)
+    };
+
+    let transit: f64 = next(ra);
+    let transit_alt: f64 = PI / 2.0 - (lat - dec).abs();
+    let cos_h0: f64 = (horizon.sin() - lat.sin() * dec.sin()) / (lat.cos() * dec.cos());
+    let visibility: Visibility = if cos_h0 < -1.0 {
+        Visibility::Circumpolar
+    } else if cos_h0 > 1.0 {
+        Visibility::NeverRises
+    } else {
+        let h0: f64 = cos_h0.acos();
+        Visibility::RisesAndSets { rise: next(ra - h0), set: next(ra + h0) }
+    };
+    RiseSet { transit, transit_alt, visibility }
+}
+
+// rise, transit and set of a J2000 position in the 24 h from jd_start (UTC), precessed to
+// the middle of the window
+pub fn rise_set(coord: &EquatorialCoord, site: Site, jd_start: f64, horizon: Angle, show_work: bool) -> Derived<RiseSet> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+
+    let (ra_date, dec_date): (f64, f64) = j2000_to_date(jd_start + 0.5, coord.ra.radians(), coord.dec.radians());
+    note!(trace, "Precess and nutate J2000 → date (see `epoch --nutation`)");
+    value!(trace, ["α"], ["{}°", ra_date * (180.0 / PI)]);
+    value!(trace, ["δ"], ["{}°", dec_date * (180.0 / PI)]);
+
+    let lat: f64 = site.lat.radians();
+    let lst_start: f64 = (gast(jd_start) + site.lon.radians()).rem_euclid(2.0 * PI);
+    let result: RiseSet = rise_transit_set(jd_start, site, ra_date, dec_date, horizon.radians());
+    note!(trace, "Local sidereal time at the start of the window (see `time`)");
+    value!(trace, ["LST₀"], ["{}°", lst_start * (180.0 / PI)]);
+    note!(trace, "Transit when LST = α");
+    note!(trace, "t = t₀ + ((α - LST₀) mod 360°) / (360° × 1.00273790935) days");
+    value!(trace, ["t_transit"], ["{} (JD {})", format_jd_iso(result.transit), result.transit]);
+    note!(trace, "a_transit = 90° - |φ - δ|");
+    value!(trace, ["a_transit"], ["{}°", result.transit_alt * (180.0 / PI)]);
+
+    let cos_h0: f64 = (horizon.radians().sin() - lat.sin() * dec_date.sin()) / (lat.cos() * dec_date.cos());
+    note!(trace, "Hour angle at the horizon altitude");
+    note!(trace, "cos(H₀) = (sin(h₀) - sin(φ)sin(δ)) / (cos(φ)cos(δ))");
+    value!(trace, ["cos(H₀)"], ["{}", cos_h0]);
+    match result.visibility {
+        Visibility::RisesAndSets { rise, set } => {
+            let h0: f64 = cos_h0.acos() * (180.0 / PI);
+            value!(trace, ["H₀"], ["{}° = {}", h0, Angle::from_degrees(h0).format_hms()]);
+            note!(trace, "Rise when LST = α - H₀, set when LST = α + H₀");
+            value!(trace, ["t_rise"], ["{}", format_jd_iso(rise)]);
+            value!(trace, ["t_set"], ["{}", format_jd_iso(set)]);
+        }
+        Visibility::Circumpolar => {
+            note!(trace, "cos(H₀) < -1, the target never drops below {}°", horizon.degrees());
+        }
+        Visibility::NeverRises => {
+            note!(trace, "cos(H₀) > 1, the target never reaches {}°", horizon.degrees());
+        }
+    }
+
+    Derived { value: result, trace }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::civil_to_jd;
+
+    fn site(lat: f64, lon: f64) -> Site {
+        Site { lat: Angle::from_degrees(lat), lon: Angle::from_degrees(lon), elevation: 0.0 }
+    }
+
+    #[test]
+    fn site_parsing() {
+        let parsed: Site = parse_site("52.1", "-4.5", Some("120")).unwrap();
+        assert!((parsed.lat.degrees() - 52.1).abs() < 1e-12);
+        assert!((parsed.lon.degrees() + 4.5).abs() < 1e-12);
+        assert_eq!(parsed.elevation, 120.0);
+        assert!(parse_site("95", "0", None).is_err());
+        assert!(parse_site("10", "0", Some("high")).is_err());
+    }
+
+    #[test]
+    fn apparent_sidereal_time_close_to_mean() {
+        // the equation of the equinoxes never exceeds about 1.2 s of time
+        let jd: f64 = civil_to_jd(1987, 4, 10.0);
+        let difference: f64 = (gast(jd) - gmst(jd)) * (180.0 / PI) * 240.0;
+        assert!(difference.abs() < 1.2, "got {}s", difference);
+        // Meeus example 12.a: GAST = 13h10m46.1351s, from a full nutation series
+        let gast_seconds: f64 = gast(jd) * (180.0 / PI) * 240.0;
+        assert!((gast_seconds - (13.0 * 3600.0 + 10.0 * 60.0 + 46.1351)).abs() < 0.1, "got {}s", gast_seconds);
+    }
+
+    #[test]
+    fn horizontal_meeus() {
+        // Meeus example 13.b: Venus from the US Naval Observatory, H = 64.352133°
+        let (alt, az) = equatorial_to_horizontal(
+            64.352133f64.to_radians(),
+            (-6.719892f64).to_radians(),
+            (38.0 + 55.0 / 60.0 + 17.0 / 3600.0f64).to_radians(),
+        );
+        assert!((alt.to_degrees() - 15.1249).abs() < 1e-3, "got {}", alt.to_degrees());
+        // Meeus measures azimuth from the south, 68.0337° westward
+        assert!((az.to_degrees() - (180.0 + 68.0337)).abs() < 1e-3, "got {}", az.to_degrees());
+    }
+
+    #[test]
+    fn airmass_and_parallactic_angle() {
+        assert!((airmass(PI / 2.0).unwrap() - 1.0).abs() < 1e-3);
+        assert!((airmass(30f64.to_radians()).unwrap() - 1.995).abs() < 1e-2);
+        assert!(airmass(-0.01).is_none());
+        // on the meridian south of the zenith the parallactic angle is zero
+        assert!(parallactic_angle(0.0, 0.1, 0.6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn horizontal_of_the_pole() {
+        // the celestial pole sits at the latitude due north, whatever the time
+        let pole: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(0.0), Angle::from_degrees(90.0));
+        let mut high: Site = site(52.0, 5.0);
+        high.elevation = 100.0;
+        let result: Horizontal = horizontal(&pole, high, civil_to_jd(2024, 3, 20.0), false).value;
+        assert!((result.alt.degrees() - 52.0).abs() < 0.5, "got {}", result.alt.degrees());
+        let az: f64 = (result.az.degrees() + 180.0).rem_euclid(360.0) - 180.0;
+        assert!(az.abs() < 0.5, "got {}", az);
+        assert!((result.dip.unwrap().degrees() - 0.321).abs() < 1e-3);
+        assert!(result.airmass.unwrap() > 1.0);
+    }
+
+    #[test]
+    fn rise_transit_set_fixed_star() {
+        let start: f64 = civil_to_jd(2024, 3, 20.0);
+        let result: RiseSet = rise_transit_set(start, site(45.0, 0.0), 1.0, 0.0, 0.0);
+        // an equatorial star is up for half a sidereal day, centred on the transit
+        match result.visibility {
+            Visibility::RisesAndSets { rise, set } => {
+                let half: f64 = 0.25 / SIDEREAL_RATE;
+                let transit: f64 = result.transit;
+                let wrap = |t: f64| (t - transit + 0.5).rem_euclid(1.0 / SIDEREAL_RATE) - 0.5;
+                assert!((wrap(rise) + half).abs() < 1e-9);
+                assert!((wrap(set) - half).abs() < 1e-9);
+            }
+            other => panic!("expected rise and set, got {:?}", other),
+        }
+        let lst: f64 = gast(result.transit).rem_euclid(2.0 * PI);
+        assert!((lst - 1.0).abs() < 1e-6);
+        assert!((result.transit_alt.to_degrees() - 45.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn circumpolar_and_never_rising() {
+        let start: f64 = civil_to_jd(2024, 1, 1.0);
+        let polaris: RiseSet = rise_transit_set(start, site(52.0, 5.0), 0.66, 89.26f64.to_radians(), 0.0);
+        assert_eq!(polaris.visibility, Visibility::Circumpolar);
+        let south: RiseSet = rise_transit_set(start, site(52.0, 5.0), 0.66, (-60f64).to_radians(), 0.0);
+        assert_eq!(south.visibility, Visibility::NeverRises);
+    }
+
+    #[test]
+    fn rise_set_precesses_first() {
+        let coord: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(83.633), Angle::from_degrees(22.0145));
+        let start: f64 = civil_to_jd(2024, 1, 1.0);
+        let result: Derived<RiseSet> = rise_set(&coord, site(52.0, 5.0), start, Angle::from_degrees(-0.5667), true);
+        assert!(matches!(result.value.visibility, Visibility::RisesAndSets { .. }));
+        assert!(result.value.transit > start && result.value.transit < start + 1.0);
+        assert!(result.trace.is_some());
+    }
+}
@@ -0,0 +1,546 @@
+// two-body orbits: Kepler's equation for every conic, classical elements <-> state vectors, and
+// minor planets and comets from MPC element lines
+
+use crate::angle::Angle;
+use crate::annual::GAUSS_K;
+use crate::coords::{from_vector, mat_vec, rot1, EquatorialCoord};
+use crate::planets::{earth_position, elongation_and_phase, LIGHT_TIME_DAYS_PER_AU};
+use crate::precession::{centuries_since_j2000, mean_obliquity};
+use crate::time::{civil_to_jd, days_in_month, utc_to_tt};
+use crate::trace::{Derived, Trace};
+use crate::PI;
+
+// the Sun's gravitational parameter in AU³/day²
+pub const MU_SUN: f64 = GAUSS_K * GAUSS_K;
+
+// eccentricities closer to 1 than this are treated as parabolic
+const PARABOLIC_TOLERANCE: f64 = 1e-8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Conic {
+    Elliptic,
+    Parabolic,
+    Hyperbolic,
+}
+
+impl Conic {
+    pub fn of(e: f64) -> Conic {
+        if (e - 1.0).abs() < PARABOLIC_TOLERANCE {
+            Conic::Parabolic
+        } else if e < 1.0 {
+            Conic::Elliptic
+        } else {
+            Conic::Hyperbolic
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Conic::Elliptic => "elliptic",
+            Conic::Parabolic => "parabolic",
+            Conic::Hyperbolic => "hyperbolic",
+        }
+    }
+}
+
+// eccentric anomaly E from the mean anomaly M = E - e sin(E), radians, by Newton's method
+pub fn solve_kepler(mean_anomaly: f64, e: f64) -> f64 {
+    let m: f64 = (mean_anomaly + PI).rem_euclid(2.0 * PI) - PI;
+    let mut ecc: f64 = if e < 0.8 { m } else { PI.copysign(m) };
+    for _ in 0..50 {
+        let step: f64 = (ecc - e * ecc.sin() - m) / (1.0 - e * ecc.cos());
+        ecc -= step;
+        if step.abs() < 1e-15 {
+            break;
+        }
+    }
+    ecc
+}
+
+// hyperbolic anomaly H from M = e sinh(H) - H
+pub fn solve_hyperbolic(mean_anomaly: f64, e: f64) -> f64 {
+    let m: f64 = mean_anomaly;
+    let mut h: f64 = (2.0 * m.abs() / e + 1.8).ln().copysign(m);
+    for _ in 0..100 {
+        let step: f64 = (e * h.sinh() - h - m) / (e * h.cosh() - 1.0);
+        h -= step;
+        if step.abs() < 1e-15 * h.abs().max(1.0) {
+            break;
+        }
+    }
+    h
+}
+
+// s = tan(ν/2) from Barker's equation s³ + 3s = W, in closed form
+pub fn solve_barker(w: f64) -> f64 {
+    let y: f64 = (w / 2.0 + (w * w / 4.0 + 1.0).sqrt()).cbrt();
+    y - 1.0 / y
+}
+
+// perihelion distance q (AU), eccentricity, and inclination, longitude of the ascending node,
+// argument of perihelion (radians, J2000 ecliptic), and time of perihelion (JD TT)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitalElements {
+    pub q: f64,
+    pub e: f64,
+    pub inclination: f64,
+    pub node: f64,
+    pub perihelion: f64,
+    pub perihelion_time: f64,
+}
+
+// heliocentric position (AU) and velocity (AU/day) on the J2000 ecliptic
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StateVector {
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+}
+
+impl OrbitalElements {
+    pub fn conic(&self) -> Conic {
+        Conic::of(self.e)
+    }
+
+    // semi-major axis, negative for a hyperbola and infinite for a parabola
+    pub fn semi_major_axis(&self) -> f64 {
+        self.q / (1.0 - self.e)
+    }
+
+    // the period in days of an elliptic orbit
+    pub fn period(&self) -> Option<f64> {
+        (self.conic() == Conic::Elliptic).then(|| 2.0 * PI * self.semi_major_axis().powf(1.5) / GAUSS_K)
+    }
+
+    // true anomaly (radians) and distance from the Sun (AU) at jd_tt
+    pub fn anomaly(&self, jd_tt: f64) -> (f64, f64) {
+        let (q, e): (f64, f64) = (self.q, self.e);
+        let dt: f64 = jd_tt - self.perihelion_time;
+        match self.conic() {
+            Conic::Elliptic => {
+                let a: f64 = q / (1.0 - e);
+                let ecc: f64 = solve_kepler(GAUSS_K / a.powf(1.5) * dt, e);
+                let nu: f64 = 2.0 * (((1.0 + e) / (1.0 - e)).sqrt() * (ecc / 2.0).tan()).atan();
+                (nu, a * (1.0 - e * ecc.cos()))
+            }
+            Conic::Hyperbolic => {
+                let a: f64 = q / (e - 1.0);
+                let h: f64 = solve_hyperbolic(GAUSS_K / a.powf(1.5) * dt, e);
+                let nu: f64 = 2.0 * (((e + 1.0) / (e - 1.0)).sqrt() * (h / 2.0).tanh()).atan();
+                (nu, a * (e * h.cosh() - 1.0))
+            }
+            Conic::Parabolic => {
+                let s: f64 = solve_barker(3.0 * GAUSS_K / (2.0 * q * q * q).sqrt() * dt);
+                (2.0 * s.atan(), q * (1.0 + s * s))
+            }
+        }
+    }
+
+    // the rotation from the orbital plane (x towards perihelion) to the ecliptic, as the images
+    // of the x and y axes
+    fn plane(&self) -> ([f64; 3], [f64; 3]) {
+        let (so, co): (f64, f64) = self.perihelion.sin_cos();
+        let (sn, cn): (f64, f64) = self.node.sin_cos();
+        let (si, ci): (f64, f64) = self.inclination.sin_cos();
+        (
+            [co * cn - so * sn * ci, co * sn + so * cn * ci, so * si],
+            [-so * cn - co * sn * ci, -so * sn + co * cn * ci, co * si],
+        )
+    }
+
+    pub fn state(&self, jd_tt: f64) -> StateVector {
+        let (nu, r): (f64, f64) = self.anomaly(jd_tt);
+        let p: f64 = self.q * (1.0 + self.e);
+        let speed: f64 = (MU_SUN / p).sqrt();
+        let (x, y): ([f64; 3], [f64; 3]) = self.plane();
+        let (px, py): (f64, f64) = (r * nu.cos(), r * nu.sin());
+        let (vx, vy): (f64, f64) = (-speed * nu.sin(), speed * (self.e + nu.cos()));
+        StateVector {
+            position: [0, 1, 2].map(|i: usize| x[i] * px + y[i] * py),
+            velocity: [0, 1, 2].map(|i: usize| x[i] * vx + y[i] * vy),
+        }
+    }
+
+    // the osculating elements of a state vector at jd_tt
+    pub fn from_state(state: &StateVector, jd_tt: f64) -> OrbitalElements {
+        let (r, v): ([f64; 3], [f64; 3]) = (state.position, state.velocity);
+        let r_len: f64 = length(r);
+        let h: [f64; 3] = cross(r, v);
+        let h_len: f64 = length(h);
+        let rv: f64 = dot(r, v);
+        let v2: f64 = dot(v, v);
+        let e_vec: [f64; 3] = [0, 1, 2].map(|i: usize| ((v2 - MU_SUN / r_len) * r[i] - rv * v[i]) / MU_SUN);
+        let e: f64 = length(e_vec);
+        let q: f64 = h_len * h_len / MU_SUN / (1.0 + e);
+        let inclination: f64 = (h[2] / h_len).clamp(-1.0, 1.0).acos();
+        // the ascending node is along ẑ × h; in the ecliptic it is undefined and taken as 0
+        let node: f64 = if h[0].hypot(h[1]) > 1e-15 * h_len { h[0].atan2(-h[1]).rem_euclid(2.0 * PI) } else { 0.0 };
+        let node_dir: [f64; 3] = [node.cos(), node.sin(), 0.0];
+        // angles in the orbital plane measured from the node, towards the motion
+        let in_plane = |u: [f64; 3]| -> f64 { dot(cross(node_dir, u), h).atan2(dot(node_dir, u) * h_len) };
+        let perihelion: f64 = if e > 1e-12 { in_plane(e_vec).rem_euclid(2.0 * PI) } else { 0.0 };
+        let nu: f64 = in_plane(r) - perihelion;
+
+        let elements: OrbitalElements = OrbitalElements { q, e, inclination, node, perihelion, perihelion_time: jd_tt };
+        let since_perihelion: f64 = match elements.conic() {
+            Conic::Elliptic => {
+                let a: f64 = q / (1.0 - e);
+                let ecc: f64 = 2.0 * (((1.0 - e) / (1.0 + e)).sqrt() * (nu / 2.0).tan()).atan();
+                (ecc - e * ecc.sin()) / (GAUSS_K / a.powf(1.5))
+            }
+            Conic::Hyperbolic => {
+                let a: f64 = q / (e - 1.0);
+                let h: f64 = 2.0 * (((e - 1.0) / (e + 1.0)).sqrt() * (nu / 2.0).tan()).atanh();
+                (e * h.sinh() - h) / (GAUSS_K / a.powf(1.5))
+            }
+            Conic::Parabolic => {
+                let s: f64 = (nu / 2.0).tan();
+                (s * s * s + 3.0 * s) * (2.0 * q * q * q).sqrt() / (3.0 * GAUSS_K)
+            }
+        };
+        OrbitalElements { perihelion_time: jd_tt - since_perihelion, ..elements }
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn length(v: [f64; 3]) -> f64 {
+    dot(v, v).sqrt()
+}
+
+// H, G for an asteroid (IAU two-parameter system), H and the activity slope K for a comet's
+// total magnitude m = H + 5 log Δ + 2.5 K log r
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Brightness {
+    Asteroid { h: f64, g: f64 },
+    Comet { h: f64, k: f64 },
+    Unknown,
+}
+
+impl Brightness {
+    pub fn magnitude(&self, r: f64, delta: f64, phase_angle: f64) -> Option<f64> {
+        match *self {
+            Brightness::Asteroid { h, g } => {
+                let tan_half: f64 = (phase_angle / 2.0).tan();
+                let phi1: f64 = (-3.33 * tan_half.powf(0.63)).exp();
+                let phi2: f64 = (-1.87 * tan_half.powf(1.22)).exp();
+                Some(h + 5.0 * (r * delta).log10() - 2.5 * ((1.0 - g) * phi1 + g * phi2).log10())
+            }
+            Brightness::Comet { h, k } => Some(h + 5.0 * delta.log10() + 2.5 * k * r.log10()),
+            Brightness::Unknown => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MinorBody {
+    pub name: String,
+    pub elements: OrbitalElements,
+    pub brightness: Brightness,
+}
+
+// 1-based, inclusive columns of a fixed-width line, trimmed
+fn column(line: &str, from: usize, to: usize) -> &str {
+    line.get(from - 1..to.min(line.len())).unwrap_or("").trim()
+}
+
+fn number(line: &str, from: usize, to: usize, what: &str) -> Result<f64, String> {
+    let text: &str = column(line, from, to);
+    text.parse::<f64>().map_err(|_| format!("invalid {} in columns {}-{}: '{}'", what, from, to, text))
+}
+
+// an MPC packed date, e.g. K239D = 2023 September 13 -> JD (TT)
+pub fn unpack_epoch(packed: &str) -> Result<f64, String> {
+    let invalid = || format!("invalid packed epoch: {}", packed);
+    let digit = |c: char| -> Option<i64> {
+        match c {
+            '0'..='9' => Some(c as i64 - '0' as i64),
+            'A'..='V' => Some(c as i64 - 'A' as i64 + 10),
+            _ => None,
+        }
+    };
+    let chars: Vec<char> = packed.trim().chars().collect();
+    if chars.len() != 5 {
+        return Err(invalid());
+    }
+    let century: i64 = match chars[0] {
+        'I' => 18,
+        'J' => 19,
+        'K' => 20,
+        _ => return Err(invalid()),
+    };
+    let decade: i64 = chars[1].to_digit(10).ok_or_else(invalid)? as i64;
+    let year: i64 = century * 100 + decade * 10 + chars[2].to_digit(10).ok_or_else(invalid)? as i64;
+    let month: i64 = digit(chars[3]).filter(|m: &i64| (1..=12).contains(m)).ok_or_else(invalid)?;
+    let day: i64 = digit(chars[4]).filter(|d: &i64| (1..=days_in_month(year, month)).contains(d)).ok_or_else(invalid)?;
+    Ok(civil_to_jd(year, month, day as f64))
+}
+
+// one line of MPCORB.DAT (asteroids: epoch, M, a) or CometEls.txt (comets: T, q). angles are
+// on the J2000 ecliptic
+pub fn parse_mpc_line(line: &str) -> Result<MinorBody, String> {
+    let line: &str = line.trim_end();
+    // comets have a four-digit perihelion year in columns 15-18
+    let is_comet: bool = column(line, 15, 18).len() == 4 && column(line, 15, 18).chars().all(|c: char| c.is_ascii_digit());
+    let angles = |peri: (usize, usize), node: (usize, usize), incl: (usize, usize)| -> Result<(f64, f64, f64), String> {
+        Ok((
+            number(line, peri.0, peri.1, "argument of perihelion")?.to_radians(),
+            number(line, node.0, node.1, "ascending node")?.to_radians(),
+            number(line, incl.0, incl.1, "inclination")?.to_radians(),
+        ))
+    };
+    if is_comet {
+        let year: i64 = column(line, 15, 18).parse().map_err(|_| "invalid perihelion year".to_string())?;
+        let month: i64 = column(line, 20, 21).parse().map_err(|_| "invalid perihelion month".to_string())?;
+        let day: f64 = number(line, 23, 29, "perihelion day")?;
+        let (perihelion, node, inclination): (f64, f64, f64) = angles((52, 59), (62, 69), (72, 79))?;
+        let name: &str = column(line, 103, 158);
+        let brightness: Brightness = match (number(line, 92, 95, "H"), number(line, 97, 100, "K")) {
+            (Ok(h), Ok(k)) => Brightness::Comet { h, k },
+            _ => Brightness::Unknown,
+        };
+        Ok(MinorBody {
+            name: if name.is_empty() { column(line, 1, 12).to_string() } else { name.to_string() },
+            elements: OrbitalElements {
+                q: number(line, 31, 39, "perihelion distance")?,
+                e: number(line, 42, 49, "eccentricity")?,
+                inclination,
+                node,
+                perihelion,
+                perihelion_time: civil_to_jd(year, month, day),
+            },
+            brightness,
+        })
+    } else {
+        let epoch: f64 = unpack_epoch(column(line, 21, 25))?;
+        let mean_anomaly: f64 = number(line, 27, 35, "mean anomaly")?;
+        let (perihelion, node, inclination): (f64, f64, f64) = angles((38, 46), (49, 57), (60, 68))?;
+        let e: f64 = number(line, 71, 79, "eccentricity")?;
+        let motion: f64 = number(line, 81, 91, "mean daily motion")?;
+        let a: f64 = number(line, 93, 103, "semi-major axis")?;
+        if !(0.0..1.0).contains(&e) || motion <= 0.0 {
+            return Err(format!("not an elliptic orbit: e = {}, n = {}", e, motion));
+        }
+        // the perihelion passage nearest the epoch
+        let since_perihelion: f64 = ((mean_anomaly + 180.0).rem_euclid(360.0) - 180.0) / motion;
+        let name: &str = column(line, 167, 194);
+        let brightness: Brightness = match (number(line, 9, 13, "H"), number(line, 15, 19, "G")) {
+            (Ok(h), Ok(g)) => Brightness::Asteroid { h, g },
+            (Ok(h), Err(_)) => Brightness::Asteroid { h, g: 0.15 },
+            _ => Brightness::Unknown,
+        };
+        Ok(MinorBody {
+            name: if name.is_empty() { column(line, 1, 7).to_string() } else { name.to_string() },
+            elements: OrbitalElements { q: a * (1.0 - e), e, inclination, node, perihelion, perihelion_time: epoch - since_perihelion },
+            brightness,
+        })
+    }
+}
+
+// the first body in an element file whose name or designation contains `object`, ignoring case.
+// blank lines and lines starting with # are skipped
+pub fn find_minor_body(data: &str, object: &str) -> Result<MinorBody, String> {
+    let wanted: String = object.trim().to_lowercase();
+    for (number, line) in data.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') || !line.to_lowercase().contains(&wanted) {
+            continue;
+        }
+        return parse_mpc_line(line).map_err(|e| format!("line {}: {}", number + 1, e));
+    }
+    Err(format!("no elements for '{}'", object.trim()))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinorPosition {
+    pub state: StateVector,
+    pub r: f64,
+    pub coord: EquatorialCoord,
+    pub delta: f64,
+    pub elongation: f64,
+    pub phase_angle: f64,
+    pub magnitude: Option<f64>,
+}
+
+// astrometric J2000 place of a minor body at jd_utc, corrected for light time
+pub fn minor_body(body: &MinorBody, jd_utc: f64, show_work: bool) -> Derived<MinorPosition> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+    let el: &OrbitalElements = &body.elements;
+    let jd_tt: f64 = utc_to_tt(jd_utc);
+
+    note!(trace, "{} orbit, q = {} AU, e = {}", el.conic().name(), el.q, el.e);
+    value!(trace, ["i, Ω, ω"], ["{}°, {}°, {}°", el.inclination.to_degrees(), el.node.to_degrees(), el.perihelion.to_degrees()]);
+    value!(trace, ["T"], ["JD {}", el.perihelion_time]);
+    match el.conic() {
+        Conic::Elliptic => note!(trace, "M = k(t - T) / a^1.5, M = E - e sin(E), tan(ν/2) = √((1 + e)/(1 - e)) tan(E/2)"),
+        Conic::Hyperbolic => note!(trace, "M = k(t - T) / |a|^1.5, M = e sinh(H) - H, tan(ν/2) = √((e + 1)/(e - 1)) tanh(H/2)"),
+        Conic::Parabolic => note!(trace, "W = 3k(t - T) / √(2q³), s³ + 3s = W, ν = 2 atan(s)"),
+    }
+    let (nu, r): (f64, f64) = el.anomaly(jd_tt);
+    value!(trace, ["t - T"], ["{} days", jd_tt - el.perihelion_time]);
+    value!(trace, ["ν"], ["{}°", nu.to_degrees()]);
+    value!(trace, ["r"], ["{} AU", r]);
+
+    note!(trace, "State vector on the J2000 ecliptic, r = R(Ω, i, ω) r(cos(ν), sin(ν), 0), v = R √(μ/p) (-sin(ν), e + cos(ν), 0)");
+    let state: StateVector = el.state(jd_tt);
+    value!(trace, ["x, y, z"], ["{}, {}, {} AU", state.position[0], state.position[1], state.position[2]]);
+    value!(trace, ["ẋ, ẏ, ż"], ["{}, {}, {} AU/day", state.velocity[0], state.velocity[1], state.velocity[2]]);
+
+    let earth: [f64; 3] = earth_position(centuries_since_j2000(jd_tt));
+    let mut helio: [f64; 3] = state.position;
+    let mut geo: [f64; 3] = [0, 1, 2].map(|i: usize| helio[i] - earth[i]);
+    let mut light_time: f64 = 0.0;
+    for _ in 0..3 {
+        light_time = length(geo) * LIGHT_TIME_DAYS_PER_AU;
+        helio = el.state(jd_tt - light_time).position;
+        geo = [0, 1, 2].map(|i: usize| helio[i] - earth[i]);
+    }
+    note!(trace, "Geocentric vector, body - Earth, with the body moved back by the light time τ = Δ / c");
+    value!(trace, ["τ"], ["{} days", light_time]);
+    let delta: f64 = length(geo);
+    value!(trace, ["Δ"], ["{} AU", delta]);
+    let (ra, dec): (f64, f64) = from_vector(mat_vec(&rot1(-mean_obliquity(0.0)), geo));
+    let coord: EquatorialCoord = EquatorialCoord::new(Angle::from_radians(ra), Angle::from_radians(dec));
+    value!(trace, ["α, δ"], ["{}°, {}°", coord.ra.degrees(), coord.dec.degrees()]);
+
+    let (elongation, phase_angle): (f64, f64) = elongation_and_phase(helio, earth);
+    let magnitude: Option<f64> = body.brightness.magnitude(length(helio), delta, phase_angle);
+    match body.brightness {
+        Brightness::Asteroid { h, g } => note!(trace, "V = H + 5 log(rΔ) - 2.5 log((1 - G)Φ₁ + GΦ₂), H = {}, G = {}", h, g),
+        Brightness::Comet { h, k } => note!(trace, "m = H + 5 log Δ + 2.5K log r, H = {}, K = {}", h, k),
+        Brightness::Unknown => {}
+    }
+    if let Some(m) = magnitude {
+        value!(trace, ["magnitude"], ["{}", m]);
+    }
+
+    Derived {
+        value: MinorPosition { state, r: length(helio), coord, delta, elongation, phase_angle, magnitude },
+        trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f64; 3], b: [f64; 3], tolerance: f64) -> bool {
+        (0..3).all(|i: usize| (a[i] - b[i]).abs() < tolerance)
+    }
+
+    #[test]
+    fn kepler_equations() {
+        for (m, e) in [(0.3, 0.1), (3.0, 0.9), (-2.0, 0.5), (10.0, 0.2), (0.01, 0.999)] {
+            let ecc: f64 = solve_kepler(m, e);
+            let residual: f64 = (ecc - e * ecc.sin() - m).rem_euclid(2.0 * PI);
+            assert!(residual < 1e-12 || 2.0 * PI - residual < 1e-12, "M = {}, e = {}", m, e);
+        }
+        for (m, e) in [(0.5, 1.5), (-20.0, 1.1), (1000.0, 3.0)] {
+            let h: f64 = solve_hyperbolic(m, e);
+            assert!((e * h.sinh() - h - m).abs() < 1e-9 * m.abs().max(1.0), "M = {}, e = {}", m, e);
+        }
+        for w in [0.0, 1.0, -7.5, 300.0] {
+            let s: f64 = solve_barker(w);
+            assert!((s * s * s + 3.0 * s - w).abs() < 1e-9 * w.abs().max(1.0));
+        }
+    }
+
+    #[test]
+    fn state_vector_round_trips() {
+        let base: OrbitalElements = OrbitalElements {
+            q: 0.8,
+            e: 0.0,
+            inclination: 0.4,
+            node: 1.2,
+            perihelion: 2.5,
+            perihelion_time: 2460000.5,
+        };
+        for e in [0.2, 0.97, 1.0, 1.4] {
+            let el: OrbitalElements = OrbitalElements { e, ..base };
+            for jd in [2459950.5, 2460000.5, 2460123.25] {
+                let state: StateVector = el.state(jd);
+                // vis-viva
+                let r: f64 = length(state.position);
+                let expected: f64 = if el.conic() == Conic::Parabolic { 2.0 * MU_SUN / r } else { MU_SUN * (2.0 / r - 1.0 / el.semi_major_axis()) };
+                assert!((dot(state.velocity, state.velocity) - expected).abs() < 1e-12, "e = {}", e);
+                let back: OrbitalElements = OrbitalElements::from_state(&state, jd);
+                assert!((back.q - el.q).abs() < 1e-9 && (back.e - el.e).abs() < 1e-9, "e = {}: {:?}", e, back);
+                assert!((back.inclination - el.inclination).abs() < 1e-9 && (back.node - el.node).abs() < 1e-9);
+                assert!((back.perihelion - el.perihelion).abs() < 1e-9, "e = {}: {:?}", e, back);
+                assert!((back.perihelion_time - el.perihelion_time).abs() < 1e-6, "e = {}: {:?}", e, back);
+                assert!(close(back.state(jd + 10.0).position, el.state(jd + 10.0).position, 1e-9));
+            }
+        }
+        // a circular orbit in the ecliptic has no node or perihelion, but the same motion
+        let circle: OrbitalElements = OrbitalElements { q: 1.0, e: 0.0, inclination: 0.0, node: 0.0, perihelion: 0.0, perihelion_time: 2460000.5 };
+        let back: OrbitalElements = OrbitalElements::from_state(&circle.state(2460050.5), 2460050.5);
+        assert!(close(back.state(2460100.5).position, circle.state(2460100.5).position, 1e-9));
+        assert_eq!(circle.period().map(|p: f64| (p - 365.2568983).abs() < 1e-4), Some(true));
+    }
+
+    #[test]
+    fn encke_meeus() {
+        // Meeus example 33.b, comet Encke on 1990 October 6.0 TD:
+        // α = 158.558965°, δ = +19.182409° (J2000), with a low precision Earth good to ~1′
+        let encke: OrbitalElements = OrbitalElements {
+            q: 2.2091404 * (1.0 - 0.8502196),
+            e: 0.8502196,
+            inclination: 11.94524f64.to_radians(),
+            node: 334.75006f64.to_radians(),
+            perihelion: 186.23352f64.to_radians(),
+            perihelion_time: 2448192.5 + 0.54502,
+        };
+        let body: MinorBody = MinorBody { name: "2P/Encke".to_string(), elements: encke, brightness: Brightness::Unknown };
+        let jd_utc: f64 = 2448170.5 - 57.184 / 86400.0;
+        let out: Derived<MinorPosition> = minor_body(&body, jd_utc, true);
+        assert!((out.value.coord.ra.degrees() - 158.558965).abs() < 0.03, "got {}", out.value.coord.ra.degrees());
+        assert!((out.value.coord.dec.degrees() - 19.182409).abs() < 0.03, "got {}", out.value.coord.dec.degrees());
+        assert!(out.value.magnitude.is_none() && out.trace.is_some());
+    }
+
+    #[test]
+    fn mpc_lines() {
+        assert_eq!(unpack_epoch("K239D").unwrap(), civil_to_jd(2023, 9, 13.0));
+        assert!(unpack_epoch("X239D").is_err());
+        assert_eq!(unpack_epoch("K242T").unwrap(), civil_to_jd(2024, 2, 29.0));
+        // February 29 and 30 of 2023, a multi-byte character in the year, and a letter in the year
+        for packed in ["K232U", "K232T", "Ké39D", "K2A9D"] {
+            assert!(unpack_epoch(packed).is_err(), "{}", packed);
+        }
+
+        let mut asteroid: String = format!(
+            "{:<7} {:>5} {:>5} {:>5} {:>9}  {:>9}  {:>9}  {:>9}  {:>9} {:>11} {:>11}",
+            "00001", "3.33", "0.12", "K239D", "60.07881", "73.42179", "80.25496", "10.58688", "0.0789126", "0.21411523", "2.7671189"
+        );
+        asteroid.push_str(&" ".repeat(166 - asteroid.len()));
+        asteroid.push_str("(1) Ceres");
+        let ceres: MinorBody = parse_mpc_line(&asteroid).unwrap();
+        assert_eq!(ceres.name, "(1) Ceres");
+        assert!((ceres.elements.q - 2.7671189 * (1.0 - 0.0789126)).abs() < 1e-12);
+        assert!((ceres.elements.perihelion_time - (civil_to_jd(2023, 9, 13.0) - 60.07881 / 0.21411523)).abs() < 1e-9);
+        assert_eq!(ceres.brightness, Brightness::Asteroid { h: 3.33, g: 0.12 });
+        // the mean anomaly it was given comes back at the epoch
+        let period: f64 = ceres.elements.period().unwrap();
+        assert!((period - 360.0 / 0.21411523).abs() < 0.5, "got {}", period);
+
+        let comet: String = format!(
+            "{:<4}{:1}{:<7}  {:4} {:02} {:7} {:9}  {:8}  {:8}  {:8}  {:8}  {:8}  {:>4} {:>4}  {}",
+            "0001", "P", "", "2061", "07", "28.0000", " 0.587700", "0.967000", "112.2500", " 59.5000", "162.1900", "20610728", " 5.5", " 8.0", "1P/Halley"
+        );
+        let halley: MinorBody = parse_mpc_line(&comet).unwrap();
+        assert_eq!(halley.name, "1P/Halley");
+        assert_eq!(halley.elements.perihelion_time, civil_to_jd(2061, 7, 28.0));
+        assert!((halley.elements.q - 0.5877).abs() < 1e-12 && (halley.elements.inclination.to_degrees() - 162.19).abs() < 1e-9);
+        assert_eq!(halley.brightness, Brightness::Comet { h: 5.5, k: 8.0 });
+
+        let data: String = format!("# elements\n{}\n{}\n", asteroid, comet);
+        assert_eq!(find_minor_body(&data, "halley").unwrap().name, "1P/Halley");
+        assert_eq!(find_minor_body(&data, "CERES").unwrap().name, "(1) Ceres");
+        assert!(find_minor_body(&data, "vesta").is_err());
+        assert!(parse_mpc_line("00002 garbage").is_err());
+    }
+}
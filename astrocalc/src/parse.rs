@@ -0,0 +1,187 @@
+// parsing of sexagesimal and unit-suffixed angles
+
+use crate::angle::{Angle, AngleUnit};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoordKind {
+    Ra,
+    Dec,
+    // galactic l or ecliptic λ, like Ra but sexagesimal input is in degrees
+    Lon,
+    // observer longitude (east positive) and latitude, always degrees
+    SiteLon,
+    SiteLat,
+    // any angle, for convert, with sexagesimal input in degrees or hours
+    Angle,
+    Hours,
+}
+
+// parses a single Ra or Dec value. accepted forms:
+//   05h34m31.94s, 05:34:31.9, 05 34 31.9     (Ra, hours)
+//   +22°00'52.2", +22d00m52.2s, +22:00:52    (Dec, degrees)
+//   83.633deg, 83.633°, 1.4597rad             (explicit units)
+//   83.633                                    (bare number, in the `bare` unit)
+pub fn parse_coord(input: &str, kind: CoordKind, bare: AngleUnit) -> Result<Angle, String> {
+    let trimmed: &str = input.trim();
+    if trimmed.is_empty() {
+        return Err("empty coordinate".to_string());
+    }
+
+    let (sign, body): (f64, &str) = if let Some(rest) = trimmed.strip_prefix('-') {
+        (-1.0, rest)
+    } else if let Some(rest) = trimmed.strip_prefix('−') {
+        (-1.0, rest)
+    } else if let Some(rest) = trimmed.strip_prefix('+') {
+        (1.0, rest)
+    } else {
+        (1.0, trimmed)
+    };
+    let body: &str = body.trim();
+
+    let degrees: f64 = if let Some(num) = body.strip_suffix("rad") {
+        parse_number(num, input)?.to_degrees()
+    } else if let Some(num) = body.strip_suffix("deg") {
+        parse_number(num, input)?
+    } else if let Ok(value) = body.parse::<f64>() {
+        match bare {
+            AngleUnit::Degrees => value,
+            AngleUnit::Radians => value.to_degrees(),
+        }
+    } else {
+        parse_sexagesimal(body, kind, input)?
+    };
+    let degrees: f64 = sign * degrees;
+
+    match kind {
+        CoordKind::Ra if !(0.0..=360.0).contains(&degrees) => {
+            Err(format!("Ra out of range (0h to 24h): {}", input))
+        }
+        CoordKind::Dec if !(-90.0..=90.0).contains(&degrees) => {
+            Err(format!("Dec out of range (-90° to +90°): {}", input))
+        }
+        CoordKind::Lon if !(0.0..=360.0).contains(&degrees) => {
+            Err(format!("longitude out of range (0° to 360°): {}", input))
+        }
+        CoordKind::SiteLon if !(-180.0..=360.0).contains(&degrees) => {
+            Err(format!("observer longitude out of range (-180° to 360°): {}", input))
+        }
+        CoordKind::SiteLat if !(-90.0..=90.0).contains(&degrees) => {
+            Err(format!("observer latitude out of range (-90° to +90°): {}", input))
+        }
+        _ => Ok(Angle::from_degrees(degrees)),
+    }
+}
+
+fn parse_number(num: &str, input: &str) -> Result<f64, String> {
+    num.trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid number in coordinate: {}", input))
+}
+
+// splits something like 05h34m31.94s or 22:00:52 into up to three fields and works out
+// whether the leading field is hours or degrees
+fn parse_sexagesimal(body: &str, kind: CoordKind, input: &str) -> Result<f64, String> {
+    let mut fields: Vec<f64> = Vec::new();
+    let mut current: String = String::new();
+    let mut is_hours: bool = matches!(kind, CoordKind::Ra | CoordKind::Hours);
+
+    for c in body.chars() {
+        if c.is_ascii_digit() || c == '.' {
+            current.push(c);
+            continue;
+        }
+        match c {
+            'h' | 'd' | '°' | 'm' | '\'' | '′' | 's' | '"' | '″' | ':' | ' ' => {}
+            _ => return Err(format!("unexpected '{}' in coordinate: {}", c, input)),
+        }
+        if current.is_empty() {
+            // tolerate repeated separators, e.g. "22° 00′" or a trailing unit
+            if c == ' ' {
+                continue;
+            }
+            return Err(format!("missing value before '{}' in coordinate: {}", c, input));
+        }
+        if fields.is_empty() {
+            match c {
+                'h' => is_hours = true,
+                'd' | '°' => is_hours = false,
+                _ => {}
+            }
+        }
+        fields.push(parse_number(&current, input)?);
+        current.clear();
+    }
+    if !current.is_empty() {
+        fields.push(parse_number(&current, input)?);
+    }
+
+    if fields.is_empty() || fields.len() > 3 {
+        return Err(format!("could not parse coordinate: {}", input));
+    }
+    if fields[1..].iter().any(|f| *f >= 60.0) {
+        return Err(format!("minutes and seconds must be below 60: {}", input));
+    }
+
+    let mut value: f64 = 0.0;
+    for (i, field) in fields.iter().enumerate() {
+        value += field / 60f64.powi(i as i32);
+    }
+    Ok(if is_hours { value * 15.0 } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sexagesimal_forms() {
+        let ra: f64 = 15.0 * (5.0 + 34.0 / 60.0 + 31.94 / 3600.0);
+        for input in ["05h34m31.94s", "05:34:31.94", "05 34 31.94"] {
+            assert!((parse_coord(input, CoordKind::Ra, AngleUnit::Radians).unwrap().degrees() - ra).abs() < 1e-9, "{}", input);
+        }
+        let dec: f64 = 22.0 + 52.2 / 3600.0;
+        for input in ["+22°00'52.2\"", "+22d00m52.2s", "22:00:52.2", "+22° 00′ 52.2″"] {
+            assert!((parse_coord(input, CoordKind::Dec, AngleUnit::Radians).unwrap().degrees() - dec).abs() < 1e-9, "{}", input);
+        }
+        assert!((parse_coord("-00:30:00", CoordKind::Dec, AngleUnit::Radians).unwrap().degrees() + 0.5).abs() < 1e-12);
+        // an explicit h on a longitude and a colon form on a latitude
+        assert!((parse_coord("1h", CoordKind::Lon, AngleUnit::Radians).unwrap().degrees() - 15.0).abs() < 1e-12);
+        assert!((parse_coord("10:30", CoordKind::SiteLat, AngleUnit::Radians).unwrap().degrees() - 10.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn units_and_bare_numbers() {
+        assert_eq!(parse_coord("83.5deg", CoordKind::Ra, AngleUnit::Radians).unwrap().degrees(), 83.5);
+        assert_eq!(parse_coord("83.5°", CoordKind::Ra, AngleUnit::Radians).unwrap().degrees(), 83.5);
+        assert_eq!(parse_coord("83.5", CoordKind::Ra, AngleUnit::Degrees).unwrap().degrees(), 83.5);
+        assert!((parse_coord("1rad", CoordKind::Ra, AngleUnit::Degrees).unwrap().degrees() - 1f64.to_degrees()).abs() < 1e-12);
+        assert!((parse_coord("-0.5", CoordKind::Dec, AngleUnit::Radians).unwrap().degrees() + 0.5f64.to_degrees()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn readme_examples() {
+        // the forms the README lists for `distance`
+        let hours = |h: f64, m: f64, s: f64| 15.0 * (h + m / 60.0 + s / 3600.0);
+        let close = |input: &str, kind: CoordKind, degrees: f64| (parse_coord(input, kind, AngleUnit::Radians).unwrap().degrees() - degrees).abs() < 1e-9;
+        assert!(close("05h34m31.94s", CoordKind::Ra, hours(5.0, 34.0, 31.94)));
+        assert!(close("05:34:31.9", CoordKind::Ra, hours(5.0, 34.0, 31.9)));
+        assert!(close("+22°00'52.2\"", CoordKind::Dec, 22.0 + 52.2 / 3600.0));
+        assert!(close("83.633deg", CoordKind::Ra, 83.633));
+        assert!(close("1.4597rad", CoordKind::Ra, 1.4597f64.to_degrees()));
+        // minutes and seconds stop at 59
+        for (input, kind) in [("05h60m00s", CoordKind::Ra), ("05:34:60", CoordKind::Ra), ("+22°60'00\"", CoordKind::Dec), ("+22°00'60\"", CoordKind::Dec)] {
+            assert!(parse_coord(input, kind, AngleUnit::Degrees).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(parse_coord("", CoordKind::Ra, AngleUnit::Degrees).is_err());
+        assert!(parse_coord("25h", CoordKind::Ra, AngleUnit::Degrees).is_err());
+        assert!(parse_coord("91", CoordKind::Dec, AngleUnit::Degrees).is_err());
+        assert!(parse_coord("10:61:00", CoordKind::Dec, AngleUnit::Degrees).is_err());
+        assert!(parse_coord("5x", CoordKind::Ra, AngleUnit::Degrees).is_err());
+        assert!(parse_coord("1:2:3:4", CoordKind::Ra, AngleUnit::Degrees).is_err());
+        assert!(parse_coord("-190", CoordKind::SiteLon, AngleUnit::Degrees).is_err());
+    }
+}
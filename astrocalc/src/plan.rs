@@ -0,0 +1,215 @@
+// observability of a target list over one night: rise/transit/set, time above a minimum altitude
+// in astronomical darkness, best airmass, distance from the Moon, and an altitude chart
+
+use crate::coords::{angular_separation, EquatorialCoord};
+use crate::ephemeris::{local_noon, moon_illumination, moon_phase_name, moon_position, twilight, Twilight, TWILIGHTS};
+use crate::observer::{airmass, equatorial_to_horizontal, gast, j2000_to_date, rise_transit_set, RiseSet, Site};
+use crate::time::format_jd_iso;
+
+// rise and set are for the apparent horizon, as `riseset` gives by default
+pub const PLAN_HORIZON: f64 = -0.5667;
+// columns of the altitude chart, sampled evenly from sunset to sunrise
+pub const CHART_COLUMNS: usize = 72;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Target {
+    pub name: String,
+    pub coord: EquatorialCoord,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Night {
+    pub noon: f64,
+    // sunset to sunrise and astronomical dusk to dawn, Julian dates (UTC). with no sunset the
+    // night is the 24 h from noon, with no astronomical darkness `dark` is None
+    pub start: f64,
+    pub end: f64,
+    pub dark: Option<(f64, f64)>,
+    // the Moon in the middle of the night
+    pub moon_illuminated: f64,
+    pub moon_phase: &'static str,
+    // when the chart columns are sampled
+    pub times: Vec<f64>,
+}
+
+pub fn night(site: Site, jd_utc: f64) -> Night {
+    let noon: f64 = local_noon(jd_utc, site);
+    let (start, end): (f64, f64) = match twilight(noon, site, TWILIGHTS[0].1.to_radians()) {
+        Twilight::Night { dusk, dawn } => (dusk, dawn),
+        _ => (noon, noon + 1.0),
+    };
+    let dark: Option<(f64, f64)> = match twilight(noon, site, TWILIGHTS[3].1.to_radians()) {
+        Twilight::Night { dusk, dawn } => Some((dusk, dawn)),
+        Twilight::AlwaysBelow => Some((noon, noon + 1.0)),
+        Twilight::AlwaysAbove => None,
+    };
+    let middle: f64 = (start + end) / 2.0;
+    let step: f64 = (end - start) / (CHART_COLUMNS - 1) as f64;
+    Night {
+        noon,
+        start,
+        end,
+        dark,
+        moon_illuminated: moon_illumination(middle).0,
+        moon_phase: moon_phase_name(middle),
+        times: (0..CHART_COLUMNS).map(|i: usize| start + step * i as f64).collect(),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TargetPlan {
+    pub rise_set: RiseSet,
+    // hours at or above the minimum altitude while it is astronomically dark
+    pub dark_hours: f64,
+    // highest altitude in darkness (radians) and its airmass, None if it never gets dark
+    pub best_alt: Option<f64>,
+    pub min_airmass: Option<f64>,
+    // from the Moon in the middle of darkness (or of the night), radians
+    pub moon_separation: f64,
+    // altitude at each of the night's chart times, radians
+    pub altitudes: Vec<f64>,
+}
+
+pub fn plan_target(target: &Target, site: Site, night: &Night, min_alt: f64) -> TargetPlan {
+    let middle: f64 = night.dark.map_or((night.start + night.end) / 2.0, |(dusk, dawn): (f64, f64)| (dusk + dawn) / 2.0);
+    let (ra, dec): (f64, f64) = j2000_to_date(middle, target.coord.ra.radians(), target.coord.dec.radians());
+    let lat: f64 = site.lat.radians();
+    let altitude = |jd: f64| -> f64 { equatorial_to_horizontal(gast(jd) + site.lon.radians() - ra, dec, lat).0 };
+
+    let rise_set: RiseSet = rise_transit_set(night.noon, site, ra, dec, PLAN_HORIZON.to_radians());
+    // minute steps through the dark, plus the transit if it falls inside
+    let (dark_hours, best_alt): (f64, Option<f64>) = match night.dark {
+        Some((dusk, dawn)) => {
+            let steps: usize = ((dawn - dusk) * 1440.0).ceil().max(1.0) as usize;
+            let step: f64 = (dawn - dusk) / steps as f64;
+            let samples: Vec<f64> = (0..steps).map(|i: usize| altitude(dusk + step * (i as f64 + 0.5))).collect();
+            let above: usize = samples.iter().filter(|alt: &&f64| **alt >= min_alt).count();
+            let mut best: f64 = samples.iter().cloned().fold(altitude(dawn).max(altitude(dusk)), f64::max);
+            if (dusk..=dawn).contains(&rise_set.transit) {
+                best = best.max(rise_set.transit_alt);
+            }
+            (above as f64 * step * 24.0, Some(best))
+        }
+        None => (0.0, None),
+    };
+    let (_, moon_ra, moon_dec, _): (f64, f64, f64, f64) = moon_position(middle);
+
+    TargetPlan {
+        rise_set,
+        dark_hours,
+        best_alt,
+        min_airmass: best_alt.and_then(airmass),
+        moon_separation: angular_separation(ra, dec, moon_ra, moon_dec),
+        altitudes: night.times.iter().map(|jd: &f64| altitude(*jd)).collect(),
+    }
+}
+
+// the symbol a target is drawn with on the chart
+pub fn chart_symbol(index: usize) -> char {
+    const SYMBOLS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    SYMBOLS.get(index).map_or('+', |c: &u8| *c as char)
+}
+
+// altitude against time (UTC hours along the bottom), 10° a row labelled with its lower edge.
+// twilight is dotted, the minimum altitude dashed, and later targets are drawn over earlier ones
+pub fn altitude_chart(night: &Night, altitudes: &[&[f64]], min_alt: f64) -> Vec<String> {
+    let dark = |jd: f64| -> bool { night.dark.is_some_and(|(dusk, dawn): (f64, f64)| (dusk..=dawn).contains(&jd)) };
+    let min_row: usize = (min_alt.to_degrees() / 10.0 + 1e-9).floor().clamp(0.0, 8.0) as usize;
+    let mut lines: Vec<String> = Vec::new();
+    for row in (0..9).rev() {
+        let (low, high): (f64, f64) = ((row * 10) as f64, (row * 10 + 10) as f64);
+        let cells: String = (0..night.times.len())
+            .map(|column: usize| {
+                let drawn: Option<usize> = altitudes.iter().rposition(|alts: &&[f64]| {
+                    let alt: f64 = alts[column].to_degrees();
+                    alt >= low && (alt < high || row == 8)
+                });
+                match drawn {
+                    Some(index) => chart_symbol(index),
+                    None if row == min_row && min_alt > 0.0 => '╌',
+                    None if dark(night.times[column]) => ' ',
+                    None => '·',
+                }
+            })
+            .collect();
+        lines.push(format!("{:>3}°┃{}", row * 10, cells));
+    }
+    lines.push(format!("    ┗{}", "━".repeat(night.times.len())));
+
+    // an hour label where each UTC hour starts, skipping those that would run into the last one
+    let mut labels: Vec<char> = vec![' '; night.times.len() + 2];
+    let mut free_from: usize = 0;
+    for column in 1..night.times.len() {
+        let hour: f64 = ((night.times[column] + 0.5) * 24.0).floor();
+        if hour != ((night.times[column - 1] + 0.5) * 24.0).floor() && column >= free_from {
+            let text: String = format!("{:02}", hour.rem_euclid(24.0) as i64);
+            for (offset, c) in text.chars().enumerate() {
+                labels[column + offset] = c;
+            }
+            free_from = column + 3;
+        }
+    }
+    lines.push(format!("     {}", labels.iter().collect::<String>().trim_end()));
+    lines
+}
+
+// HH:MM of a Julian date (UTC)
+pub fn clock(jd: f64) -> String {
+    format_jd_iso(jd + 30.0 / 86400.0)[11..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Angle;
+    use crate::observer::Visibility;
+    use crate::time::civil_to_jd;
+
+    fn target(name: &str, ra: f64, dec: f64) -> Target {
+        Target { name: name.to_string(), coord: EquatorialCoord::new(Angle::from_degrees(ra), Angle::from_degrees(dec)) }
+    }
+
+    #[test]
+    fn winter_night_at_52_north() {
+        let site: Site = Site { lat: Angle::from_degrees(52.0), lon: Angle::from_degrees(0.0), elevation: 0.0 };
+        let night: Night = night(site, civil_to_jd(2024, 1, 15.0));
+        let (dusk, dawn): (f64, f64) = night.dark.unwrap();
+        // astronomical darkness from about 18:00 to 06:10 UTC in mid January
+        assert!((dawn - dusk) * 24.0 > 11.5 && (dawn - dusk) * 24.0 < 12.5, "got {} h", (dawn - dusk) * 24.0);
+        assert!(night.start < dusk && dawn < night.end && night.times.len() == CHART_COLUMNS);
+
+        // near the pole: up all night at about the latitude
+        let polar: TargetPlan = plan_target(&target("pole", 0.0, 89.0), site, &night, 30f64.to_radians());
+        assert_eq!(polar.rise_set.visibility, Visibility::Circumpolar);
+        assert!((polar.dark_hours - (dawn - dusk) * 24.0).abs() < 0.05);
+        assert!((polar.best_alt.unwrap().to_degrees() - 53.0).abs() < 0.7);
+        assert!(polar.min_airmass.unwrap() > 1.2 && polar.min_airmass.unwrap() < 1.3);
+
+        // never above the horizon
+        let south: TargetPlan = plan_target(&target("south", 0.0, -60.0), site, &night, 30f64.to_radians());
+        assert_eq!(south.rise_set.visibility, Visibility::NeverRises);
+        assert_eq!(south.dark_hours, 0.0);
+        assert!(south.min_airmass.is_none());
+
+        // the Orion nebula transits near midnight in January, peaking at 90° - 52° - 5.4°
+        let orion: TargetPlan = plan_target(&target("M42", 83.82, -5.39), site, &night, 30f64.to_radians());
+        assert!(orion.dark_hours > 2.0 && orion.dark_hours < 6.0, "got {} h", orion.dark_hours);
+        assert!((orion.best_alt.unwrap().to_degrees() - 32.6).abs() < 0.5);
+    }
+
+    #[test]
+    fn chart_layout() {
+        let site: Site = Site { lat: Angle::from_degrees(52.0), lon: Angle::from_degrees(0.0), elevation: 0.0 };
+        let night: Night = night(site, civil_to_jd(2024, 1, 15.0));
+        let polar: TargetPlan = plan_target(&target("pole", 0.0, 89.0), site, &night, 30f64.to_radians());
+        let lines: Vec<String> = altitude_chart(&night, &[&polar.altitudes], 30f64.to_radians());
+        assert_eq!(lines.len(), 11);
+        // the pole star sits in the 50-60° row all night, the minimum altitude marks the 30° row
+        assert_eq!(lines[3], format!(" 50°┃{}", "A".repeat(CHART_COLUMNS)));
+        assert!(lines[5].starts_with(" 30°┃╌╌"));
+        assert!(lines[9].starts_with("    ┗━"));
+        assert!(lines[10].contains("00"));
+        assert_eq!(chart_symbol(0), 'A');
+        assert_eq!(chart_symbol(100), '+');
+    }
+}
@@ -0,0 +1,262 @@
+// the major planets from Keplerian mean elements (Standish, "Keplerian Elements for Approximate
+// Positions of the Major Planets", JPL, valid 1800-2050 to a few arcminutes)
+
+use crate::angle::Angle;
+use crate::coords::{from_vector, mat_vec, rot1, EquatorialCoord};
+use crate::orbit::solve_kepler;
+use crate::precession::{centuries_since_j2000, mean_obliquity};
+use crate::time::{civil_to_jd, utc_to_tt};
+use crate::trace::{Derived, Trace};
+use crate::PI;
+
+// light travel time for 1 AU in days
+pub const LIGHT_TIME_DAYS_PER_AU: f64 = 0.0057755183;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Planet {
+    Mercury,
+    Venus,
+    Mars,
+    Jupiter,
+    Saturn,
+    Uranus,
+    Neptune,
+}
+
+pub const PLANETS: [Planet; 7] = [Planet::Mercury, Planet::Venus, Planet::Mars, Planet::Jupiter, Planet::Saturn, Planet::Uranus, Planet::Neptune];
+
+// a, e, I, L, ϖ, Ω at J2000 and their rates per Julian century; AU and degrees
+type Elements = [(f64, f64); 6];
+
+const EARTH_MOON_BARYCENTRE: Elements = [
+    (1.00000261, 0.00000562),
+    (0.01671123, -0.00004392),
+    (-0.00001531, -0.01294668),
+    (100.46457166, 35999.37244981),
+    (102.93768193, 0.32327364),
+    (0.0, 0.0),
+];
+
+impl Planet {
+    pub fn parse(name: &str) -> Option<Planet> {
+        PLANETS.into_iter().find(|p: &Planet| p.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Planet::Mercury => "Mercury",
+            Planet::Venus => "Venus",
+            Planet::Mars => "Mars",
+            Planet::Jupiter => "Jupiter",
+            Planet::Saturn => "Saturn",
+            Planet::Uranus => "Uranus",
+            Planet::Neptune => "Neptune",
+        }
+    }
+
+    fn elements(&self) -> Elements {
+        match self {
+            Planet::Mercury => [(0.38709927, 0.00000037), (0.20563593, 0.00001906), (7.00497902, -0.00594749), (252.25032350, 149472.67411175), (77.45779628, 0.16047689), (48.33076593, -0.12534081)],
+            Planet::Venus => [(0.72333566, 0.00000390), (0.00677672, -0.00004107), (3.39467605, -0.00078890), (181.97909950, 58517.81538729), (131.60246718, 0.00268329), (76.67984255, -0.27769418)],
+            Planet::Mars => [(1.52371034, 0.00001847), (0.09339410, 0.00007882), (1.84969142, -0.00813131), (-4.55343205, 19140.30268499), (-23.94362959, 0.44441088), (49.55953891, -0.29257343)],
+            Planet::Jupiter => [(5.20288700, -0.00011607), (0.04838624, -0.00013253), (1.30439695, -0.00183714), (34.39644051, 3034.74612775), (14.72847983, 0.21252668), (100.47390909, 0.20469106)],
+            Planet::Saturn => [(9.53667594, -0.00125060), (0.05386179, -0.00050991), (2.48599187, 0.00193609), (49.95424423, 1222.49362201), (92.59887831, -0.41897216), (113.66242448, -0.28867794)],
+            Planet::Uranus => [(19.18916464, -0.00196176), (0.04725744, -0.00004397), (0.77263783, -0.00242939), (313.23810451, 428.48202785), (170.95427630, 0.40805281), (74.01692503, 0.04240589)],
+            Planet::Neptune => [(30.06992276, 0.00026291), (0.00859048, 0.00005105), (1.77004347, 0.00035372), (-55.12002969, 218.45945325), (44.96476227, -0.32241464), (131.78422574, -0.00508664)],
+        }
+    }
+
+    // visual magnitude from the distances in AU and the phase angle in degrees (Astronomical
+    // Almanac, as in Meeus ch. 41). Saturn's ring term uses the ring tilt only
+    pub fn magnitude(&self, r: f64, delta: f64, phase_deg: f64, ring_tilt: f64) -> f64 {
+        let i: f64 = phase_deg;
+        let distance: f64 = 5.0 * (r * delta).log10();
+        distance
+            + match self {
+                Planet::Mercury => -0.42 + 0.0380 * i - 0.000273 * i * i + 0.000002 * i * i * i,
+                Planet::Venus => -4.40 + 0.0009 * i + 0.000239 * i * i - 0.00000065 * i * i * i,
+                Planet::Mars => -1.52 + 0.016 * i,
+                Planet::Jupiter => -9.40 + 0.005 * i,
+                Planet::Saturn => -8.88 - 2.60 * ring_tilt.abs().sin() + 1.25 * ring_tilt.sin().powi(2),
+                Planet::Uranus => -7.19,
+                Planet::Neptune => -6.87,
+            }
+    }
+}
+
+// heliocentric position in AU on the J2000 ecliptic and equinox
+fn heliocentric(elements: &Elements, t: f64) -> [f64; 3] {
+    let [a, e, incl, mean_lon, peri, node]: [f64; 6] = elements.map(|(value, rate): (f64, f64)| value + rate * t);
+    let (incl, node): (f64, f64) = (incl.to_radians(), node.to_radians());
+    let omega: f64 = (peri - node.to_degrees()).to_radians();
+    let ecc: f64 = solve_kepler((mean_lon - peri).to_radians(), e);
+    // in the orbital plane, x towards perihelion
+    let x: f64 = a * (ecc.cos() - e);
+    let y: f64 = a * (1.0 - e * e).sqrt() * ecc.sin();
+    let (so, co): (f64, f64) = omega.sin_cos();
+    let (sn, cn): (f64, f64) = node.sin_cos();
+    let (si, ci): (f64, f64) = incl.sin_cos();
+    [
+        (co * cn - so * sn * ci) * x + (-so * cn - co * sn * ci) * y,
+        (co * sn + so * cn * ci) * x + (-so * sn + co * cn * ci) * y,
+        so * si * x + co * si * y,
+    ]
+}
+
+fn length(v: [f64; 3]) -> f64 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+// the Earth-Moon barycentre, AU on the J2000 ecliptic, t in centuries since J2000 (TT)
+pub fn earth_position(t: f64) -> [f64; 3] {
+    heliocentric(&EARTH_MOON_BARYCENTRE, t)
+}
+
+// elongation (radians, east of the Sun if positive) and phase angle of a body at heliocentric
+// position `helio` seen from the Earth at `earth`, both on the J2000 ecliptic
+pub fn elongation_and_phase(helio: [f64; 3], earth: [f64; 3]) -> (f64, f64) {
+    let geo: [f64; 3] = [0, 1, 2].map(|i: usize| helio[i] - earth[i]);
+    let (r, delta, big_r): (f64, f64, f64) = (length(helio), length(geo), length(earth));
+    let psi: f64 = ((big_r * big_r + delta * delta - r * r) / (2.0 * big_r * delta)).clamp(-1.0, 1.0).acos();
+    let phase_angle: f64 = ((r * r + delta * delta - big_r * big_r) / (2.0 * r * delta)).clamp(-1.0, 1.0).acos();
+    // east of the Sun when the body's longitude is ahead of the Sun's
+    let sun_lon: f64 = (-earth[1]).atan2(-earth[0]);
+    let east: bool = (geo[1].atan2(geo[0]) - sun_lon).rem_euclid(2.0 * PI) < PI;
+    (if east { psi } else { -psi }, phase_angle)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlanetPosition {
+    // heliocentric ecliptic longitude and latitude (J2000, radians) and distance from the Sun, AU
+    pub helio_lon: f64,
+    pub helio_lat: f64,
+    pub r: f64,
+    // geocentric ecliptic longitude/latitude and astrometric Ra/Dec (J2000, light-time corrected)
+    pub geo_lon: f64,
+    pub geo_lat: f64,
+    pub coord: EquatorialCoord,
+    pub delta: f64,
+    // Sun-Earth-planet and Sun-planet-Earth angles in radians, elongation east of the Sun if positive
+    pub elongation: f64,
+    pub phase_angle: f64,
+    pub illuminated: f64,
+    pub magnitude: f64,
+}
+
+// the tilt of Saturn's rings seen from the Earth, from the ring plane's inclination and node on
+// the J2000 ecliptic (Meeus ch. 45, whose node rate is almost all precession)
+fn saturn_ring_tilt(geo_lon: f64, geo_lat: f64) -> f64 {
+    let i: f64 = 28.075216f64.to_radians();
+    let node: f64 = 169.508470f64.to_radians();
+    (i.sin() * geo_lat.cos() * (geo_lon - node).sin() - i.cos() * geo_lat.sin()).asin()
+}
+
+// whether a UTC time is inside 1800-2050, the span the mean elements were fitted over
+pub fn elements_valid(jd_utc: f64) -> bool {
+    (civil_to_jd(1800, 1, 1.0)..civil_to_jd(2051, 1, 1.0)).contains(&jd_utc)
+}
+
+pub fn planet(body: Planet, jd_utc: f64, show_work: bool) -> Derived<PlanetPosition> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+
+    let jd_tt: f64 = utc_to_tt(jd_utc);
+    let t: f64 = centuries_since_j2000(jd_tt);
+    note!(trace, "Mean elements at t = {} centuries since J2000 (JPL, J2000 ecliptic)", t);
+    note!(trace, "a, e, I, L, ϖ, Ω each their J2000 value plus rate × t; M = L - ϖ, ω = ϖ - Ω");
+    let [a, e, incl, mean_lon, peri, node]: [f64; 6] = body.elements().map(|(value, rate): (f64, f64)| value + rate * t);
+    value!(trace, ["a, e, I"], ["{} AU, {}, {}°", a, e, incl]);
+    value!(trace, ["L, ϖ, Ω"], ["{}°, {}°, {}°", mean_lon.rem_euclid(360.0), peri.rem_euclid(360.0), node.rem_euclid(360.0)]);
+    note!(trace, "Solve Kepler's equation M = E - e sin(E), then x' = a(cos(E) - e), y' = a√(1 - e²) sin(E)");
+    value!(trace, ["E"], ["{}°", solve_kepler((mean_lon - peri).to_radians(), e).to_degrees()]);
+    note!(trace, "Rotate by ω, I and Ω onto the ecliptic");
+
+    let earth: [f64; 3] = earth_position(t);
+    // where the planet was when the light left it, iterated from the geometric distance
+    let mut light_time: f64 = 0.0;
+    let mut helio: [f64; 3] = heliocentric(&body.elements(), t);
+    let mut geo: [f64; 3] = [0, 1, 2].map(|i: usize| helio[i] - earth[i]);
+    for _ in 0..3 {
+        light_time = length(geo) * LIGHT_TIME_DAYS_PER_AU;
+        helio = heliocentric(&body.elements(), t - light_time / 36525.0);
+        geo = [0, 1, 2].map(|i: usize| helio[i] - earth[i]);
+    }
+    let r: f64 = length(helio);
+    let delta: f64 = length(geo);
+    let (helio_lon, helio_lat): (f64, f64) = from_vector(helio);
+    value!(trace, ["heliocentric λ, β, r"], ["{}°, {}°, {} AU", helio_lon.to_degrees(), helio_lat.to_degrees(), r]);
+    value!(trace, ["Earth (Earth-Moon barycentre) X, Y, Z"], ["{}, {}, {} AU", earth[0], earth[1], earth[2]]);
+    note!(trace, "Geocentric vector, planet - Earth, with the planet moved back by the light time τ = Δ / c");
+    value!(trace, ["τ"], ["{} days", light_time]);
+    value!(trace, ["Δ"], ["{} AU", delta]);
+
+    let (geo_lon, geo_lat): (f64, f64) = from_vector(geo);
+    let (ra, dec): (f64, f64) = from_vector(mat_vec(&rot1(-mean_obliquity(0.0)), geo));
+    let coord: EquatorialCoord = EquatorialCoord::new(Angle::from_radians(ra), Angle::from_radians(dec));
+    value!(trace, ["geocentric λ, β"], ["{}°, {}°", geo_lon.to_degrees(), geo_lat.to_degrees()]);
+    note!(trace, "Rotate by ε₀ = 23.439279° onto the J2000 equator");
+    value!(trace, ["α, δ"], ["{}°, {}°", coord.ra.degrees(), coord.dec.degrees()]);
+
+    note!(trace, "Elongation ψ and phase angle i from the triangle Sun-Earth-planet");
+    note!(trace, "cos(ψ) = (R² + Δ² - r²) / 2RΔ, cos(i) = (r² + Δ² - R²) / 2rΔ, k = (1 + cos(i)) / 2");
+    let (elongation, phase_angle): (f64, f64) = elongation_and_phase(helio, earth);
+    let (psi, east): (f64, bool) = (elongation.abs(), elongation > 0.0);
+    value!(trace, ["ψ"], ["{}° {}", psi.to_degrees(), if east { "east" } else { "west" }]);
+    value!(trace, ["i"], ["{}°", phase_angle.to_degrees()]);
+    let illuminated: f64 = (1.0 + phase_angle.cos()) / 2.0;
+    value!(trace, ["k"], ["{}", illuminated]);
+
+    let ring_tilt: f64 = if body == Planet::Saturn { saturn_ring_tilt(geo_lon, geo_lat) } else { 0.0 };
+    note!(trace, "Magnitude (Astronomical Algorithms ch. 41), V = V₀ + 5 log₁₀(rΔ) + phase terms");
+    if body == Planet::Saturn {
+        note!(trace, "Saturn's rings: sin(B) = sin(i)cos(β)sin(λ - Ω) - cos(i)sin(β), V₀ = -8.88 - 2.60 sin|B| + 1.25 sin²(B)");
+        value!(trace, ["B"], ["{}°", ring_tilt.to_degrees()]);
+    }
+    let magnitude: f64 = body.magnitude(r, delta, phase_angle.to_degrees(), ring_tilt);
+    value!(trace, ["V"], ["{}", magnitude]);
+
+    let position: PlanetPosition = PlanetPosition {
+        helio_lon: helio_lon.rem_euclid(2.0 * PI),
+        helio_lat,
+        r,
+        geo_lon: geo_lon.rem_euclid(2.0 * PI),
+        geo_lat,
+        coord,
+        delta,
+        elongation,
+        phase_angle,
+        illuminated,
+        magnitude,
+    };
+    Derived { value: position, trace }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn venus_meeus() {
+        // Meeus example 33.a, 1992 December 20.0 TD: heliocentric L = 26.11428° of date
+        // (26.2125° on the J2000 equinox), r = 0.724603 AU; Δ = 0.910947 AU
+        let jd_utc: f64 = 2448976.5 - 59.0 / 86400.0;
+        let venus: PlanetPosition = planet(Planet::Venus, jd_utc, false).value;
+        assert!((venus.helio_lon.to_degrees() - 26.2125).abs() < 0.03, "got {}", venus.helio_lon.to_degrees());
+        assert!((venus.r - 0.724603).abs() < 1e-4, "got {}", venus.r);
+        assert!((venus.delta - 0.910947).abs() < 1e-3, "got {}", venus.delta);
+        assert!(venus.elongation > 0.0);
+    }
+
+    #[test]
+    fn jupiter_opposition_2023() {
+        // opposition on 2023 November 3 at magnitude -2.9
+        let jupiter: Derived<PlanetPosition> = planet(Planet::Jupiter, civil_to_jd(2023, 11, 3.2), true);
+        assert!(jupiter.value.elongation.abs().to_degrees() > 178.0, "got {}", jupiter.value.elongation.to_degrees());
+        assert!((jupiter.value.magnitude + 2.9).abs() < 0.1, "got {}", jupiter.value.magnitude);
+        assert!(jupiter.value.illuminated > 0.999);
+        assert!(jupiter.trace.is_some());
+        assert_eq!(Planet::parse(" jupiter"), Some(Planet::Jupiter));
+        assert_eq!(Planet::parse("pluto"), None);
+        assert!(elements_valid(civil_to_jd(1800, 1, 1.0)) && elements_valid(civil_to_jd(2050, 12, 31.9)));
+        assert!(!elements_valid(civil_to_jd(1799, 12, 31.9)) && !elements_valid(civil_to_jd(2051, 1, 1.0)));
+    }
+}
@@ -0,0 +1,339 @@
+// IAU 2006 precession and a short nutation series
+
+use crate::angle::Angle;
+use crate::coords::{from_vector, mat_mul, mat_vec, rot1, rot2, rot3, to_vector, transpose, EquatorialCoord};
+use crate::time::jd_now;
+use crate::trace::{Derived, Trace};
+use crate::PI;
+
+pub const ARCSEC_TO_RAD: f64 = PI / (180.0 * 3600.0);
+pub const J2000_JD: f64 = 2451545.0;
+
+// J2000, J2025.5, B1950, 2024.3 (read as Julian) or now -> Julian date (TT)
+pub fn parse_epoch(input: &str) -> Result<f64, String> {
+    let trimmed: &str = input.trim();
+    if trimmed.eq_ignore_ascii_case("now") {
+        return jd_now();
+    }
+    let invalid = || format!("invalid epoch: {}, use e.g. J2000, B1950 or now", input);
+    if let Some(year) = trimmed.strip_prefix(['B', 'b']) {
+        let year: f64 = year.parse().map_err(|_| invalid())?;
+        Ok(besselian_jd(year))
+    } else {
+        let year: &str = trimmed.strip_prefix(['J', 'j']).unwrap_or(trimmed);
+        let year: f64 = year.parse().map_err(|_| invalid())?;
+        Ok(J2000_JD + (year - 2000.0) * 365.25)
+    }
+}
+
+// Besselian year of a B epoch, None for Julian ones
+pub fn besselian_year(input: &str) -> Option<f64> {
+    input.trim().strip_prefix(['B', 'b'])?.parse().ok()
+}
+
+pub fn besselian_jd(year: f64) -> f64 {
+    2415020.31352 + (year - 1900.0) * 365.242198781
+}
+
+// Julian centuries of TT since J2000
+pub fn centuries_since_j2000(jd: f64) -> f64 {
+    (jd - J2000_JD) / 36525.0
+}
+
+// IAU 2006 (P03) equatorial precession angles ζ, z, θ in radians
+pub fn precession_angles(t: f64) -> (f64, f64, f64) {
+    let zeta: f64 = 2.650545
+        + t * (2306.083227 + t * (0.2988499 + t * (0.01801828 + t * (-0.000005971 + t * -0.0000003173))));
+    let z: f64 = -2.650545
+        + t * (2306.077181 + t * (1.0927348 + t * (0.01826837 + t * (-0.000028596 + t * -0.0000002904))));
+    let theta: f64 =
+        t * (2004.191903 + t * (-0.4294934 + t * (-0.04182264 + t * (-0.000007089 + t * -0.0000001274))));
+    (zeta * ARCSEC_TO_RAD, z * ARCSEC_TO_RAD, theta * ARCSEC_TO_RAD)
+}
+
+// J2000 mean equator and equinox -> mean equator and equinox of date
+pub fn precession_matrix(t: f64) -> [[f64; 3]; 3] {
+    let (zeta, z, theta): (f64, f64, f64) = precession_angles(t);
+    mat_mul(&rot3(-z), &mat_mul(&rot2(theta), &rot3(-zeta)))
+}
+
+// IAU 2006 mean obliquity of the ecliptic in radians
+pub fn mean_obliquity(t: f64) -> f64 {
+    let eps: f64 = 84381.406
+        + t * (-46.836769 + t * (-0.0001831 + t * (0.00200340 + t * (-0.000000576 + t * -0.0000000434))));
+    eps * ARCSEC_TO_RAD
+}
+
+// nutation in longitude and obliquity (Δψ, Δε) in radians from the four largest terms
+// of the IAU 1980 series, good to about 0.5″
+pub fn nutation(t: f64) -> (f64, f64) {
+    let omega: f64 = (125.04452 - 1934.136261 * t).to_radians();
+    let sun: f64 = (280.4665 + 36000.7698 * t).to_radians();
+    let moon: f64 = (218.3165 + 481267.8813 * t).to_radians();
+    let dpsi: f64 = -17.20 * omega.sin() - 1.32 * (2.0 * sun).sin() - 0.23 * (2.0 * moon).sin()
+        + 0.21 * (2.0 * omega).sin();
+    let deps: f64 = 9.20 * omega.cos() + 0.57 * (2.0 * sun).cos() + 0.10 * (2.0 * moon).cos()
+        - 0.09 * (2.0 * omega).cos();
+    (dpsi * ARCSEC_TO_RAD, deps * ARCSEC_TO_RAD)
+}
+
+// mean equator and equinox of date -> true equator and equinox of date
+pub fn nutation_matrix(t: f64) -> [[f64; 3]; 3] {
+    let eps: f64 = mean_obliquity(t);
+    let (dpsi, deps): (f64, f64) = nutation(t);
+    mat_mul(&rot1(-(eps + deps)), &mat_mul(&rot3(-dpsi), &rot1(eps)))
+}
+
+// mean place at jd_from -> mean place at jd_to, or true place when nutate is set.
+// Ra/Dec in radians
+pub fn precess(jd_from: f64, jd_to: f64, nutate: bool, ra: f64, dec: f64) -> (f64, f64) {
+    let m: [[f64; 3]; 3] = epoch_matrix(jd_from, jd_to, nutate);
+    from_vector(mat_vec(&m, to_vector(ra, dec)))
+}
+
+pub fn epoch_matrix(jd_from: f64, jd_to: f64, nutate: bool) -> [[f64; 3]; 3] {
+    let t_to: f64 = centuries_since_j2000(jd_to);
+    let back_to_j2000: [[f64; 3]; 3] = transpose(&precession_matrix(centuries_since_j2000(jd_from)));
+    let m: [[f64; 3]; 3] = mat_mul(&precession_matrix(t_to), &back_to_j2000);
+    if nutate {
+        mat_mul(&nutation_matrix(t_to), &m)
+    } else {
+        m
+    }
+}
+
+// mean place at jd_from (TT) -> mean place at jd_to, or true place when nutate is set
+pub fn precess_coord(jd_from: f64, jd_to: f64, nutate: bool, coord: &EquatorialCoord, show_work: bool) -> Derived<EquatorialCoord> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+
+    note!(trace, "Epochs as Julian dates (TT) and centuries since J2000");
+    note!(trace, "t = (JD - 2451545.0) / 36525");
+    let t_from: f64 = centuries_since_j2000(jd_from);
+    let t_to: f64 = centuries_since_j2000(jd_to);
+    value!(trace, ["t₁ (JD {})", jd_from], ["{}", t_from]);
+    value!(trace, ["t₂ (JD {})", jd_to], ["{}", t_to]);
+
+    note!(trace, "IAU 2006 precession angles (arcseconds)");
+    note!(trace, "ζ = 2.650545 + 2306.083227t + 0.2988499t² + 0.01801828t³ - 0.000005971t⁴ - 0.0000003173t⁵");
+    note!(trace, "z = -2.650545 + 2306.077181t + 1.0927348t² + 0.01826837t³ - 0.000028596t⁴ - 0.0000002904t⁵");
+    note!(trace, "θ = 2004.191903t - 0.4294934t² - 0.04182264t³ - 0.000007089t⁴ - 0.0000001274t⁵");
+    for (label, t) in [("t₁", t_from), ("t₂", t_to)] {
+        let (zeta, z, theta): (f64, f64, f64) = precession_angles(t);
+        value!(trace, ["{}: ζ, z, θ", label], ["{}″, {}″, {}″", zeta / ARCSEC_TO_RAD, z / ARCSEC_TO_RAD, theta / ARCSEC_TO_RAD]);
+    }
+    note!(trace, "P(t) = R₃(-z) R₂(θ) R₃(-ζ) takes J2000 to the mean equinox of t");
+    if nutate {
+        let (dpsi, deps): (f64, f64) = nutation(t_to);
+        note!(trace, "Nutation at t₂");
+        note!(trace, "Δψ = -17.20″sin(Ω) - 1.32″sin(2L☉) - 0.23″sin(2L☾) + 0.21″sin(2Ω)");
+        note!(trace, "Δε = 9.20″cos(Ω) + 0.57″cos(2L☉) + 0.10″cos(2L☾) - 0.09″cos(2Ω)");
+        value!(trace, ["Δψ"], ["{}″", dpsi / ARCSEC_TO_RAD]);
+        value!(trace, ["Δε"], ["{}″", deps / ARCSEC_TO_RAD]);
+        value!(trace, ["ε₀"], ["{}″", mean_obliquity(t_to) / ARCSEC_TO_RAD]);
+        note!(trace, "N = R₁(-(ε₀ + Δε)) R₃(-Δψ) R₁(ε₀)");
+        note!(trace, "M = N(t₂) P(t₂) P(t₁)ᵀ");
+    } else {
+        note!(trace, "M = P(t₂) P(t₁)ᵀ");
+    }
+    let m: [[f64; 3]; 3] = epoch_matrix(jd_from, jd_to, nutate);
+    matrix!(trace, m);
+    let v: [f64; 3] = coord.to_vector();
+    let w: [f64; 3] = mat_vec(&m, v);
+    note!(trace, "Rotate the unit vector");
+    value!(trace, ["v"], ["({}, {}, {})", v[0], v[1], v[2]]);
+    value!(trace, ["v' = M v"], ["({}, {}, {})", w[0], w[1], w[2]]);
+
+    let out: EquatorialCoord = EquatorialCoord::from_vector(w);
+    note!(trace, "α = atan2(v'y, v'x), δ = atan2(v'z, √(v'x² + v'y²))");
+    value!(trace, ["α"], ["{}°", out.ra.degrees()]);
+    value!(trace, ["δ"], ["{}°", out.dec.degrees()]);
+
+    Derived { value: out, trace }
+}
+
+// B epochs are FK4 places. FK4 -> FK5 follows Standish (1982) as in SLALIB's FK45Z: the E-terms
+// of aberration come out, then one matrix corrects the equinox and precession constant and
+// precesses B1950 to J2000. places with no proper motion are taken to have none in FK5
+
+// E-terms, and their change per tropical century
+const E_TERMS: [f64; 3] = [-1.62557e-6, -0.31919e-6, -0.13843e-6];
+const E_TERMS_RATE: [f64; 3] = [1.245e-3, -1.580e-3, -0.659e-3];
+// radians per year to arcseconds per century
+const PMF: f64 = 100.0 * 60.0 * 60.0 * 360.0 / (2.0 * PI);
+// a B1950 FK4 unit vector to the J2000 FK5 position (first three rows) and the fictitious FK4
+// proper motion, arcseconds per century (last three)
+const FK4_TO_FK5: [[f64; 3]; 6] = [
+    [0.9999256782, -0.0111820611, -0.0048579477],
+    [0.0111820610, 0.9999374784, -0.0000271765],
+    [0.0048579479, -0.0000271474, 0.9999881997],
+    [-0.000551, -0.238565, 0.435739],
+    [0.238514, -0.002667, -0.008541],
+    [-0.435623, 0.012254, 0.002117],
+];
+
+// B1950 FK4 mean place (radians) observed at Besselian year `epoch` -> J2000 FK5
+pub fn fk4_to_fk5(ra: f64, dec: f64, epoch: f64) -> (f64, f64) {
+    let r: [f64; 3] = to_vector(ra, dec);
+    let w: f64 = (epoch - 1950.0) / PMF;
+    let a: [f64; 3] = std::array::from_fn(|i: usize| E_TERMS[i] + w * E_TERMS_RATE[i]);
+    let ra_dot: f64 = (0..3).map(|i: usize| r[i] * a[i]).sum();
+    let v: [f64; 3] = std::array::from_fn(|i: usize| r[i] - a[i] + ra_dot * r[i]);
+    let fk5: [f64; 6] = std::array::from_fn(|i: usize| (0..3).map(|j: usize| FK4_TO_FK5[i][j] * v[j]).sum());
+    // no proper motion in FK5 means undoing FK4's fictitious one from the epoch to J2000
+    let julian: f64 = 2000.0 + (besselian_jd(epoch) - J2000_JD) / 365.25;
+    let w: f64 = (julian - 2000.0) / PMF;
+    from_vector(std::array::from_fn(|i: usize| fk5[i] + w * fk5[i + 3]))
+}
+
+// J2000 FK5 -> B1950 FK4 at Besselian year `epoch`, inverting fk4_to_fk5 by iteration
+pub fn fk5_to_fk4(ra: f64, dec: f64, epoch: f64) -> (f64, f64) {
+    let target: [f64; 3] = to_vector(ra, dec);
+    let back: [[f64; 3]; 3] = transpose(&[FK4_TO_FK5[0], FK4_TO_FK5[1], FK4_TO_FK5[2]]);
+    let mut v: [f64; 3] = mat_vec(&back, target);
+    for _ in 0..5 {
+        let (r, d): (f64, f64) = from_vector(v);
+        let (r5, d5): (f64, f64) = fk4_to_fk5(r, d, epoch);
+        let there: [f64; 3] = to_vector(r5, d5);
+        let step: [f64; 3] = mat_vec(&back, std::array::from_fn(|i: usize| target[i] - there[i]));
+        v = std::array::from_fn(|i: usize| v[i] + step[i]);
+    }
+    from_vector(v)
+}
+
+// Newcomb's precession within FK4 between Besselian years (Kinoshita 1975, SLALIB's PREBN)
+pub fn fk4_precession_matrix(from: f64, to: f64) -> [[f64; 3]; 3] {
+    let big_t: f64 = (from - 1850.0) / 100.0;
+    let t: f64 = (to - from) / 100.0;
+    let w: f64 = 2303.5548 + (1.39720 + 0.000059 * big_t) * big_t;
+    let zeta: f64 = (w + (0.30242 - 0.000269 * big_t + 0.017996 * t) * t) * t;
+    let z: f64 = (w + (1.09478 + 0.000387 * big_t + 0.018324 * t) * t) * t;
+    let theta: f64 = (2005.1125 + (-0.85294 - 0.000365 * big_t) * big_t + (-0.42647 - 0.000365 * big_t - 0.041802 * t) * t) * t;
+    mat_mul(&rot3(-z * ARCSEC_TO_RAD), &mat_mul(&rot2(theta * ARCSEC_TO_RAD), &rot3(-zeta * ARCSEC_TO_RAD)))
+}
+
+// between epochs as typed: a B epoch in is an FK4 place, taken to J2000 FK5 (through B1950)
+// before precessing, and a B epoch out is taken back to FK4 the same way
+pub fn change_epoch(from: &str, to: &str, nutate: bool, coord: &EquatorialCoord, show_work: bool) -> Result<Derived<EquatorialCoord>, String> {
+    let mut jd_from: f64 = parse_epoch(from)?;
+    let mut jd_to: f64 = parse_epoch(to)?;
+    let (b_from, b_to): (Option<f64>, Option<f64>) = (besselian_year(from), besselian_year(to));
+    if nutate && b_to.is_some() {
+        return Err(format!("--nutation gives a true place of date, {} is an FK4 mean equinox", to.trim()));
+    }
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+    let mut place: EquatorialCoord = *coord;
+
+    if let Some(year) = b_from {
+        if year != 1950.0 {
+            note!(trace, "Newcomb precession within FK4, B{} to B1950", year);
+            place = EquatorialCoord::from_vector(mat_vec(&fk4_precession_matrix(year, 1950.0), place.to_vector()));
+            value!(trace, ["α, δ (FK4 B1950)"], ["{}°, {}°", place.ra.degrees(), place.dec.degrees()]);
+        }
+        note!(trace, "FK4 B1950 to FK5 J2000 (Standish 1982): remove the E-terms of aberration, rotate, and take the proper motion in FK5 as zero");
+        let (ra, dec): (f64, f64) = fk4_to_fk5(place.ra.radians(), place.dec.radians(), year);
+        place = EquatorialCoord::new(Angle::from_radians(ra), Angle::from_radians(dec));
+        value!(trace, ["α, δ (FK5 J2000)"], ["{}°, {}°", place.ra.degrees(), place.dec.degrees()]);
+        jd_from = J2000_JD;
+    }
+    if b_to.is_some() {
+        jd_to = J2000_JD;
+    }
+    let precessed: Derived<EquatorialCoord> = precess_coord(jd_from, jd_to, nutate, &place, show_work);
+    if let (Some(trace), Some(steps)) = (trace.as_mut(), precessed.trace) {
+        trace.steps.extend(steps.steps);
+    }
+    place = precessed.value;
+    if let Some(year) = b_to {
+        note!(trace, "FK5 J2000 to FK4 B1950, the inverse of Standish (1982), by iteration");
+        let (ra, dec): (f64, f64) = fk5_to_fk4(place.ra.radians(), place.dec.radians(), year);
+        place = EquatorialCoord::new(Angle::from_radians(ra), Angle::from_radians(dec));
+        value!(trace, ["α, δ (FK4 B1950)"], ["{}°, {}°", place.ra.degrees(), place.dec.degrees()]);
+        if year != 1950.0 {
+            note!(trace, "Newcomb precession within FK4, B1950 to B{}", year);
+            place = EquatorialCoord::from_vector(mat_vec(&fk4_precession_matrix(1950.0, year), place.to_vector()));
+            value!(trace, ["α, δ (FK4 B{})", year], ["{}°, {}°", place.ra.degrees(), place.dec.degrees()]);
+        }
+    }
+    Ok(Derived { value: place, trace })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::angular_separation;
+
+    #[test]
+    fn precession_meeus_theta_persei() {
+        // Meeus, Astronomical Algorithms, example 21.b with the proper motion already applied
+        let jd_to: f64 = 2462088.69;
+        let (ra, dec) = precess(J2000_JD, jd_to, false, 41.054063f64.to_radians(), 49.227750f64.to_radians());
+        assert!((ra.to_degrees() - 41.547214).abs() < 1e-4, "got {}", ra.to_degrees());
+        assert!((dec.to_degrees() - 49.348483).abs() < 1e-4, "got {}", dec.to_degrees());
+
+        let (ra2, dec2) = precess(jd_to, J2000_JD, false, ra, dec);
+        assert!(angular_separation(ra2, dec2, 41.054063f64.to_radians(), 49.227750f64.to_radians()) < 1e-12);
+    }
+
+    #[test]
+    fn epoch_parsing() {
+        assert_eq!(parse_epoch("J2000").unwrap(), J2000_JD);
+        assert_eq!(parse_epoch("2050").unwrap(), J2000_JD + 50.0 * 365.25);
+        assert!((parse_epoch("B1950").unwrap() - 2433282.4235).abs() < 1e-3);
+        assert!(parse_epoch("X1950").is_err());
+    }
+
+    #[test]
+    fn fk4_fk5_transformation() {
+        // the SOFA test of iauFk45z, which is the same transformation
+        let (r, d): (f64, f64) = fk4_to_fk5(0.01602284975382961, -0.1164347929099906, 1954.6776176252567);
+        assert!((r - 0.027192959116068623).abs() < 1e-14 && (d + 0.1115766001565927).abs() < 1e-12, "got {} {}", r, d);
+        let (rb, db): (f64, f64) = fk5_to_fk4(r, d, 1954.6776176252567);
+        assert!(angular_separation(rb, db, 0.01602284975382961, -0.1164347929099906) < 1e-6 * ARCSEC_TO_RAD);
+
+        // the B1950 galactic pole comes out at the J2000 192.85948°, +27.12825° to 0.2″, where
+        // precession alone is 0.6″ off
+        let pole: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(192.25), Angle::from_degrees(27.4));
+        let out: EquatorialCoord = change_epoch("B1950", "J2000", false, &pole, false).unwrap().value;
+        assert!(angular_separation(out.ra.radians(), out.dec.radians(), 192.85948f64.to_radians(), 27.12825f64.to_radians()) < 0.2 * ARCSEC_TO_RAD);
+        let back: EquatorialCoord = change_epoch("J2000", "B1950", false, &out, false).unwrap().value;
+        assert!(angular_separation(back.ra.radians(), back.dec.radians(), pole.ra.radians(), pole.dec.radians()) < 1e-6 * ARCSEC_TO_RAD);
+        assert!(change_epoch("J2000", "B1950", true, &out, false).is_err());
+        // other B equinoxes are precessed to B1950 within FK4 first
+        let b1900: EquatorialCoord = change_epoch("J2000", "B1900", false, &out, false).unwrap().value;
+        let again: EquatorialCoord = change_epoch("B1900", "J2000", false, &b1900, false).unwrap().value;
+        assert!(angular_separation(again.ra.radians(), again.dec.radians(), out.ra.radians(), out.dec.radians()) < 1e-6 * ARCSEC_TO_RAD);
+    }
+
+    #[test]
+    fn obliquity_and_nutation() {
+        assert_eq!(mean_obliquity(0.0), 84381.406 * ARCSEC_TO_RAD);
+        // Meeus example 22.a: 1987 April 10, Δψ = -3.788″, Δε = +9.443″
+        let t: f64 = centuries_since_j2000(2446895.5);
+        let (dpsi, deps): (f64, f64) = nutation(t);
+        assert!((dpsi / ARCSEC_TO_RAD + 3.788).abs() < 0.5, "got {}", dpsi / ARCSEC_TO_RAD);
+        assert!((deps / ARCSEC_TO_RAD - 9.443).abs() < 0.5, "got {}", deps / ARCSEC_TO_RAD);
+    }
+
+    #[test]
+    fn matrices_are_rotations() {
+        assert_eq!(precession_angles(0.0).2, 0.0);
+        for m in [precession_matrix(0.25), nutation_matrix(0.25), epoch_matrix(J2000_JD, 2460000.5, true)] {
+            let identity: [[f64; 3]; 3] = mat_mul(&m, &transpose(&m));
+            for (i, row) in identity.iter().enumerate() {
+                for (j, cell) in row.iter().enumerate() {
+                    assert!((cell - if i == j { 1.0 } else { 0.0 }).abs() < 1e-14);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn precess_coord_matches_raw() {
+        let coord: EquatorialCoord = EquatorialCoord::new(Angle::from_degrees(41.054063), Angle::from_degrees(49.227750));
+        let result: Derived<EquatorialCoord> = precess_coord(J2000_JD, 2462088.69, true, &coord, true);
+        let (ra, dec) = precess(J2000_JD, 2462088.69, true, coord.ra.radians(), coord.dec.radians());
+        assert!((result.value.ra.radians() - ra).abs() < 1e-15);
+        assert!((result.value.dec.radians() - dec).abs() < 1e-15);
+        assert!(result.trace.is_some());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::export;
+use crate::trace::{Step, Trace};
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use unicode_width::UnicodeWidthStr;
+
+pub fn strip_ansi(s: &str) -> String {
+    let ansi_regex: Regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    ansi_regex.replace_all(s, "").to_string()
+}
+
+pub fn gen_box(lines: &[String]) -> String {
+    let max_width: usize = lines.iter()
+        .map(|s: &String| UnicodeWidthStr::width(strip_ansi(s).as_str()))
+        .max()
+        .unwrap_or(0);
+
+    let mut output: String = String::new();
+    output.push_str(&format!("┏{}┓\n", "━".repeat(max_width)));
+
+    for line in lines {
+        let visible_width: usize = UnicodeWidthStr::width(strip_ansi(line).as_str());
+        let padding: usize = max_width - visible_width;
+        output.push_str(&format!("┃{}{}┃\n", line, " ".repeat(padding)));
+    }
+
+    output.push_str(&format!("┗{}┛", "━".repeat(max_width)));
+    output
+}
+
+// wraps a value in the bold white used for results
+pub fn bold(value: &str) -> String {
+    format!("\x1b[1m\x1b[37m{}\x1b[0m", value)
+}
+
+// the showwork lines for a trace: notes as-is, worked values indented with the result in bold
+pub fn trace_lines(trace: &Trace) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for step in &trace.steps {
+        match step {
+            Step::Note(text) => lines.push(text.clone()),
+            Step::Value { expr, result } => lines.push(format!("\t{} = {}", expr, bold(result))),
+            Step::Matrix(m) => {
+                for row in m.iter() {
+                    lines.push(format!("\t│ {:>20.16} {:>20.16} {:>20.16} │", row[0], row[1], row[2]));
+                }
+            }
+        }
+    }
+    lines
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    // result lines in a box with bold values, as on a terminal
+    Box,
+    // the same lines without the box or ANSI escapes
+    Plain,
+    Json,
+    Csv,
+    // the derivation as a LaTeX align* block, or a Markdown document with $$ math
+    Latex,
+    Markdown,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name.trim().to_lowercase().as_str() {
+            "box" => Some(Format::Box),
+            "plain" | "text" => Some(Format::Plain),
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "latex" | "tex" => Some(Format::Latex),
+            "markdown" | "md" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+
+    // latex and markdown are exports of the working, so they always show it
+    pub fn shows_work(self) -> bool {
+        matches!(self, Format::Latex | Format::Markdown)
+    }
+}
+
+// what a command prints: the echoed inputs and derivation when showing work, the lines of the
+// box, and the same results as named values for json and csv
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub inputs: Vec<String>,
+    pub trace: Option<Trace>,
+    pub lines: Vec<String>,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Report {
+    pub fn input(&mut self, text: String) {
+        self.inputs.push(text);
+    }
+
+    pub fn line(&mut self, text: String) {
+        self.lines.push(text);
+    }
+
+    pub fn field(&mut self, name: &str, value: impl Into<Value>) {
+        self.fields.push((name.to_string(), value.into()));
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        let mut work: Vec<String> = self.inputs.clone();
+        if let Some(trace) = &self.trace {
+            work.extend(trace_lines(trace));
+        }
+        match format {
+            Format::Box => {
+                let mut out: String = work.iter().map(|l: &String| format!("{}\n", l)).collect();
+                out.push_str(&gen_box(&self.lines));
+                out
+            }
+            Format::Plain => work.iter().chain(&self.lines).map(|l: &String| format!("{}\n", strip_ansi(l))).collect(),
+            Format::Json => {
+                let mut object: Map<String, Value> = self.fields.iter().cloned().collect();
+                if !self.inputs.is_empty() || self.trace.is_some() {
+                    let mut steps: Vec<Value> = self.inputs.iter().map(|i: &String| json!({ "note": i })).collect();
+                    if let Some(trace) = &self.trace {
+                        steps.extend(trace.steps.iter().map(step_json));
+                    }
+                    object.insert("steps".to_string(), Value::Array(steps));
+                }
+                format!("{}\n", serde_json::to_string_pretty(&Value::Object(object)).unwrap_or_default())
+            }
+            Format::Csv => {
+                let header: Vec<&str> = self.fields.iter().map(|(name, _)| name.as_str()).collect();
+                let row: Vec<String> = self.fields.iter().map(|(_, value)| csv_field(value)).collect();
+                csv_lines(&[header.iter().map(|h: &&str| h.to_string()).collect(), row])
+            }
+            Format::Latex => export::latex(self),
+            Format::Markdown => export::markdown(self),
+        }
+    }
+}
+
+// a step as a json object: {"note"}, {"expr", "result"} or {"matrix"}
+pub fn step_json(step: &Step) -> Value {
+    match step {
+        Step::Note(text) => json!({ "note": text }),
+        Step::Value { expr, result } => json!({ "expr": expr, "result": result }),
+        Step::Matrix(m) => json!({ "matrix": m }),
+    }
+}
+
+// an error the way the format would print a result
+pub fn render_error(format: Format, message: &str) -> String {
+    match format {
+        Format::Box => gen_box(&message.lines().map(String::from).collect::<Vec<String>>()),
+        Format::Plain | Format::Markdown => format!("{}\n", message),
+        Format::Latex => format!("% {}\n", message.replace('\n', "\n% ")),
+        Format::Json => format!("{}\n", serde_json::to_string_pretty(&json!({ "error": message })).unwrap_or_default()),
+        Format::Csv => csv_lines(&[vec!["error".to_string()], vec![message.to_string()]]),
+    }
+}
+
+fn csv_field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn csv_lines(records: &[Vec<String>]) -> String {
+    let mut writer: csv::Writer<Vec<u8>> = csv::Writer::from_writer(Vec::new());
+    for record in records {
+        // writing to a Vec can't fail
+        writer.write_record(record).unwrap_or_default();
+    }
+    String::from_utf8(writer.into_inner().unwrap_or_default()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_pads_to_visible_width() {
+        let out: String = gen_box(&[bold("ab"), "abcd".to_string()]);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "┏━━━━┓");
+        assert_eq!(strip_ansi(lines[1]), "┃ab  ┃");
+        assert_eq!(lines[2], "┃abcd┃");
+        assert_eq!(lines[3], "┗━━━━┛");
+    }
+
+    #[test]
+    fn trace_rendering() {
+        let mut trace: Trace = Trace::default();
+        trace.note("heading".to_string());
+        trace.value("x".to_string(), "1".to_string());
+        let lines: Vec<String> = trace_lines(&trace);
+        assert_eq!(lines[0], "heading");
+        assert_eq!(strip_ansi(&lines[1]), "\tx = 1");
+    }
+
+    fn report() -> Report {
+        let mut trace: Trace = Trace::default();
+        trace.value("x".to_string(), "1".to_string());
+        let mut report: Report = Report { trace: Some(trace), ..Report::default() };
+        report.input("a = 2".to_string());
+        report.line(format!("θ = {}", bold("3″")));
+        report.field("theta_arcsec", 3.0);
+        report.field("name", "a, b");
+        report.field("missing", None::<f64>);
+        report
+    }
+
+    #[test]
+    fn report_formats() {
+        let report: Report = report();
+        let boxed: String = report.render(Format::Box);
+        assert!(boxed.starts_with("a = 2\n\tx = "));
+        assert!(boxed.ends_with("┗━━━━━━┛"));
+        assert_eq!(report.render(Format::Plain), "a = 2\n\tx = 1\nθ = 3″\n");
+        assert_eq!(report.render(Format::Csv), "theta_arcsec,name,missing\n3.0,\"a, b\",\n");
+        let json: Value = serde_json::from_str(&report.render(Format::Json)).unwrap();
+        assert_eq!(json["theta_arcsec"], 3.0);
+        assert_eq!(json["missing"], Value::Null);
+        assert_eq!(json["steps"][0]["note"], "a = 2");
+        assert_eq!(json["steps"][1]["result"], "1");
+        // no work asked for, no steps key
+        let quiet: Report = Report { inputs: Vec::new(), trace: None, ..report };
+        assert!(!quiet.render(Format::Json).contains("steps"));
+    }
+
+    #[test]
+    fn errors_follow_the_format() {
+        assert_eq!(render_error(Format::Plain, "bad"), "bad\n");
+        assert_eq!(render_error(Format::Csv, "bad"), "error\nbad\n");
+        assert_eq!(render_error(Format::Json, "bad"), "{\n  \"error\": \"bad\"\n}\n");
+        assert_eq!(render_error(Format::Box, "a\nb").lines().count(), 4);
+        assert_eq!(Format::parse("JSON"), Some(Format::Json));
+        assert_eq!(Format::parse("xml"), None);
+    }
+}
@@ -0,0 +1,185 @@
+// CSV/TSV input and CSV/JSON output for the batch commands
+
+use crate::error::Error;
+use std::io::Read;
+
+pub fn read_input(path: Option<&str>) -> Result<String, Error> {
+    match path {
+        None | Some("-") => {
+            let mut data: String = String::new();
+            std::io::stdin()
+                .read_to_string(&mut data)
+                .map_err(|e| Error::Io(format!("could not read stdin: {}", e)))?;
+            Ok(data)
+        }
+        Some(path) => std::fs::read_to_string(path).map_err(|e| Error::Io(format!("could not read {}: {}", path, e))),
+    }
+}
+
+// reads a CSV/TSV table. the delimiter is a tab if the header has one, otherwise a comma,
+// unless one is given
+pub fn read_table(data: &str, delimiter: Option<&str>) -> Result<(Vec<String>, Vec<Vec<String>>), Error> {
+    let delimiter: u8 = match delimiter {
+        Some("tab") | Some("\\t") | Some("\t") => b'\t',
+        Some(d) if d.len() == 1 => d.as_bytes()[0],
+        Some(d) => return Err(Error::Usage(format!("invalid delimiter: {}", d))),
+        None if data.lines().next().unwrap_or("").contains('\t') => b'\t',
+        None => b',',
+    };
+    let mut reader: csv::Reader<&[u8]> = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| Error::Table(format!("could not read header: {}", e)))?
+        .iter()
+        .map(|h: &str| h.to_string())
+        .collect();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for record in reader.records() {
+        let record: csv::StringRecord = record.map_err(|e| Error::Table(format!("could not read row: {}", e)))?;
+        rows.push(record.iter().map(|f: &str| f.to_string()).collect());
+    }
+    Ok((headers, rows))
+}
+
+pub fn find_column(headers: &[String], name: &str) -> Result<usize, Error> {
+    headers
+        .iter()
+        .position(|h: &String| h.eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error::Table(format!("no column named '{}' in header: {}", name, headers.join(", "))))
+}
+
+// the input columns plus the computed ones, as CSV or a JSON array of objects.
+// computed values are None for rows that failed, with the reason in the error column
+pub fn render_table(format: &str, headers: &[String], rows: &[Vec<String>], extra: &[&str], values: &[Result<Vec<f64>, String>]) -> Result<String, Error> {
+    match format {
+        "csv" => {
+            let mut writer: csv::Writer<Vec<u8>> = csv::Writer::from_writer(Vec::new());
+            let mut header: Vec<&str> = headers.iter().map(|h: &String| h.as_str()).collect();
+            header.extend_from_slice(extra);
+            header.push("error");
+            writer.write_record(&header).map_err(|e| Error::Io(e.to_string()))?;
+            for (row, value) in rows.iter().zip(values) {
+                let mut record: Vec<String> = row.clone();
+                record.resize(headers.len(), String::new());
+                match value {
+                    Ok(v) => {
+                        record.extend(v.iter().map(|x: &f64| x.to_string()));
+                        record.push(String::new());
+                    }
+                    Err(e) => {
+                        record.extend(extra.iter().map(|_| String::new()));
+                        record.push(e.clone());
+                    }
+                }
+                writer.write_record(&record).map_err(|e| Error::Io(e.to_string()))?;
+            }
+            let bytes: Vec<u8> = writer.into_inner().map_err(|e| Error::Io(e.to_string()))?;
+            String::from_utf8(bytes).map_err(|e| Error::Io(e.to_string()))
+        }
+        "json" => {
+            let mut out: Vec<serde_json::Value> = Vec::new();
+            for (row, value) in rows.iter().zip(values) {
+                let mut object: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
+                for (i, header) in headers.iter().enumerate() {
+                    let field: String = row.get(i).cloned().unwrap_or_default();
+                    object.insert(header.clone(), serde_json::Value::String(field));
+                }
+                for (i, name) in extra.iter().enumerate() {
+                    let v: serde_json::Value = match value {
+                        Ok(v) => serde_json::json!(v[i]),
+                        Err(_) => serde_json::Value::Null,
+                    };
+                    object.insert(name.to_string(), v);
+                }
+                let error: serde_json::Value = match value {
+                    Ok(_) => serde_json::Value::Null,
+                    Err(e) => serde_json::Value::String(e.clone()),
+                };
+                object.insert("error".to_string(), error);
+                out.push(serde_json::Value::Object(object));
+            }
+            let text: String = serde_json::to_string_pretty(&out).map_err(|e| Error::Io(e.to_string()))?;
+            Ok(text + "\n")
+        }
+        _ => Err(Error::Usage(format!("invalid output format: {}, use csv | json", format))),
+    }
+}
+
+// named values for each of several results, as CSV with the names as the header or a JSON
+// array of objects
+pub fn render_records(format: &str, records: &[Vec<(String, serde_json::Value)>]) -> Result<String, Error> {
+    match format {
+        "csv" => {
+            let mut writer: csv::Writer<Vec<u8>> = csv::Writer::from_writer(Vec::new());
+            if let Some(first) = records.first() {
+                writer.write_record(first.iter().map(|(name, _)| name.as_str())).map_err(|e| Error::Io(e.to_string()))?;
+            }
+            for record in records {
+                let fields: Vec<String> = record
+                    .iter()
+                    .map(|(_, value)| match value {
+                        serde_json::Value::Null => String::new(),
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect();
+                writer.write_record(&fields).map_err(|e| Error::Io(e.to_string()))?;
+            }
+            let bytes: Vec<u8> = writer.into_inner().map_err(|e| Error::Io(e.to_string()))?;
+            String::from_utf8(bytes).map_err(|e| Error::Io(e.to_string()))
+        }
+        "json" => {
+            let out: Vec<serde_json::Value> = records
+                .iter()
+                .map(|record| serde_json::Value::Object(record.iter().cloned().collect()))
+                .collect();
+            let text: String = serde_json::to_string_pretty(&out).map_err(|e| Error::Io(e.to_string()))?;
+            Ok(text + "\n")
+        }
+        _ => Err(Error::Usage(format!("invalid output format: {}, use csv | json", format))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_reading() {
+        let (headers, rows) = read_table("name\tRA1\tdec1\n\"a b\"\t05:34:31.9\t+22:00:52\n", None).unwrap();
+        assert_eq!(headers, ["name", "RA1", "dec1"]);
+        assert_eq!(rows[0], ["a b", "05:34:31.9", "+22:00:52"]);
+        assert_eq!(find_column(&headers, "ra1").unwrap(), 1);
+        assert!(find_column(&headers, "ra2").is_err());
+
+        let (_, rows) = read_table("x;y\n1;2\n", Some(";")).unwrap();
+        assert_eq!(rows[0], ["1", "2"]);
+        assert!(read_table("x\n", Some("ab")).is_err());
+    }
+
+    #[test]
+    fn table_rendering() {
+        let headers: Vec<String> = vec!["name".to_string()];
+        let rows: Vec<Vec<String>> = vec![vec!["a".to_string()], vec!["b".to_string()]];
+        let values: Vec<Result<Vec<f64>, String>> = vec![Ok(vec![1.5]), Err("bad".to_string())];
+        let csv: String = render_table("csv", &headers, &rows, &["x"], &values).unwrap();
+        assert_eq!(csv, "name,x,error\na,1.5,\nb,,bad\n");
+        let json: serde_json::Value = serde_json::from_str(&render_table("json", &headers, &rows, &["x"], &values).unwrap()).unwrap();
+        assert_eq!(json[0]["x"], 1.5);
+        assert_eq!(json[1]["x"], serde_json::Value::Null);
+        assert_eq!(json[1]["error"], "bad");
+        assert!(render_table("xml", &headers, &rows, &["x"], &values).is_err());
+
+        let records: Vec<Vec<(String, serde_json::Value)>> = vec![
+            vec![("name".to_string(), "a".into()), ("x".to_string(), 1.5.into())],
+            vec![("name".to_string(), "b, c".into()), ("x".to_string(), serde_json::Value::Null)],
+        ];
+        assert_eq!(render_records("csv", &records).unwrap(), "name,x\na,1.5\n\"b, c\",\n");
+        let json: serde_json::Value = serde_json::from_str(&render_records("json", &records).unwrap()).unwrap();
+        assert_eq!(json[1]["name"], "b, c");
+    }
+}
@@ -0,0 +1,360 @@
+// civil dates, Julian dates, UTC/TAI/TT and sidereal time
+
+use crate::angle::Angle;
+use crate::precession::{centuries_since_j2000, ARCSEC_TO_RAD, J2000_JD};
+use crate::trace::{Derived, Trace};
+use crate::PI;
+
+pub const MJD_OFFSET: f64 = 2400000.5;
+pub const TT_MINUS_TAI: f64 = 32.184;
+
+// (MJD the offset starts applying, TAI - UTC in seconds), from IERS Bulletin C.
+// update when a new leap second is announced
+pub const LEAP_SECONDS: [(i64, f64); 28] = [
+    (41317, 10.0),
+    (41499, 11.0),
+    (41683, 12.0),
+    (42048, 13.0),
+    (42413, 14.0),
+    (42778, 15.0),
+    (43144, 16.0),
+    (43509, 17.0),
+    (43874, 18.0),
+    (44239, 19.0),
+    (44786, 20.0),
+    (45151, 21.0),
+    (45516, 22.0),
+    (46247, 23.0),
+    (47161, 24.0),
+    (47892, 25.0),
+    (48257, 26.0),
+    (48804, 27.0),
+    (49169, 28.0),
+    (49534, 29.0),
+    (50083, 30.0),
+    (50630, 31.0),
+    (51179, 32.0),
+    (53736, 33.0),
+    (54832, 34.0),
+    (56109, 35.0),
+    (57204, 36.0),
+    (57754, 37.0),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeScale {
+    Utc,
+    Tai,
+    Tt,
+}
+
+impl TimeScale {
+    pub fn parse(name: &str) -> Option<TimeScale> {
+        match name.to_lowercase().as_str() {
+            "utc" => Some(TimeScale::Utc),
+            "tai" => Some(TimeScale::Tai),
+            "tt" | "td" | "tdt" => Some(TimeScale::Tt),
+            _ => None,
+        }
+    }
+}
+
+pub fn jd_now() -> Result<f64, String> {
+    let unix: f64 = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|_| "system clock is before 1970".to_string())?
+        .as_secs_f64();
+    Ok(unix / 86400.0 + 2440587.5)
+}
+
+// Gregorian calendar date (Julian calendar before 1582-10-15) to Julian date, Meeus ch. 7
+pub fn civil_to_jd(year: i64, month: i64, day: f64) -> f64 {
+    let (y, m): (i64, i64) = if month <= 2 { (year - 1, month + 12) } else { (year, month) };
+    let gregorian: bool = (year, month, day) >= (1582, 10, 15.0);
+    let b: i64 = if gregorian {
+        let a: i64 = y.div_euclid(100);
+        2 - a + a.div_euclid(4)
+    } else {
+        0
+    };
+    (365.25 * (y + 4716) as f64).floor() + (30.6001 * (m + 1) as f64).floor() + day + b as f64 - 1524.5
+}
+
+// length of a month, with the Julian calendar's leap years before 1582 as civil_to_jd has them
+pub fn days_in_month(year: i64, month: i64) -> i64 {
+    let leap: bool = if year > 1582 {
+        (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+    } else {
+        year.rem_euclid(4) == 0
+    };
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Julian date to (year, month, day, hour, minute, second) rounded to the millisecond
+pub fn jd_to_civil(jd: f64) -> (i64, i64, i64, i64, i64, f64) {
+    let ms: i64 = ((jd + 0.5) * 86400000.0).round() as i64;
+    let z: i64 = ms.div_euclid(86400000);
+    let ms_of_day: i64 = ms.rem_euclid(86400000);
+    let a: i64 = if z < 2299161 {
+        z
+    } else {
+        let alpha: i64 = ((z as f64 - 1867216.25) / 36524.25).floor() as i64;
+        z + 1 + alpha - alpha.div_euclid(4)
+    };
+    let b: i64 = a + 1524;
+    let c: i64 = ((b as f64 - 122.1) / 365.25).floor() as i64;
+    let d: i64 = (365.25 * c as f64).floor() as i64;
+    let e: i64 = ((b - d) as f64 / 30.6001).floor() as i64;
+    let day: i64 = b - d - (30.6001 * e as f64).floor() as i64;
+    let month: i64 = if e < 14 { e - 1 } else { e - 13 };
+    let year: i64 = if month > 2 { c - 4716 } else { c - 4715 };
+    (
+        year,
+        month,
+        day,
+        ms_of_day / 3600000,
+        ms_of_day / 60000 % 60,
+        (ms_of_day % 60000) as f64 / 1000.0,
+    )
+}
+
+pub fn format_jd_iso(jd: f64) -> String {
+    let (year, month, day, hour, minute, second) = jd_to_civil(jd);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:06.3}", year, month, day, hour, minute, second)
+}
+
+// accepted forms: 2024-03-20, 2024-03-20T21:15, 2024-03-20 21:15:30.5Z, JD2460390.5,
+// MJD60390, a bare Julian date or now. the scale of the result is whatever the input was in
+pub fn parse_time(input: &str) -> Result<f64, String> {
+    let trimmed: &str = input.trim();
+    let invalid = || format!("invalid time: {}, use e.g. 2024-03-20T21:15:00, JD2460390.5, MJD60390 or now", input);
+    if trimmed.eq_ignore_ascii_case("now") {
+        return jd_now();
+    }
+    let upper: String = trimmed.to_uppercase();
+    if let Some(mjd) = upper.strip_prefix("MJD") {
+        return Ok(mjd.trim().parse::<f64>().map_err(|_| invalid())? + MJD_OFFSET);
+    }
+    if let Some(jd) = upper.strip_prefix("JD") {
+        return jd.trim().parse::<f64>().map_err(|_| invalid());
+    }
+    if let Ok(jd) = trimmed.parse::<f64>() {
+        return Ok(jd);
+    }
+
+    let upper: &str = upper.strip_suffix('Z').unwrap_or(&upper);
+    let (date, time): (&str, &str) = match upper.split_once(['T', ' ']) {
+        Some((date, time)) => (date, time.trim()),
+        None => (upper, ""),
+    };
+    let date_parts: Vec<&str> = date.split('-').collect();
+    if date_parts.len() != 3 {
+        return Err(invalid());
+    }
+    let year: i64 = date_parts[0].parse().map_err(|_| invalid())?;
+    let month: i64 = date_parts[1].parse().map_err(|_| invalid())?;
+    let day: i64 = date_parts[2].parse().map_err(|_| invalid())?;
+
+    let mut clock: [f64; 3] = [0.0; 3];
+    if !time.is_empty() {
+        let time_parts: Vec<&str> = time.split(':').collect();
+        if time_parts.len() > 3 {
+            return Err(invalid());
+        }
+        for (i, part) in time_parts.iter().enumerate() {
+            clock[i] = part.parse().map_err(|_| invalid())?;
+        }
+    }
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        // the Gregorian reform went from 1582-10-04 straight to 1582-10-15
+        || (year == 1582 && month == 10 && (5..15).contains(&day))
+        || !(0.0..24.0).contains(&clock[0])
+        || !(0.0..60.0).contains(&clock[1])
+        || !(0.0..61.0).contains(&clock[2])
+    {
+        return Err(format!("date or time out of range: {}", input));
+    }
+    let day_fraction: f64 = (clock[0] * 3600.0 + clock[1] * 60.0 + clock[2]) / 86400.0;
+    Ok(civil_to_jd(year, month, day as f64 + day_fraction))
+}
+
+// TAI - UTC in seconds. held at 10 s before 1972, when UTC was not yet an integer offset
+pub fn tai_minus_utc(jd_utc: f64) -> f64 {
+    let mjd: i64 = (jd_utc - MJD_OFFSET).floor() as i64;
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(start, _)| mjd >= *start)
+        .map(|(_, offset)| *offset)
+        .unwrap_or(LEAP_SECONDS[0].1)
+}
+
+pub fn utc_to_tai(jd_utc: f64) -> f64 {
+    jd_utc + tai_minus_utc(jd_utc) / 86400.0
+}
+
+pub fn tai_to_utc(jd_tai: f64) -> f64 {
+    // look the offset up twice so dates right after a leap second land on the right side
+    let guess: f64 = jd_tai - tai_minus_utc(jd_tai) / 86400.0;
+    jd_tai - tai_minus_utc(guess) / 86400.0
+}
+
+pub fn utc_to_tt(jd_utc: f64) -> f64 {
+    utc_to_tai(jd_utc) + TT_MINUS_TAI / 86400.0
+}
+
+// Earth rotation angle in radians (IAU 2000), UT1 Julian date
+pub fn earth_rotation_angle(jd_ut1: f64) -> f64 {
+    let du: f64 = jd_ut1 - J2000_JD;
+    // the whole days drop out of 2π·1.0027...·Du, keep them out of the sum for precision
+    (2.0 * PI * (jd_ut1.fract() + 0.7790572732640 + 0.00273781191135448 * du)).rem_euclid(2.0 * PI)
+}
+
+// Greenwich mean sidereal time in radians (IAU 2006). UT1 is taken to be UTC, |UT1 - UTC| < 0.9 s
+pub fn gmst(jd_utc: f64) -> f64 {
+    let t: f64 = centuries_since_j2000(utc_to_tt(jd_utc));
+    let poly: f64 = 0.014506
+        + t * (4612.156534 + t * (1.3915817 + t * (-0.00000044 + t * (-0.000029956 + t * -0.0000000368))));
+    (earth_rotation_angle(jd_utc) + poly * ARCSEC_TO_RAD).rem_euclid(2.0 * PI)
+}
+
+// one instant in every scale the `time` command reports
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instant {
+    // Julian dates
+    pub utc: f64,
+    pub tai: f64,
+    pub tt: f64,
+    // TAI - UTC in seconds
+    pub leap_seconds: f64,
+    pub gmst: Angle,
+    // local mean sidereal time, when a longitude was given
+    pub lst: Option<Angle>,
+}
+
+// a Julian date in the given scale -> the same instant in UTC, TAI and TT plus sidereal
+// time, and the local sidereal time for an east positive longitude
+pub fn time_scales(jd: f64, scale: TimeScale, longitude: Option<Angle>, show_work: bool) -> Derived<Instant> {
+    let mut trace: Option<Trace> = show_work.then(Trace::default);
+    note!(trace, "Julian date of the input");
+    note!(trace, "JD = ⌊365.25(Y + 4716)⌋ + ⌊30.6001(M + 1)⌋ + D + B - 1524.5, B = 2 - ⌊Y/100⌋ + ⌊⌊Y/100⌋/4⌋");
+    value!(trace, ["JD"], ["{}", jd]);
+
+    let (jd_utc, jd_tai): (f64, f64) = match scale {
+        TimeScale::Utc => (jd, utc_to_tai(jd)),
+        TimeScale::Tai => (tai_to_utc(jd), jd),
+        TimeScale::Tt => {
+            let tai: f64 = jd - TT_MINUS_TAI / 86400.0;
+            (tai_to_utc(tai), tai)
+        }
+    };
+    let jd_tt: f64 = jd_tai + TT_MINUS_TAI / 86400.0;
+    let leap: f64 = tai_minus_utc(jd_utc);
+    note!(trace, "Time scales");
+    note!(trace, "TAI = UTC + ΔAT, TT = TAI + 32.184 s");
+    value!(trace, ["ΔAT"], ["{} s (leap second table)", leap]);
+    value!(trace, ["JD(UTC)"], ["{}", jd_utc]);
+    value!(trace, ["JD(TAI)"], ["{}", jd_tai]);
+    value!(trace, ["JD(TT)"], ["{}", jd_tt]);
+
+    let sidereal: f64 = gmst(jd_utc);
+    note!(trace, "Greenwich mean sidereal time (IAU 2006, UT1 ≈ UTC)");
+    note!(trace, "ERA = 2π(0.7790572732640 + 1.00273781191135448 Du), Du = JD(UT1) - 2451545.0");
+    value!(trace, ["Du"], ["{}", jd_utc - J2000_JD]);
+    value!(trace, ["ERA"], ["{} rad", earth_rotation_angle(jd_utc)]);
+    note!(trace, "GMST = ERA + 0.014506″ + 4612.156534″t + 1.3915817″t² - 0.00000044″t³ - 0.000029956″t⁴ - 0.0000000368″t⁵");
+    value!(trace, ["t"], ["{}", centuries_since_j2000(jd_tt)]);
+    value!(trace, ["GMST"], ["{} rad = {}°", sidereal, sidereal * (180.0 / PI)]);
+
+    let lst: Option<Angle> = longitude.map(|longitude: Angle| {
+        let lst: f64 = (sidereal + longitude.radians()).rem_euclid(2.0 * PI);
+        note!(trace, "Local sidereal time");
+        note!(trace, "LST = GMST + λ (east positive)");
+        value!(trace, ["LST = {}° + {}°", sidereal * (180.0 / PI), longitude.degrees()], ["{}°", lst * (180.0 / PI)]);
+        Angle::from_radians(lst)
+    });
+
+    Derived {
+        value: Instant {
+            utc: jd_utc,
+            tai: jd_tai,
+            tt: jd_tt,
+            leap_seconds: leap,
+            gmst: Angle::from_radians(sidereal),
+            lst,
+        },
+        trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn julian_date_round_trip() {
+        assert_eq!(parse_time("2000-01-01T12:00:00").unwrap(), J2000_JD);
+        // Meeus example 7.a, Sputnik 1
+        assert!((civil_to_jd(1957, 10, 4.81) - 2436116.31).abs() < 1e-9);
+        // Meeus example 7.b, the Julian calendar
+        assert_eq!(civil_to_jd(333, 1, 27.5), 1842713.0);
+        assert_eq!(format_jd_iso(2460390.0 + 0.25), "2024-03-20T18:00:00.000");
+        assert_eq!(format_jd_iso(parse_time("MJD60389.5").unwrap()), "2024-03-20T12:00:00.000");
+        assert_eq!(jd_to_civil(2436116.31), (1957, 10, 4, 19, 26, 24.0));
+    }
+
+    #[test]
+    fn time_parsing() {
+        assert_eq!(parse_time("JD2460390.5").unwrap(), 2460390.5);
+        assert_eq!(parse_time("2460390.5").unwrap(), 2460390.5);
+        assert_eq!(parse_time("2024-03-20 21:15:30Z").unwrap(), civil_to_jd(2024, 3, 20.0 + 76530.0 / 86400.0));
+        assert!(parse_time("2024-13-01").is_err());
+        // days past the end of the month, February by the leap year rules, and the days dropped in 1582
+        for date in ["2024-02-30", "2024-04-31", "2023-02-29", "1900-02-29", "1582-10-05", "1582-10-10", "1582-10-14"] {
+            assert!(parse_time(date).is_err(), "{}", date);
+        }
+        assert!(parse_time("2024-02-29").is_ok() && parse_time("2000-02-29").is_ok() && parse_time("1500-02-29").is_ok());
+        assert_eq!(parse_time("1582-10-15").unwrap() - parse_time("1582-10-04").unwrap(), 1.0);
+        assert!(parse_time("2024-03").is_err());
+        assert!(parse_time("yesterday").is_err());
+        assert!(jd_now().unwrap() > 2460000.0);
+        assert_eq!(TimeScale::parse("TDT"), Some(TimeScale::Tt));
+        assert_eq!(TimeScale::parse("gps"), None);
+    }
+
+    #[test]
+    fn leap_second_table() {
+        assert_eq!(tai_minus_utc(parse_time("2016-12-31T23:59:59").unwrap()), 36.0);
+        assert_eq!(tai_minus_utc(parse_time("2017-01-01").unwrap()), 37.0);
+        let utc: f64 = parse_time("2017-01-01T00:00:00.5").unwrap();
+        assert!((tai_to_utc(utc_to_tai(utc)) - utc).abs() * 86400.0 < 1e-4);
+        assert!(((utc_to_tt(J2000_JD) - J2000_JD) * 86400.0 - 64.184).abs() < 1e-4);
+    }
+
+    #[test]
+    fn sidereal_time_meeus() {
+        // Meeus example 12.a: 1987 April 10 0h UT, GMST = 13h10m46.3668s
+        let gmst_seconds: f64 = gmst(civil_to_jd(1987, 4, 10.0)) * (180.0 / PI) / 15.0 * 3600.0;
+        let expected: f64 = 13.0 * 3600.0 + 10.0 * 60.0 + 46.3668;
+        assert!((gmst_seconds - expected).abs() < 0.05, "got {}s", gmst_seconds);
+        // the ERA advances by 2π per sidereal day
+        let day: f64 = 1.0 / (1.0 + 0.00273781191135448);
+        let step: f64 = earth_rotation_angle(J2000_JD + day) - earth_rotation_angle(J2000_JD);
+        assert!(step.abs() < 1e-8, "got {}", step);
+    }
+
+    #[test]
+    fn scales_from_tt() {
+        let instant: Instant = time_scales(J2000_JD, TimeScale::Tt, Some(Angle::from_degrees(90.0)), false).value;
+        assert!(((instant.tt - instant.utc) * 86400.0 - 64.184).abs() < 1e-4);
+        assert_eq!(instant.leap_seconds, 32.0);
+        let lst: f64 = instant.lst.unwrap().radians();
+        assert!((lst - (instant.gmst.radians() + PI / 2.0).rem_euclid(2.0 * PI)).abs() < 1e-12);
+    }
+}
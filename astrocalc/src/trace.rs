@@ -0,0 +1,84 @@
+// step-by-step derivations ("showwork") collected alongside a result
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    // a heading or a formula
+    Note(String),
+    // a worked value, expr = result
+    Value { expr: String, result: String },
+    // a rotation matrix used in the derivation
+    Matrix([[f64; 3]; 3]),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+}
+
+impl Trace {
+    pub fn note(&mut self, text: String) {
+        self.steps.push(Step::Note(text));
+    }
+
+    pub fn value(&mut self, expr: String, result: String) {
+        self.steps.push(Step::Value { expr, result });
+    }
+
+    pub fn matrix(&mut self, m: [[f64; 3]; 3]) {
+        self.steps.push(Step::Matrix(m));
+    }
+}
+
+// a result plus its derivation, when one was asked for
+#[derive(Clone, Debug)]
+pub struct Derived<T> {
+    pub value: T,
+    pub trace: Option<Trace>,
+}
+
+// note!(trace, "θ = cos⁻¹(θ)") adds a line to an Option<Trace> if it is being collected
+macro_rules! note {
+    ($trace:expr, $($arg:tt)*) => {
+        if let Some(t) = $trace.as_mut() {
+            t.note(format!($($arg)*));
+        }
+    };
+}
+
+// value!(trace, ["θ° = {} * 180/π", theta], ["{}°", theta_deg]) adds a worked value
+macro_rules! value {
+    ($trace:expr, [$($expr:tt)*], [$($result:tt)*]) => {
+        if let Some(t) = $trace.as_mut() {
+            t.value(format!($($expr)*), format!($($result)*));
+        }
+    };
+}
+
+macro_rules! matrix {
+    ($trace:expr, $m:expr) => {
+        if let Some(t) = $trace.as_mut() {
+            t.matrix($m);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macros_only_collect_when_tracing() {
+        let mut off: Option<Trace> = None;
+        note!(off, "ignored {}", 1);
+        assert!(off.is_none());
+
+        let mut on: Option<Trace> = Some(Trace::default());
+        note!(on, "θ = {}", 1);
+        value!(on, ["x = {} + {}", 1, 2], ["{}", 3]);
+        matrix!(on, [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        let steps: Vec<Step> = on.unwrap().steps;
+        assert_eq!(steps[0], Step::Note("θ = 1".to_string()));
+        assert_eq!(steps[1], Step::Value { expr: "x = 1 + 2".to_string(), result: "3".to_string() });
+        assert!(matches!(steps[2], Step::Matrix(_)));
+    }
+}